@@ -0,0 +1,99 @@
+//! Calls into native code through the platform's C ABI.
+//!
+//! On the supported targets, integer and float arguments are assigned to separate register
+//! files in order, so every signature the reifier allows can be called through a single
+//! function pointer type that takes the maximum number of each. Unused registers are
+//! simply ignored by the callee.
+
+use std::ffi::c_void;
+#[cfg(unix)]
+use std::ffi::{c_char, c_int, CString};
+
+use crate::lowerer::Kind;
+
+const MAX_INTEGER_ARGS: usize = 6;
+const MAX_FLOAT_ARGS: usize = 8;
+
+#[cfg(unix)]
+extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
+
+#[cfg(unix)]
+const RTLD_NOW: c_int = 2;
+
+/// Finds the function `name` in `library`, or in the program itself if `library` is `None`.
+#[cfg(unix)]
+pub fn resolve(library: Option<&str>, name: &str) -> Option<*const c_void> {
+    let name = CString::new(name).ok()?;
+    let library = library.map(CString::new).transpose().ok()?;
+
+    // SAFETY: both strings are valid and NUL-terminated
+    unsafe {
+        let handle = dlopen(
+            library.as_ref().map_or(std::ptr::null(), |l| l.as_ptr()),
+            RTLD_NOW,
+        );
+        if handle.is_null() {
+            return None;
+        }
+
+        let f = dlsym(handle, name.as_ptr());
+        if f.is_null() {
+            None
+        } else {
+            Some(f as *const c_void)
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn resolve(_: Option<&str>, _: &str) -> Option<*const c_void> {
+    None
+}
+
+/// Calls the native function `f`, returning `None` if the signature has too many parameters.
+///
+/// # Safety
+///
+/// `f` must be a function with the C signature described by `params` and `ret`.
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub unsafe fn call(f: *const c_void, params: &[Kind], ret: Kind, args: &[u64]) -> Option<u64> {
+    type IntegerFn = extern "C" fn(u64, u64, u64, u64, u64, u64, f64, f64, f64, f64, f64, f64, f64, f64) -> u64;
+    type FloatFn = extern "C" fn(u64, u64, u64, u64, u64, u64, f64, f64, f64, f64, f64, f64, f64, f64) -> f64;
+
+    let mut ints = [0; MAX_INTEGER_ARGS];
+    let mut floats = [0.0; MAX_FLOAT_ARGS];
+    let (mut i, mut f_i) = (0, 0);
+    for (&kind, &arg) in params.iter().zip(args) {
+        match kind {
+            Kind::Float => {
+                *floats.get_mut(f_i)? = f64::from_bits(arg);
+                f_i += 1;
+            }
+            Kind::Integer => {
+                *ints.get_mut(i)? = arg;
+                i += 1;
+            }
+        }
+    }
+
+    let [i0, i1, i2, i3, i4, i5] = ints;
+    let [f0, f1, f2, f3, f4, f5, f6, f7] = floats;
+    Some(match ret {
+        Kind::Integer => {
+            let f: IntegerFn = std::mem::transmute(f);
+            f(i0, i1, i2, i3, i4, i5, f0, f1, f2, f3, f4, f5, f6, f7)
+        }
+        Kind::Float => {
+            let f: FloatFn = std::mem::transmute(f);
+            f(i0, i1, i2, i3, i4, i5, f0, f1, f2, f3, f4, f5, f6, f7).to_bits()
+        }
+    })
+}
+
+#[cfg(not(all(unix, any(target_arch = "x86_64", target_arch = "aarch64"))))]
+pub unsafe fn call(_: *const c_void, _: &[Kind], _: Kind, _: &[u64]) -> Option<u64> {
+    None
+}
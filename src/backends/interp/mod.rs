@@ -0,0 +1,704 @@
+//! Executes lowered code directly.
+//!
+//! Every temp holds one 8-byte word; floats are stored as their bit patterns.
+//!
+//! The temps of every running function are kept in [`Machine::frames`], which the garbage
+//! collector scans conservatively for roots.
+
+use std::{
+    ffi::c_void,
+    io::{BufRead, Read, Write},
+    time::Instant,
+};
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    lowerer::*,
+    reifier::{Builtin, Symbol},
+    runtime::Heap,
+    tokenizer::Span,
+};
+
+mod ffi;
+
+/// A host function that externs can be bound to instead of native code.
+///
+/// It returns `None` if the result doesn't match the extern's declared return type.
+pub type HostFn = Box<dyn FnMut(&[u64]) -> Option<u64>>;
+
+/// The tag bit set on every function handle, so they can't be mistaken for heap addresses.
+const FUNCTION_TAG: u64 = 1 << 62;
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    InvalidAddress(u64),
+    InvalidFunction(u64),
+    UndefinedSymbol(Symbol),
+    Arity { expected: usize, found: usize },
+    DivisionByZero,
+    /// An array or string was indexed outside its bounds.
+    IndexOutOfBounds { index: i64, len: u64 },
+    /// Checked arithmetic overflowed, in the operation at this span of the source.
+    Overflow(Span),
+    /// Checked arithmetic divided by zero, in the operation at this span of the source.
+    ZeroDivisor(Span),
+    /// A string was indexed in the middle of a character.
+    NotCharBoundary(i64),
+    /// `atoi` was given a string that isn't an integer.
+    InvalidInteger(String),
+    /// `atof` was given a string that isn't a float.
+    InvalidFloat(String),
+    /// `ftoa_fixed` was given a negative number of digits.
+    InvalidDigits(i64),
+    /// `chr` was given a number that isn't the code point of a character.
+    InvalidCodePoint(i64),
+    /// Reading standard input or writing standard output failed.
+    Io(std::io::ErrorKind),
+    /// The program called `exit` with this status.
+    Exit(i64),
+    /// The program called `abort`.
+    Abort,
+    /// The program called `assert` with a false condition.
+    AssertionFailed,
+    Unsupported(Builtin),
+    /// No host function or native symbol could be found for the extern of this name.
+    UnresolvedExtern(String),
+    /// The signature of the extern of this name can't be called through the C ABI on this
+    /// platform.
+    ExternSignature(String),
+    /// The host function bound to the extern of this name returned the wrong kind of value.
+    ExternResult(String),
+}
+
+type Result<T> = std::result::Result<T, RuntimeError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Callable {
+    Def(Symbol),
+    Builtin(Builtin),
+    Extern(Symbol),
+    Instance(usize),
+    Closure(usize),
+    Ir(*const Cfg),
+}
+
+/// The state of a running program: its heap, and every function value it has seen.
+///
+/// The machine refers to [`Cfg`]s of the module it runs by address, so it must always be
+/// given the same module, and that module must not be modified while the machine is alive.
+#[derive(Default)]
+pub struct Machine {
+    pub heap: Heap,
+    /// The temps of every function that's currently running, innermost last.
+    frames: Vec<Box<[u64]>>,
+    callables: Vec<Callable>,
+    handles: FxHashMap<Callable, u64>,
+    host_fns: FxHashMap<String, HostFn>,
+    natives: FxHashMap<Symbol, *const c_void>,
+    /// The string literals that have been evaluated, by index, which are never freed.
+    literals: FxHashMap<usize, u64>,
+    /// When the program first read the clock, which `clock` counts from.
+    epoch: Option<Instant>,
+    /// Every live specialization, by its words, so that identical ones are shared.
+    specializations: FxHashMap<Box<[u64]>, u64>,
+    /// Values the host holds on to, which are never freed until they're unpinned.
+    pinned: Vec<u64>,
+}
+
+impl Machine {
+    pub fn new() -> Machine {
+        Machine::default()
+    }
+
+    /// The function handle for the def `sym`.
+    pub fn def(&mut self, sym: Symbol) -> u64 {
+        self.intern(Callable::Def(sym))
+    }
+
+    /// Binds every extern named `name` to `f`, instead of looking it up in native code.
+    pub fn define_extern(&mut self, name: &str, f: HostFn) {
+        self.host_fns.insert(name.to_string(), f);
+    }
+
+    /// Looks up the native function of every extern in `module` that isn't bound to a host
+    /// function, so that a missing one is found before any code runs rather than when it's
+    /// first called.
+    pub fn resolve_externs(&mut self, module: &Module) -> Result<()> {
+        let mut externs: Vec<_> = module.externs.iter().collect();
+        externs.sort_by_key(|(_, ext)| ext.name.0);
+        for (&sym, ext) in externs {
+            if self.host_fns.contains_key(ext.name.0) || self.natives.contains_key(&sym) {
+                continue;
+            }
+
+            let f = ffi::resolve(ext.library.map(|l| l.0), ext.name.0)
+                .ok_or_else(|| RuntimeError::UnresolvedExtern(ext.name.0.to_string()))?;
+            self.natives.insert(sym, f);
+        }
+
+        Ok(())
+    }
+
+    /// Keeps whatever `value` points to alive for as long as the machine is, since the host
+    /// holds on to it where the garbage collector can't see.
+    pub fn pin(&mut self, value: u64) {
+        self.pinned.push(value);
+    }
+
+    /// Undoes one [`Machine::pin`] of `value`, so that the garbage collector may free it once
+    /// nothing else refers to it.
+    pub fn unpin(&mut self, value: u64) {
+        if let Some(i) = self.pinned.iter().position(|&pinned| pinned == value) {
+            self.pinned.swap_remove(i);
+        }
+    }
+
+    /// Calls the function value `f` with the already-split arguments `args`.
+    pub fn call(&mut self, module: &Module, f: u64, args: &[u64]) -> Result<u64> {
+        // anything that isn't a handle is a closure, whose body takes the closure itself
+        // before its own arguments
+        if f < FUNCTION_TAG {
+            let body = self.heap.load(f).ok_or(RuntimeError::InvalidFunction(f))?;
+            let mut full_args = Vec::with_capacity(args.len() + 1);
+            full_args.push(f);
+            full_args.extend_from_slice(args);
+            return self.call(module, body, &full_args);
+        }
+
+        let callable = f
+            .checked_sub(FUNCTION_TAG)
+            .and_then(|i| self.callables.get(i as usize))
+            .copied()
+            .ok_or(RuntimeError::InvalidFunction(f))?;
+
+        match callable {
+            Callable::Def(sym) => match module.defs.get(&sym) {
+                Some(Def {
+                    value: Value::Function(cfg),
+                    ..
+                }) => self.run(module, cfg, args),
+                _ => Err(RuntimeError::UndefinedSymbol(sym)),
+            },
+            Callable::Builtin(builtin) => self.builtin(builtin, args),
+            Callable::Extern(sym) => self.extern_(module, sym, args),
+            Callable::Instance(i) => match module.instances.get(i) {
+                Some(Def {
+                    value: Value::Function(cfg),
+                    ..
+                }) => self.run(module, cfg, args),
+                _ => Err(RuntimeError::InvalidFunction(f)),
+            },
+            Callable::Closure(i) => match module.closures.get(i) {
+                Some(cfg) => self.run(module, cfg, args),
+                None => Err(RuntimeError::InvalidFunction(f)),
+            },
+            // SAFETY: the module outlives the machine and is never modified
+            Callable::Ir(cfg) => self.run(module, unsafe { &*cfg }, args),
+        }
+    }
+
+    fn builtin(&mut self, builtin: Builtin, args: &[u64]) -> Result<u64> {
+        let params = builtin.info().params.len();
+        if args.len() != params {
+            return Err(RuntimeError::Arity {
+                expected: params,
+                found: args.len(),
+            });
+        }
+
+        match (builtin, args) {
+            (Builtin::Alloc, &[bytes, pointer_map]) => {
+                if self.heap.should_collect() {
+                    self.collect();
+                }
+                Ok(self.heap.alloc(bytes, pointer_map))
+            }
+            (Builtin::Spec, &[spec]) => {
+                // a specialization is called like a closure, whose code and environment
+                // are all of its words, so ones with the same words behave the same
+                let words = self
+                    .heap
+                    .object_words(spec)
+                    .ok_or(RuntimeError::InvalidAddress(spec))?;
+                Ok(*self.specializations.entry(words.into()).or_insert(spec))
+            }
+            (Builtin::Len, &[array]) => self
+                .heap
+                .load(array)
+                .ok_or(RuntimeError::InvalidAddress(array)),
+            (Builtin::OutOfBounds, &[index, len]) => Err(RuntimeError::IndexOutOfBounds {
+                index: index as i64,
+                len,
+            }),
+            (Builtin::Overflow, &[start, end]) => Err(RuntimeError::Overflow(Span {
+                start: start as usize,
+                end: end as usize,
+            })),
+            (Builtin::ZeroDivisor, &[start, end]) => Err(RuntimeError::ZeroDivisor(Span {
+                start: start as usize,
+                end: end as usize,
+            })),
+            (Builtin::Print | Builtin::Println, &[string]) => {
+                let mut bytes = self.string(string)?;
+                if let Builtin::Println = builtin {
+                    bytes.push(b'\n');
+                }
+                let mut stdout = std::io::stdout().lock();
+                stdout
+                    .write_all(&bytes)
+                    .and_then(|()| stdout.flush())
+                    .map_err(|err| RuntimeError::Io(err.kind()))?;
+                Ok(0)
+            }
+            (Builtin::Itoa, &[i]) => Ok(self.alloc_string((i as i64).to_string().as_bytes())),
+            (Builtin::StrLen, &[string]) => self
+                .heap
+                .load(string)
+                .ok_or(RuntimeError::InvalidAddress(string)),
+            (Builtin::ByteAt, &[string, index]) => {
+                let bytes = self.string(string)?;
+                let i = Self::offset(&bytes, index, false)?;
+                Ok(bytes[i] as u64)
+            }
+            (Builtin::CharAt, &[string, index]) => {
+                let bytes = self.string(string)?;
+                let i = Self::offset(&bytes, index, false)?;
+                let width = match bytes[i] {
+                    0x00..0x80 => 1,
+                    0x80..0xe0 => 2,
+                    0xe0..0xf0 => 3,
+                    _ => 4,
+                };
+                let c = bytes
+                    .get(i..i + width)
+                    .and_then(|bytes| std::str::from_utf8(bytes).ok())
+                    .and_then(|c| c.chars().next())
+                    .ok_or(RuntimeError::NotCharBoundary(index as i64))?;
+                Ok(c as u64)
+            }
+            (Builtin::Slice, &[string, start, end]) => {
+                let bytes = self.string(string)?;
+                let start = Self::offset(&bytes, start, true)?;
+                if (end as i64) < start as i64 {
+                    return Err(RuntimeError::IndexOutOfBounds {
+                        index: end as i64,
+                        len: bytes.len() as u64,
+                    });
+                }
+                let end = Self::offset(&bytes, end, true)?;
+                Ok(self.alloc_string(&bytes[start..end]))
+            }
+            (Builtin::Concat, &[a, b]) => {
+                let mut bytes = self.string(a)?;
+                bytes.extend(self.string(b)?);
+                Ok(self.alloc_string(&bytes))
+            }
+            (Builtin::Compare, &[a, b]) => {
+                let ordering = self.string(a)?.cmp(&self.string(b)?);
+                Ok(ordering as i64 as u64)
+            }
+            (Builtin::Atoi, &[string]) => {
+                let bytes = self.string(string)?;
+                std::str::from_utf8(&bytes)
+                    .ok()
+                    .and_then(|s| s.parse::<i64>().ok())
+                    .map(|i| i as u64)
+                    .ok_or_else(|| {
+                        RuntimeError::InvalidInteger(String::from_utf8_lossy(&bytes).into_owned())
+                    })
+            }
+            (Builtin::Chr, &[code_point]) => {
+                let c = u32::try_from(code_point as i64)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or(RuntimeError::InvalidCodePoint(code_point as i64))?;
+                Ok(self.alloc_string(c.encode_utf8(&mut [0; 4]).as_bytes()))
+            }
+            (Builtin::Join, &[array, separator]) => {
+                let separator = self.string(separator)?;
+                let len = self
+                    .heap
+                    .load(array)
+                    .ok_or(RuntimeError::InvalidAddress(array))?;
+                let mut bytes = Vec::new();
+                for i in 0..len {
+                    if i > 0 {
+                        bytes.extend_from_slice(&separator);
+                    }
+                    let addr = array + 8 * (i + 1);
+                    let item = self
+                        .heap
+                        .load(addr)
+                        .ok_or(RuntimeError::InvalidAddress(addr))?;
+                    bytes.extend(self.string(item)?);
+                }
+                Ok(self.alloc_string(&bytes))
+            }
+            (Builtin::Exit, &[status]) => Err(RuntimeError::Exit(status as i64)),
+            (Builtin::Abort, &[]) => Err(RuntimeError::Abort),
+            (Builtin::Assert, &[cond]) => match cond {
+                0 => Err(RuntimeError::AssertionFailed),
+                _ => Ok(0),
+            },
+            (Builtin::Itof, &[i]) => Ok((i as i64 as f64).to_bits()),
+            (Builtin::Ftoi, &[f]) => Ok(f64::from_bits(f) as i64 as u64),
+            (Builtin::Ftoa, &[f]) => {
+                // debug formatting always shows that it's a float, like `1.0`
+                Ok(self.alloc_string(format!("{:?}", f64::from_bits(f)).as_bytes()))
+            }
+            (Builtin::Atof, &[string]) => {
+                let bytes = self.string(string)?;
+                std::str::from_utf8(&bytes)
+                    .ok()
+                    .and_then(|s| s.parse::<f64>().ok())
+                    .map(f64::to_bits)
+                    .ok_or_else(|| {
+                        RuntimeError::InvalidFloat(String::from_utf8_lossy(&bytes).into_owned())
+                    })
+            }
+            (Builtin::FtoaFixed, &[f, digits]) => {
+                let Ok(digits) = usize::try_from(digits as i64) else {
+                    return Err(RuntimeError::InvalidDigits(digits as i64));
+                };
+                Ok(self.alloc_string(format!("{:.*}", digits, f64::from_bits(f)).as_bytes()))
+            }
+            (Builtin::Sqrt, &[f]) => Ok(f64::from_bits(f).sqrt().to_bits()),
+            (Builtin::Floor, &[f]) => Ok(f64::from_bits(f).floor().to_bits()),
+            (Builtin::Ceil, &[f]) => Ok(f64::from_bits(f).ceil().to_bits()),
+            (Builtin::Round, &[f]) => Ok(f64::from_bits(f).round().to_bits()),
+            (Builtin::Pow, &[a, b]) => Ok(f64::from_bits(a).powf(f64::from_bits(b)).to_bits()),
+            (Builtin::Exp, &[f]) => Ok(f64::from_bits(f).exp().to_bits()),
+            (Builtin::Log, &[f]) => Ok(f64::from_bits(f).ln().to_bits()),
+            (Builtin::Sin, &[f]) => Ok(f64::from_bits(f).sin().to_bits()),
+            (Builtin::Cos, &[f]) => Ok(f64::from_bits(f).cos().to_bits()),
+            (Builtin::Tan, &[f]) => Ok(f64::from_bits(f).tan().to_bits()),
+            (Builtin::Asin, &[f]) => Ok(f64::from_bits(f).asin().to_bits()),
+            (Builtin::Acos, &[f]) => Ok(f64::from_bits(f).acos().to_bits()),
+            (Builtin::Atan, &[f]) => Ok(f64::from_bits(f).atan().to_bits()),
+            (Builtin::Atan2, &[y, x]) => {
+                Ok(f64::from_bits(y).atan2(f64::from_bits(x)).to_bits())
+            }
+            (Builtin::IsNan, &[f]) => Ok(f64::from_bits(f).is_nan() as u64),
+            (Builtin::Input, &[]) => {
+                let mut bytes = Vec::new();
+                std::io::stdin()
+                    .lock()
+                    .read_to_end(&mut bytes)
+                    .map_err(|err| RuntimeError::Io(err.kind()))?;
+                Ok(self.alloc_string(String::from_utf8_lossy(&bytes).as_bytes()))
+            }
+            (Builtin::ReadLine, &[]) => {
+                let mut bytes = Vec::new();
+                std::io::stdin()
+                    .lock()
+                    .read_until(b'\n', &mut bytes)
+                    .map_err(|err| RuntimeError::Io(err.kind()))?;
+                if bytes.ends_with(b"\n") {
+                    bytes.pop();
+                    if bytes.ends_with(b"\r") {
+                        bytes.pop();
+                    }
+                }
+                Ok(self.alloc_string(String::from_utf8_lossy(&bytes).as_bytes()))
+            }
+            (Builtin::Clock, &[]) => {
+                let epoch = *self.epoch.get_or_insert_with(Instant::now);
+                Ok(epoch.elapsed().as_nanos() as u64)
+            }
+            (
+                Builtin::ToI8
+                | Builtin::ToI16
+                | Builtin::ToI32
+                | Builtin::ToInt
+                | Builtin::ToU8
+                | Builtin::ToU16
+                | Builtin::ToU32
+                | Builtin::ToU64,
+                &[i],
+            ) => Ok(builtin.int_conversion().unwrap().wrap(i)),
+            _ => Err(RuntimeError::Unsupported(builtin)),
+        }
+    }
+
+    /// The bytes of the string at `addr`.
+    fn string(&self, addr: u64) -> Result<Vec<u8>> {
+        self.heap
+            .string(addr)
+            .ok_or(RuntimeError::InvalidAddress(addr))
+    }
+
+    fn alloc_string(&mut self, bytes: &[u8]) -> u64 {
+        if self.heap.should_collect() {
+            self.collect();
+        }
+        self.heap.alloc_string(bytes)
+    }
+
+    /// Checks that `index` is the offset of a character in the string `bytes`, or of its
+    /// end if `allow_end` is set.
+    fn offset(bytes: &[u8], index: u64, allow_end: bool) -> Result<usize> {
+        let len = bytes.len() as u64;
+        if index > len || (index == len && !allow_end) {
+            return Err(RuntimeError::IndexOutOfBounds {
+                index: index as i64,
+                len,
+            });
+        }
+
+        let i = index as usize;
+        match bytes.get(i) {
+            // continuation bytes all start with 0b10
+            Some(b) if b & 0xc0 == 0x80 => Err(RuntimeError::NotCharBoundary(index as i64)),
+            _ => Ok(i),
+        }
+    }
+
+    fn extern_(&mut self, module: &Module, sym: Symbol, args: &[u64]) -> Result<u64> {
+        let ext = module
+            .externs
+            .get(&sym)
+            .ok_or(RuntimeError::UndefinedSymbol(sym))?;
+
+        if args.len() != ext.params.len() {
+            return Err(RuntimeError::Arity {
+                expected: ext.params.len(),
+                found: args.len(),
+            });
+        }
+
+        if let Some(f) = self.host_fns.get_mut(ext.name.0) {
+            return f(args).ok_or_else(|| RuntimeError::ExternResult(ext.name.0.to_string()));
+        }
+
+        // externs that aren't bound to host functions are resolved before the program runs
+        let Some(&f) = self.natives.get(&sym) else {
+            return Err(RuntimeError::UnresolvedExtern(ext.name.0.to_string()));
+        };
+
+        // SAFETY: the extern's declaration is trusted to match the native function
+        unsafe { ffi::call(f, &ext.params, ext.ret, args) }
+            .ok_or_else(|| RuntimeError::ExternSignature(ext.name.0.to_string()))
+    }
+
+    /// Frees every heap object that running code can no longer reach.
+    pub fn collect(&mut self) {
+        let frames = self.frames.iter().flat_map(|frame| frame.iter().copied());
+        let literals = self.literals.values().copied();
+        let pinned = self.pinned.iter().copied();
+
+        self.heap.collect(frames.chain(literals).chain(pinned));
+
+        // specializations are only shared while something else still refers to them
+        let heap = &self.heap;
+        self.specializations
+            .retain(|_, &mut spec| heap.object_words(spec).is_some());
+    }
+
+    fn run(&mut self, module: &Module, cfg: &Cfg, args: &[u64]) -> Result<u64> {
+        if args.len() != cfg.params.len() {
+            return Err(RuntimeError::Arity {
+                expected: cfg.params.len(),
+                found: args.len(),
+            });
+        }
+
+        let mut temps = vec![0; cfg.temps].into_boxed_slice();
+        for (param, &arg) in cfg.params.iter().zip(args) {
+            temps[param.idx] = arg;
+        }
+
+        let frame = self.frames.len();
+        self.frames.push(temps);
+        let result = self.execute(module, cfg, frame);
+        self.frames.truncate(frame);
+
+        result
+    }
+
+    fn execute(&mut self, module: &Module, cfg: &Cfg, frame: usize) -> Result<u64> {
+        let mut current = cfg.entry;
+        loop {
+            let block = &cfg.blocks[current.0];
+
+            for insn in &*block.insns {
+                match insn {
+                    Insn::Load(dest, producer) => {
+                        let value = self.produce(module, frame, producer)?;
+                        self.frames[frame][dest.idx] = value;
+                    }
+                    Insn::Store(MemRef(base, offset), source) => {
+                        let temps = &self.frames[frame];
+                        let addr = temps[base.idx].wrapping_add(*offset);
+                        self.heap
+                            .store(addr, temps[source.idx])
+                            .ok_or(RuntimeError::InvalidAddress(addr))?
+                    }
+                }
+            }
+
+            let temps = &mut self.frames[frame];
+
+            if let Some(Branch(cmp, a, b, target)) = &block.branch {
+                if Self::compare(*cmp, *a, *b, temps) {
+                    current = Self::jump(cfg, target, temps);
+                    continue;
+                }
+            }
+
+            match &block.ctrl {
+                Ctrl::Jump(target) => current = Self::jump(cfg, target, temps),
+                Ctrl::Return(temp) => return Ok(temps[temp.idx]),
+            }
+        }
+    }
+
+    fn jump(cfg: &Cfg, target: &Target, temps: &mut [u64]) -> BlockRef {
+        // read all the arguments first, since they may overlap with the params
+        let values: Vec<_> = target.arguments.iter().map(|a| temps[a.idx]).collect();
+        for (param, value) in cfg.blocks[target.block.0].params.iter().zip(values) {
+            temps[param.idx] = value;
+        }
+
+        target.block
+    }
+
+    fn compare(cmp: BranchCmp, a: Temp, b: Temp, temps: &[u64]) -> bool {
+        let (x, y) = (temps[a.idx], temps[b.idx]);
+        if a.kind == Kind::Float {
+            let (x, y) = (f64::from_bits(x), f64::from_bits(y));
+            match cmp {
+                BranchCmp::Eq => x == y,
+                BranchCmp::Neq => x != y,
+                BranchCmp::Lt | BranchCmp::LtU => x < y,
+                BranchCmp::Geq | BranchCmp::GeqU => x >= y,
+            }
+        } else {
+            match cmp {
+                BranchCmp::Eq => x == y,
+                BranchCmp::Neq => x != y,
+                BranchCmp::Lt => (x as i64) < y as i64,
+                BranchCmp::Geq => x as i64 >= y as i64,
+                BranchCmp::LtU => x < y,
+                BranchCmp::GeqU => x >= y,
+            }
+        }
+    }
+
+    fn produce(&mut self, module: &Module, frame: usize, producer: &Producer) -> Result<u64> {
+        let temps = &self.frames[frame];
+        Ok(match producer {
+            Producer::Memory(_, MemRef(base, offset)) => {
+                let addr = temps[base.idx].wrapping_add(*offset);
+                self.heap
+                    .load(addr)
+                    .ok_or(RuntimeError::InvalidAddress(addr))?
+            }
+            &Producer::Symbol(_, sym) => match module.defs.get(&sym) {
+                Some(Def {
+                    value: Value::Function(_),
+                    ..
+                }) => self.def(sym),
+                Some(Def {
+                    value: Value::Integer(i),
+                    ..
+                }) => *i as u64,
+                Some(Def {
+                    value: Value::Float(f),
+                    ..
+                }) => f.to_bits(),
+                _ => return Err(RuntimeError::UndefinedSymbol(sym)),
+            },
+            &Producer::Builtin(builtin) => self.intern(Callable::Builtin(builtin)),
+            &Producer::Extern(sym) => self.intern(Callable::Extern(sym)),
+            &Producer::Instance(i) => self.intern(Callable::Instance(i)),
+            &Producer::Closure(i) => self.intern(Callable::Closure(i)),
+            Producer::Ir(cfg) => self.intern(Callable::Ir(cfg)),
+            Producer::Copy(t) => temps[t.idx],
+            &Producer::Binary(op, a, b) => Self::binary(op, temps[a.idx], temps[b.idx])?,
+            &Producer::Unary(op, a) => Self::unary(op, temps[a.idx]),
+            Producer::Call(f, args, _) => {
+                let args: Vec<_> = args.iter().map(|a| temps[a.idx]).collect();
+                self.call(module, temps[f.idx], &args)?
+            }
+            &Producer::ConstI(i) => i,
+            &Producer::ConstF(f) => f.to_bits(),
+            &Producer::String(i) => match self.literals.get(&i) {
+                Some(&addr) => addr,
+                None => {
+                    let addr = self.alloc_string(module.strings[i].0.as_bytes());
+                    self.literals.insert(i, addr);
+                    addr
+                }
+            },
+        })
+    }
+
+    fn binary(op: BinOp, a: u64, b: u64) -> Result<u64> {
+        let (ai, bi) = (a as i64, b as i64);
+        let (af, bf) = (f64::from_bits(a), f64::from_bits(b));
+
+        Ok(match op {
+            BinOp::BitOrI => a | b,
+            BinOp::BitXorI => a ^ b,
+            BinOp::BitAndI => a & b,
+            BinOp::BitShlI => a.wrapping_shl(b as u32),
+            BinOp::BitShrI => ai.wrapping_shr(b as u32) as u64,
+            BinOp::BitShrU => a.wrapping_shr(b as u32),
+            BinOp::AddI => ai.wrapping_add(bi) as u64,
+            BinOp::SubI => ai.wrapping_sub(bi) as u64,
+            BinOp::MulI => ai.wrapping_mul(bi) as u64,
+            BinOp::MulHiI => ((ai as i128 * bi as i128) >> 64) as u64,
+            BinOp::MulHiU => ((a as u128 * b as u128) >> 64) as u64,
+            BinOp::DivI | BinOp::ModI | BinOp::DivU | BinOp::ModU if b == 0 => {
+                return Err(RuntimeError::DivisionByZero)
+            }
+            BinOp::DivI => ai.wrapping_div(bi) as u64,
+            BinOp::ModI => ai.wrapping_rem(bi) as u64,
+            BinOp::DivU => a / b,
+            BinOp::ModU => a % b,
+            BinOp::EqI => (a == b) as u64,
+            BinOp::NeqI => (a != b) as u64,
+            BinOp::LtI => (ai < bi) as u64,
+            BinOp::LeqI => (ai <= bi) as u64,
+            BinOp::LtU => (a < b) as u64,
+            BinOp::LeqU => (a <= b) as u64,
+            BinOp::AddF => (af + bf).to_bits(),
+            BinOp::SubF => (af - bf).to_bits(),
+            BinOp::MulF => (af * bf).to_bits(),
+            BinOp::DivF => (af / bf).to_bits(),
+            BinOp::EqF => (af == bf) as u64,
+            BinOp::NeqF => (af != bf) as u64,
+            BinOp::LtF => (af < bf) as u64,
+            BinOp::LeqF => (af <= bf) as u64,
+        })
+    }
+
+    fn unary(op: UnOp, a: u64) -> u64 {
+        match op {
+            UnOp::BoolNotI => (a == 0) as u64,
+            UnOp::BitNotI => !a,
+            UnOp::NegI => (a as i64).wrapping_neg() as u64,
+            UnOp::NegF => (-f64::from_bits(a)).to_bits(),
+            UnOp::SignExtend(bits) => ((a << (64 - bits)) as i64 >> (64 - bits)) as u64,
+            UnOp::ZeroExtend(bits) => a << (64 - bits) >> (64 - bits),
+        }
+    }
+
+    /// Like [`Machine::callable`], but reuses the handle if `callable` has one already.
+    fn intern(&mut self, callable: Callable) -> u64 {
+        if let Some(&handle) = self.handles.get(&callable) {
+            handle
+        } else {
+            let handle = self.callable(callable);
+            self.handles.insert(callable, handle);
+            handle
+        }
+    }
+
+    fn callable(&mut self, callable: Callable) -> u64 {
+        let handle = FUNCTION_TAG + self.callables.len() as u64;
+        self.callables.push(callable);
+        handle
+    }
+}
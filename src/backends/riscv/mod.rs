@@ -2,20 +2,23 @@ use crate::lowerer::*;
 
 pub mod regalloc;
 
-pub fn codegen(module: Module) -> Vec<u8> {
+pub fn codegen(_module: Module) -> Vec<u8> {
     todo!()
 }
 
+#[allow(dead_code)]
 struct RvCodegen {
     buf: Vec<u8>,
 }
 
+#[allow(dead_code)]
 impl RvCodegen {
-    fn gen(&mut self, cfg: Cfg) {
+    fn gen(&mut self, _cfg: Cfg) {
 
     }
 }
 
+#[allow(dead_code)]
 enum RvInsn {
 
 }
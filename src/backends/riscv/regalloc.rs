@@ -17,8 +17,8 @@ pub fn dominators(cfg: &Cfg) -> Vec<Option<BlockRef>> {
             let preds = &*preds[b.0];
             let mut new_idom = preds[0];
             for &p in &preds[1..] {
-                if !doms[p.0].is_none() {
-                    new_idom = intersect(&labels, &mut *doms, p, new_idom)
+                if doms[p.0].is_some() {
+                    new_idom = intersect(&labels, &mut doms, p, new_idom)
                 }
             }
             if doms[b.0] != Some(new_idom) {
@@ -31,7 +31,7 @@ pub fn dominators(cfg: &Cfg) -> Vec<Option<BlockRef>> {
     doms
 }
 
-fn intersect(labels: &Vec<usize>, doms: &mut [Option<BlockRef>], b1: BlockRef, b2: BlockRef) -> BlockRef {
+fn intersect(labels: &[usize], doms: &mut [Option<BlockRef>], b1: BlockRef, b2: BlockRef) -> BlockRef {
     let mut finger1 = b1;
     let mut finger2 = b2;
     while finger1 != finger2 {
@@ -0,0 +1,267 @@
+//! The interface for embedding codef in other programs.
+//!
+//! ```ignore
+//! let strings = Strings::new();
+//! let mut program = codef::compile(&strings, "def double(val x :: Int) => x * 2;")?;
+//! let double = program.def("double").unwrap();
+//! let result = program.call(&double, &[Value::Integer(21)])?;
+//! ```
+
+use std::path::Path;
+
+use crate::{
+    backends::interp::{Machine, RuntimeError},
+    errors::ErrorStream,
+    loader::{self, LoadError},
+    lowerer::{self, Kind, Options, Value},
+    reifier::{self, ReifyError, Type},
+    runtime::{Layout, MarshalError},
+    strings::Strings,
+};
+
+#[derive(Debug)]
+pub enum Error<'s> {
+    Load(LoadError<'s>),
+    Reify(ReifyError<'s>),
+    Runtime(RuntimeError),
+    /// The number of arguments given doesn't match the number of parameters.
+    Arity { expected: usize, found: usize },
+    /// The argument at `index` couldn't be converted to the type of its parameter.
+    ArgumentType {
+        index: usize,
+        error: MarshalError<'s>,
+    },
+    /// The result couldn't be converted to a host value.
+    Result(MarshalError<'s>),
+    /// Values of this type can't be passed between codef and the host.
+    UnsupportedType(Type<'s>),
+    /// The program doesn't declare an extern with this name.
+    UnknownExtern(String),
+}
+
+impl<'s> From<LoadError<'s>> for Error<'s> {
+    fn from(err: LoadError<'s>) -> Self {
+        Error::Load(err)
+    }
+}
+
+impl<'s> From<ReifyError<'s>> for Error<'s> {
+    fn from(err: ReifyError<'s>) -> Self {
+        Error::Reify(err)
+    }
+}
+
+impl<'s> From<RuntimeError> for Error<'s> {
+    fn from(err: RuntimeError) -> Self {
+        Error::Runtime(err)
+    }
+}
+
+type Result<'s, T> = std::result::Result<T, Error<'s>>;
+
+/// Compiles `source` into a [`Program`] that's ready to be called into. The modules it uses
+/// are looked up relative to the working directory.
+pub fn compile<'s>(strings: &'s Strings, source: &str) -> Result<'s, Program<'s>> {
+    compile_with(strings, source, Options::default())
+}
+
+/// Like [`compile`], but with other [`Options`] than the defaults.
+pub fn compile_with<'s>(
+    strings: &'s Strings,
+    source: &str,
+    options: Options,
+) -> Result<'s, Program<'s>> {
+    compile_in(strings, source, None, Path::new("."), options)
+}
+
+/// Compiles the file at `path`, along with the modules it uses from its directory.
+pub fn compile_file<'s>(strings: &'s Strings, path: &Path) -> Result<'s, Program<'s>> {
+    compile_file_with(strings, path, Options::default())
+}
+
+/// Like [`compile_file`], but with other [`Options`] than the defaults.
+pub fn compile_file_with<'s>(
+    strings: &'s Strings,
+    path: &Path,
+    options: Options,
+) -> Result<'s, Program<'s>> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|err| LoadError::Io(path.display().to_string(), err))?;
+    let name = path.file_stem().and_then(|stem| stem.to_str());
+    compile_in(strings, &source, name, path.parent().unwrap_or(Path::new(".")), options)
+}
+
+fn compile_in<'s>(
+    strings: &'s Strings,
+    source: &str,
+    name: Option<&str>,
+    dir: &Path,
+    options: Options,
+) -> Result<'s, Program<'s>> {
+    let errors = ErrorStream::new();
+    let sources = loader::load(strings, &errors, source, name, dir)?;
+    let reified = reifier::reify(strings, &errors, &sources)?;
+    let lowered = lowerer::lower(&reified, options);
+
+    Ok(Program {
+        reified,
+        lowered,
+        machine: Machine::new(),
+    })
+}
+
+/// A compiled program, along with the heap that all calls into it share.
+pub struct Program<'s> {
+    reified: reifier::Module<'s>,
+    // never modified after compilation, since the machine refers into it
+    lowered: lowerer::Module<'s>,
+    machine: Machine,
+}
+
+/// A function that can be called by the host, either a `def` or the result of specializing one.
+#[derive(Debug, Clone)]
+pub struct Function<'s> {
+    handle: u64,
+    ty: Type<'s>,
+}
+
+impl<'s> Function<'s> {
+    pub fn ty(&self) -> &Type<'s> {
+        &self.ty
+    }
+}
+
+impl<'s> Program<'s> {
+    /// Looks up the function defined by `def <name>`.
+    pub fn def(&mut self, name: &str) -> Option<Function<'s>> {
+        let (&sym, def) = self
+            .reified
+            .defs
+            .iter()
+            .find(|(sym, def)| def.name.0 == name && self.lowered.defs.contains_key(sym))?;
+
+        Some(Function {
+            handle: self.machine.def(sym),
+            ty: def.body.ty.clone(),
+        })
+    }
+
+    /// Binds the extern declared as `use def <name>` to `f`, so calls to it run host code
+    /// instead of looking for a native function.
+    pub fn define_extern(
+        &mut self,
+        name: &str,
+        mut f: impl FnMut(&[Value<'s>]) -> Value<'s> + 'static,
+    ) -> Result<'s, ()> {
+        let Some(ext) = self.lowered.externs.values().find(|ext| ext.name.0 == name) else {
+            return Err(Error::UnknownExtern(name.to_string()));
+        };
+        let params = ext.params.clone();
+        let ret = ext.ret;
+
+        self.machine.define_extern(
+            name,
+            Box::new(move |words| {
+                let args: Vec<_> = params
+                    .iter()
+                    .zip(words)
+                    .map(|(kind, &word)| match kind {
+                        Kind::Integer => Value::Integer(word as i64),
+                        Kind::Float => Value::Float(f64::from_bits(word)),
+                    })
+                    .collect();
+
+                match (ret, f(&args)) {
+                    (Kind::Integer, Value::Integer(i)) => Some(i as u64),
+                    (Kind::Integer, Value::Boolean(b)) => Some(b as u64),
+                    (Kind::Float, Value::Float(f)) => Some(f.to_bits()),
+                    // externs returning unit return an arbitrary word
+                    (Kind::Integer, Value::Tuple(items)) if items.is_empty() => Some(0),
+                    _ => None,
+                }
+            }),
+        );
+
+        Ok(())
+    }
+
+    /// Calls `function`, which must not return a function; use [`Program::specialize`] for those.
+    pub fn call(&mut self, function: &Function<'s>, args: &[Value<'s>]) -> Result<'s, Value<'s>> {
+        let (result, ret) = self.invoke(function, args)?;
+
+        Layout::new(&self.reified, &self.lowered)
+            .read(&self.machine.heap, result, &ret)
+            .map_err(Error::Result)
+    }
+
+    /// Calls the specializing function `function` (one defined with `$`), returning the
+    /// function it generates.
+    pub fn specialize(
+        &mut self,
+        function: &Function<'s>,
+        args: &[Value<'s>],
+    ) -> Result<'s, Function<'s>> {
+        let (result, ret) = self.invoke(function, args)?;
+
+        if let Type::Function(..) = ret {
+            // the result may be a closure, which nothing in the heap refers to
+            self.machine.pin(result);
+            Ok(Function {
+                handle: result,
+                ty: ret,
+            })
+        } else {
+            Err(Error::UnsupportedType(ret))
+        }
+    }
+
+    /// Lets the garbage collector free the function that [`Program::specialize`] returned,
+    /// once the program no longer refers to it either. `function` mustn't be called after
+    /// this, through any of its clones.
+    pub fn release(&mut self, function: Function<'s>) {
+        self.machine.unpin(function.handle);
+    }
+
+    fn invoke(
+        &mut self,
+        function: &Function<'s>,
+        args: &[Value<'s>],
+    ) -> Result<'s, (u64, Type<'s>)> {
+        let Type::Function(param, ret) = &function.ty else {
+            return Err(Error::UnsupportedType(function.ty.clone()));
+        };
+
+        // tuple parameters are split into one argument per item
+        let params = match param.as_deref() {
+            None => &[][..],
+            Some(Type::Tuple(items)) => items,
+            Some(param) => std::slice::from_ref(param),
+        };
+
+        if params.len() != args.len() {
+            return Err(Error::Arity {
+                expected: params.len(),
+                found: args.len(),
+            });
+        }
+
+        // every extern that wasn't bound with `define_extern` must be found in native code
+        // before anything runs
+        self.machine.resolve_externs(&self.lowered)?;
+
+        let layout = Layout::new(&self.reified, &self.lowered);
+        let mut words = Vec::with_capacity(args.len());
+        for (index, (param, arg)) in params.iter().zip(args).enumerate() {
+            let word = layout
+                .write(&mut self.machine.heap, arg, param)
+                .map_err(|error| Error::ArgumentType { index, error })?;
+            words.push(word);
+        }
+
+        let result = self
+            .machine
+            .call(&self.lowered, function.handle, &words)?;
+
+        Ok((result, (**ret).clone()))
+    }
+}
@@ -2,6 +2,7 @@ use std::{io, marker::PhantomData};
 
 use crate::{
     parser::ParseErrorKind,
+    reifier::{ReifyError, ReifyErrorKind},
     tokenizer::{Span, TokenizationError, TokenizationErrorKind},
 };
 
@@ -9,6 +10,12 @@ pub struct ErrorStream<'s> {
     phantom: PhantomData<&'s str>,
 }
 
+impl<'s> Default for ErrorStream<'s> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'s> ErrorStream<'s> {
     pub fn new() -> ErrorStream<'s> {
         ErrorStream {
@@ -35,6 +42,7 @@ pub struct CompilationError<'s> {
 pub enum CompilationErrorKind<'s> {
     Parse(ParseErrorKind<'s>),
     Tokenization(TokenizationErrorKind),
+    Reify(ReifyErrorKind<'s>),
     Io(io::Error),
 }
 
@@ -51,6 +59,15 @@ impl<'s> From<ParseErrorKind<'s>> for CompilationError<'s> {
     }
 }
 
+impl<'s> From<ReifyError<'s>> for CompilationError<'s> {
+    fn from(err: ReifyError<'s>) -> Self {
+        CompilationError {
+            kind: CompilationErrorKind::Reify(err.kind),
+            span: err.span,
+        }
+    }
+}
+
 impl<'s> From<TokenizationError> for CompilationError<'s> {
     fn from(err: TokenizationError) -> Self {
         if let TokenizationErrorKind::Io(io_err) = err.kind {
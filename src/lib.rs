@@ -0,0 +1,22 @@
+//! `codef` as a library, so host applications can compile codef programs, call into them,
+//! and specialize their `$` functions at runtime.
+//!
+//! The stable interface is [`compile`] and the types it returns; the compiler's stages are
+//! public as well, but may change at any time.
+
+pub mod backends;
+pub mod char_reader;
+pub mod engine;
+pub mod errors;
+pub mod loader;
+pub mod lowerer;
+pub mod optimizers;
+pub mod parser;
+pub mod reifier;
+pub mod runtime;
+pub mod strings;
+pub mod tokenizer;
+
+pub use engine::{compile, compile_file, compile_file_with, compile_with, Error, Function, Program};
+pub use lowerer::{Options, Value};
+pub use strings::Strings;
@@ -0,0 +1,177 @@
+//! Finds and parses every module that a program `use`s.
+//!
+//! `use a::b;` refers to the file `a/b.co`, relative to the directory of the root module,
+//! except for the modules of the standard library under `std`, which are bundled with the
+//! compiler. Modules are parsed once each, however many modules use them, and share one
+//! [`Strings`].
+
+use std::{io, path::Path};
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    char_reader::IoCharReader,
+    errors::ErrorStream,
+    parser::{self, ExprKind, ParseError},
+    strings::{Intern, Strings},
+    tokenizer::Tokens,
+};
+
+/// The file extension of codef source files.
+pub const EXTENSION: &str = "co";
+
+/// The name of the standard library, which the paths of its modules start with.
+pub const STD: &str = "std";
+
+/// The source of each module of the standard library, after the modules it uses. Every
+/// program loads all of them ahead of its own modules.
+pub const STD_MODULES: &[(&str, &str)] = &[
+    ("option", include_str!("../std/option.co")),
+    ("result", include_str!("../std/result.co")),
+    ("list", include_str!("../std/list.co")),
+    ("map", include_str!("../std/map.co")),
+    ("string", include_str!("../std/string.co")),
+    ("math", include_str!("../std/math.co")),
+];
+
+#[derive(Debug)]
+pub enum LoadError<'s> {
+    /// The file of the named module couldn't be read.
+    Io(String, io::Error),
+    /// The named module's file doesn't parse.
+    Parse(String, ParseError<'s>),
+    /// The modules `use` each other in a cycle, listed in the order they're used, starting
+    /// and ending with the same module.
+    Cycle(Box<[String]>),
+}
+
+type Result<'s, T> = std::result::Result<T, LoadError<'s>>;
+
+/// A parsed module.
+#[derive(Debug)]
+pub struct Source<'s> {
+    /// The path that other modules `use` it by, which is empty for the root module.
+    pub path: Box<[Intern<'s>]>,
+    pub tree: parser::Expr<'s>,
+}
+
+impl<'s> Source<'s> {
+    /// The module's path, written the way it is in a `use`.
+    pub fn name(&self) -> String {
+        display(&self.path)
+    }
+}
+
+/// Parses `source` as the root module, along with the standard library and every module it
+/// uses, directly or not, whose files are looked up in `dir`. Each module comes after all the
+/// modules it uses, so the root module is last.
+///
+/// `name` is the name of the root module's file in `dir` without its extension, if it has one,
+/// so a module that uses it back is reported as a cycle rather than loading it a second time.
+pub fn load<'s>(
+    strings: &'s Strings,
+    errors: &ErrorStream<'s>,
+    source: &str,
+    name: Option<&str>,
+    dir: &Path,
+) -> Result<'s, Vec<Source<'s>>> {
+    let root = match name {
+        Some(name) => Box::new([strings.intern(name.into())]) as Box<[_]>,
+        None => Box::new([]),
+    };
+    let mut loader = Loader {
+        strings,
+        errors,
+        dir,
+        root,
+        states: FxHashMap::default(),
+        stack: Vec::new(),
+        sources: Vec::new(),
+    };
+    let std = strings.intern(STD.into());
+    for &(name, _) in STD_MODULES {
+        loader.dependency(&[std, strings.intern(name.into())])?;
+    }
+    loader.module(Box::new([]), source)?;
+
+    Ok(loader.sources)
+}
+
+fn display(path: &[Intern]) -> String {
+    path.iter().map(|name| name.0).collect::<Vec<_>>().join("::")
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The module's dependencies are still being loaded.
+    Loading,
+    Loaded,
+}
+
+struct Loader<'a, 'e, 's> {
+    strings: &'s Strings,
+    errors: &'e ErrorStream<'s>,
+    dir: &'a Path,
+    /// The path that the root module's file would be used by, which is empty if it has none.
+    root: Box<[Intern<'s>]>,
+    states: FxHashMap<Box<[Intern<'s>]>, State>,
+    /// The modules being loaded, each used by the one before it.
+    stack: Vec<Box<[Intern<'s>]>>,
+    sources: Vec<Source<'s>>,
+}
+
+impl<'a, 'e, 's> Loader<'a, 'e, 's> {
+    fn module(&mut self, path: Box<[Intern<'s>]>, source: &str) -> Result<'s, ()> {
+        let tokens = Tokens::of(IoCharReader::<256, _>::new(source.as_bytes()), self.strings);
+        let (_, tree) = parser::parse(tokens, self.errors)
+            .map_err(|err| LoadError::Parse(display(&path), err))?;
+
+        let key = if path.is_empty() { self.root.clone() } else { path.clone() };
+        self.states.insert(key.clone(), State::Loading);
+        self.stack.push(key.clone());
+        // only the top-level of a module can use other modules
+        if let ExprKind::Scope(scope) = &tree.kind {
+            for use_ in &*scope.uses {
+                self.dependency(&use_.path)?;
+            }
+        }
+        self.stack.pop();
+        self.states.insert(key, State::Loaded);
+
+        self.sources.push(Source { path, tree });
+        Ok(())
+    }
+
+    fn dependency(&mut self, path: &[Intern<'s>]) -> Result<'s, ()> {
+        match self.states.get(path) {
+            Some(State::Loaded) => Ok(()),
+            Some(State::Loading) => {
+                let start = self.stack.iter().position(|p| **p == *path).unwrap();
+                let mut cycle: Vec<_> = self.stack[start..].iter().map(|p| display(p)).collect();
+                cycle.push(display(path));
+                Err(LoadError::Cycle(cycle.into_boxed_slice()))
+            }
+            None if path.first().is_some_and(|name| name.0 == STD) => {
+                let source = match path {
+                    [_, name] => STD_MODULES.iter().find(|&&(std_name, _)| std_name == name.0),
+                    _ => None,
+                };
+                let Some(&(_, source)) = source else {
+                    let err = io::Error::from(io::ErrorKind::NotFound);
+                    return Err(LoadError::Io(display(path), err));
+                };
+
+                self.module(path.into(), source)
+            }
+            None => {
+                let mut file = self.dir.to_path_buf();
+                file.extend(path.iter().map(|name| name.0));
+                file.set_extension(EXTENSION);
+                let source = std::fs::read_to_string(&file)
+                    .map_err(|err| LoadError::Io(display(path), err))?;
+
+                self.module(path.into(), &source)
+            }
+        }
+    }
+}
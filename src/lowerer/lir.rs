@@ -9,6 +9,30 @@ use crate::{
 pub struct Module<'s> {
     pub main: Option<Symbol>,
     pub defs: FxHashMap<Symbol, Def<'s>>,
+    /// A copy of a generic def for each set of types it's used at, which
+    /// [`Producer::Instance`] refers to by index.
+    pub instances: Vec<Def<'s>>,
+    /// The id that identifies each variant by name at runtime.
+    pub variants: FxHashMap<Intern<'s>, u64>,
+    pub externs: FxHashMap<Symbol, Extern<'s>>,
+    /// The contents of every string literal, which [`Producer::String`] refers to by index.
+    pub strings: Vec<Intern<'s>>,
+    /// The body of every closure, lifted out of the function it's written in, which
+    /// [`Producer::Closure`] refers to by index.
+    ///
+    /// A closure is a heap object holding the handle of its body, followed by the value of
+    /// each local it captures. Its body takes the closure itself as an extra first argument,
+    /// so calling a closure means passing it to the function in its first word.
+    pub closures: Vec<Cfg>,
+}
+
+/// A function that's called through the platform's C ABI.
+#[derive(Debug)]
+pub struct Extern<'s> {
+    pub name: Intern<'s>,
+    pub library: Option<Intern<'s>>,
+    pub params: Box<[Kind]>,
+    pub ret: Kind,
 }
 
 #[derive(Debug)]
@@ -17,9 +41,12 @@ pub struct Def<'s> {
     pub value: Value<'s>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Cfg {
     pub temps: usize,
+    /// One for each item if the function's parameter type is a tuple, so none for the unit,
+    /// and one for the whole parameter otherwise. Closures and specializations take their
+    /// closure object or reified arguments before these.
     pub params: Box<[Temp]>,
     pub blocks: Box<[Block]>,
     pub entry: BlockRef,
@@ -36,7 +63,7 @@ pub struct Temp {
     pub idx: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Block<T = Target> {
     pub params: Box<[Temp]>,
     pub insns: Box<[Insn]>,
@@ -48,9 +75,8 @@ impl<T> Block<T> {
     pub fn successors(&self) -> impl Iterator<Item = &T> {
         let mut succs = [None, None];
 
-        match &self.branch {
-            Some(Branch(.., t)) => succs[0] = Some(t),
-            None => (),
+        if let Some(Branch(.., t)) = &self.branch {
+            succs[0] = Some(t);
         }
 
         match &self.ctrl {
@@ -58,54 +84,66 @@ impl<T> Block<T> {
             Ctrl::Return(_) => (),
         }
 
-        succs.into_iter().filter_map(|s| s)
+        succs.into_iter().flatten()
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct BlockRef(pub usize);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Target {
     pub block: BlockRef,
     pub arguments: Box<[Temp]>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Ctrl<Target> {
     Jump(Target),
     Return(Temp),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Branch<Target>(pub BranchCmp, pub Temp, pub Temp, pub Target);
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BranchCmp {
     Eq,
     Neq,
     Lt,
     Geq,
+    /// Like `Lt`, but comparing the words as unsigned integers.
+    LtU,
+    GeqU,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Insn {
     Load(Temp, Producer),
     Store(MemRef, Temp),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Producer {
     Memory(Kind, MemRef),
     Symbol(Kind, Symbol),
     Builtin(Builtin),
+    Extern(Symbol),
+    Instance(usize),
+    /// The handle of the body of a closure, which is only called through the closure.
+    Closure(usize),
     Ir(Cfg),
     Copy(Temp),
     Binary(BinOp, Temp, Temp),
     Unary(UnOp, Temp),
+    /// Calls a function with arguments as described in [`Cfg::params`], producing a value of
+    /// the kind of the function's return type.
     Call(Temp, Box<[Temp]>, Kind),
     ConstI(u64),
     ConstF(f64),
+    /// A string literal, which must never be modified, since every evaluation of the
+    /// literal shares it.
+    String(usize),
 }
 
 impl Producer {
@@ -114,7 +152,7 @@ impl Producer {
         use Producer::*;
 
         match self {
-            Builtin(_) | Ir(_) => Integer,
+            Builtin(_) | Extern(_) | Instance(_) | Closure(_) | Ir(_) => Integer,
             Memory(k, _) | Symbol(k, _) | Call(_, _, k) => *k,
             Copy(t) => t.kind,
             Binary(op, _, _) => match op {
@@ -123,15 +161,22 @@ impl Producer {
                 | BinOp::BitAndI
                 | BinOp::BitShlI
                 | BinOp::BitShrI
+                | BinOp::BitShrU
                 | BinOp::AddI
                 | BinOp::SubI
                 | BinOp::MulI
+                | BinOp::MulHiI
+                | BinOp::MulHiU
                 | BinOp::DivI
                 | BinOp::ModI
+                | BinOp::DivU
+                | BinOp::ModU
                 | BinOp::EqI
                 | BinOp::NeqI
                 | BinOp::LtI
                 | BinOp::LeqI
+                | BinOp::LtU
+                | BinOp::LeqU
                 | BinOp::EqF
                 | BinOp::NeqF
                 | BinOp::LtF
@@ -139,34 +184,51 @@ impl Producer {
                 BinOp::AddF | BinOp::SubF | BinOp::MulF | BinOp::DivF => Float,
             },
             Unary(op, _) => match op {
-                UnOp::BoolNotI | UnOp::BitNotI | UnOp::NegI => Integer,
+                UnOp::BoolNotI
+                | UnOp::BitNotI
+                | UnOp::NegI
+                | UnOp::SignExtend(_)
+                | UnOp::ZeroExtend(_) => Integer,
                 UnOp::NegF => Float,
             },
-            ConstI(_) => Integer,
+            ConstI(_) | String(_) => Integer,
             ConstF(_) => Float,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MemRef(pub Temp, pub u64);
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BinOp {
     BitOrI,
     BitXorI,
     BitAndI,
     BitShlI,
+    /// Shifts right, copying the sign bit into the bits that are shifted in.
     BitShrI,
+    /// Shifts right, shifting in zeroes.
+    BitShrU,
     AddI,
     SubI,
     MulI,
+    /// The upper word of the double-word product of two words, which only has bits other
+    /// than the sign bits of `MulI`'s result if the product overflows.
+    MulHiI,
+    MulHiU,
     DivI,
     ModI,
+    /// Like `DivI`, but on the words as unsigned integers, as are the other ops ending in
+    /// `U`.
+    DivU,
+    ModU,
     EqI,
     NeqI,
     LtI,
     LeqI,
+    LtU,
+    LeqU,
     AddF,
     SubF,
     MulF,
@@ -177,12 +239,18 @@ pub enum BinOp {
     LeqF,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum UnOp {
     BoolNotI,
     BitNotI,
     NegI,
     NegF,
+    /// Wraps a word around to a signed integer of this many bits, by copying its sign bit
+    /// into the bits above it.
+    SignExtend(u32),
+    /// Wraps a word around to an unsigned integer of this many bits, by clearing the bits
+    /// above it.
+    ZeroExtend(u32),
 }
 
 /// The *kind* of data that is stored in an individual place accessible by the program.
@@ -198,22 +266,49 @@ impl Kind {
         match ty {
             Type::Function(..) => Kind::Integer,
             Type::Primitive(
-                PrimitiveType::Boolean | PrimitiveType::Integer | PrimitiveType::String,
+                PrimitiveType::Boolean
+                | PrimitiveType::Integer
+                | PrimitiveType::Sized(_)
+                | PrimitiveType::String,
             ) => Kind::Integer,
             Type::Primitive(PrimitiveType::Float) => Kind::Float,
+            // nothing is ever stored in a value that's never produced
+            Type::Primitive(PrimitiveType::Never) => Kind::Integer,
             Type::Variant(..) => Kind::Integer,
-            Type::Tuple(..) => Kind::Integer,
+            Type::Tuple(..) | Type::Record(..) | Type::Array(..) => Kind::Integer,
             Type::Instance(..) => Kind::Integer, // is this valid?
-            Type::Unknown => unreachable!(),
+            // generic values all share one representation, whatever they're instantiated with
+            Type::Var(..) => Kind::Integer,
+        }
+    }
+
+    /// The kind of a builtin's parameter or result.
+    pub fn of_sig(sig: crate::reifier::Sig) -> Kind {
+        use crate::reifier::Sig;
+        match sig {
+            Sig::Float => Kind::Float,
+            Sig::Int
+            | Sig::Sized(_)
+            | Sig::Integer
+            | Sig::Bool
+            | Sig::String
+            | Sig::Unit
+            | Sig::Item
+            | Sig::Array(_) => Kind::Integer,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value<'s> {
     Integer(i64),
+    Boolean(bool),
     Float(f64),
     Tuple(Box<[Value<'s>]>),
+    /// The name and value of each field, in any order.
+    Record(Box<[(Intern<'s>, Value<'s>)]>),
+    Array(Box<[Value<'s>]>),
     Variant(Intern<'s>, Option<Box<Value<'s>>>),
+    String(Box<str>),
     Function(Cfg),
 }
@@ -1,27 +1,167 @@
-use crate::reifier::{self, Builtin, VariantItemType};
+use std::cell::RefCell;
 
-use rustc_hash::FxHashMap;
+use crate::{
+    reifier::{self, Builtin},
+    runtime,
+    strings::Intern,
+    tokenizer::Span,
+};
+
+use rustc_hash::{FxHashMap, FxHashSet};
 
 mod lir;
 pub use lir::*;
 
-pub fn lower<'s>(module: &reifier::Module<'s>) -> Module<'s> {
+/// How a module is lowered.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Whether integer arithmetic aborts the program when it overflows or divides by zero,
+    /// in every def that isn't declared `unchecked`.
+    pub checked: bool,
+}
+
+pub fn lower<'s>(module: &reifier::Module<'s>, options: Options) -> Module<'s> {
     let mut defs = FxHashMap::default();
+    let variants = RefCell::new(FxHashMap::default());
+    let instances = RefCell::new(Instances::default());
+    let strings = RefCell::new(FxHashMap::default());
+    let closures = RefCell::new(Vec::new());
 
     for (sym, def) in &module.defs {
         let reifier::ExprKind::Abstract { spec, arg, body } = &def.body.kind else {
             continue;
         };
+        // generic defs are only lowered for the types they're used at
+        if is_generic(&def.body.ty) {
+            continue;
+        }
+        let lowerer = Lowerer::new(
+            module,
+            &variants,
+            &instances,
+            &strings,
+            &closures,
+            FxHashMap::default(),
+            options.checked && !def.unchecked,
+        );
         defs.insert(
             *sym,
             Def {
                 name: def.name,
-                value: Value::Function(Lowerer::new(module).lower(*spec, arg.as_ref(), &body)),
+                value: Value::Function(lowerer.lower(*spec, arg.as_ref(), body)),
             },
         );
     }
 
-    Module { main: None, defs }
+    // lowering an instance can use more instances, which are lowered in turn
+    let mut lowered_instances = Vec::new();
+    loop {
+        let Some((sym, subst)) = instances.borrow().queue.get(lowered_instances.len()).cloned()
+        else {
+            break;
+        };
+        let def = &module.defs[&sym];
+        let reifier::ExprKind::Abstract { spec, arg, body } = &def.body.kind else {
+            unreachable!("only functions are instantiated")
+        };
+        let lowerer = Lowerer::new(
+            module,
+            &variants,
+            &instances,
+            &strings,
+            &closures,
+            subst,
+            options.checked && !def.unchecked,
+        );
+        lowered_instances.push(Def {
+            name: def.name,
+            value: Value::Function(lowerer.lower(*spec, arg.as_ref(), body)),
+        });
+    }
+
+    // the host can pass in variants that no code constructs, so every variant that appears
+    // in a type gets an id too
+    let mut variants = variants.into_inner();
+    let types = module
+        .defs
+        .values()
+        .map(|def| &def.body.ty)
+        .chain(module.locals.values().map(|local| &local.ty));
+    for ty in types {
+        register_variants(ty, &mut variants);
+    }
+
+    let mut externs = FxHashMap::default();
+    for (sym, ext) in &module.externs {
+        let reifier::Type::Function(Some(param), ret) = &ext.ty else {
+            unreachable!("externs always take a parameter")
+        };
+        let params: Box<[Kind]> = match &**param {
+            reifier::Type::Tuple(items) => items.iter().map(Kind::of).collect(),
+            param => Box::new([Kind::of(param)]),
+        };
+        externs.insert(
+            *sym,
+            Extern {
+                name: ext.name,
+                library: ext.library,
+                params,
+                ret: Kind::of(ret),
+            },
+        );
+    }
+
+    let mut strings: Vec<_> = strings.into_inner().into_iter().collect();
+    strings.sort_by_key(|&(_, id)| id);
+
+    Module {
+        main: None,
+        defs,
+        instances: lowered_instances,
+        variants,
+        externs,
+        strings: strings.into_iter().map(|(string, _)| string).collect(),
+        closures: closures.into_inner(),
+    }
+}
+
+/// Whether `ty` still has type variables, which a def's type only has if it's generic.
+fn is_generic(ty: &reifier::Type) -> bool {
+    let mut vars = Vec::new();
+    ty.vars(&mut vars);
+    !vars.is_empty()
+}
+
+fn register_variants<'s>(ty: &reifier::Type<'s>, variants: &mut FxHashMap<Intern<'s>, u64>) {
+    match ty {
+        reifier::Type::Function(param, ret) => {
+            if let Some(param) = param {
+                register_variants(param, variants);
+            }
+            register_variants(ret, variants);
+        }
+        reifier::Type::Variant(items) => {
+            for item in items.iter() {
+                let next_id = variants.len() as u64;
+                variants.entry(item.name).or_insert(next_id);
+                if let Some(inner) = &item.inner {
+                    register_variants(inner, variants);
+                }
+            }
+        }
+        reifier::Type::Tuple(items) | reifier::Type::Instance(_, items) => {
+            for item in items.iter() {
+                register_variants(item, variants);
+            }
+        }
+        reifier::Type::Record(fields) => {
+            for field in fields.iter() {
+                register_variants(&field.ty, variants);
+            }
+        }
+        reifier::Type::Array(item) => register_variants(item, variants),
+        reifier::Type::Primitive(_) | reifier::Type::Var(_) => (),
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -30,17 +170,48 @@ struct LabelRef(usize);
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 struct VarRef(usize);
 
+/// A `set` in a pattern, which only happens once the whole pattern has matched.
 #[derive(Debug)]
-struct Lowerer<'a> {
+enum Assignment {
+    Var(reifier::Symbol, Temp),
+    Store(MemRef, Temp),
+}
+
+/// The copies of generic defs that lowered code uses, shared by every function in the module.
+#[derive(Debug, Default)]
+struct Instances<'a> {
+    /// The index of each def's instance, by the types its type variables stand for.
+    ids: FxHashMap<(reifier::Symbol, Box<[reifier::Type<'a>]>), usize>,
+    /// Every instance by index, with what the def's type variables stand for.
+    queue: Vec<(reifier::Symbol, FxHashMap<reifier::TypeVar, reifier::Type<'a>>)>,
+}
+
+#[derive(Debug)]
+struct Lowerer<'m, 'a> {
     // the module we're lowering
-    reified_module: &'a reifier::Module<'a>,
+    reified_module: &'m reifier::Module<'a>,
 
     // counter to manage all the temp handles we're giving out
     temp_counter: usize,
 
-    // data so we can assign a numeric id to each variant
-    variant_ids: FxHashMap<reifier::VariantItemType<'a>, u64>,
-    variant_id_counter: u64,
+    // the numeric id of each variant name, shared by every function in the module
+    variant_ids: &'m RefCell<FxHashMap<Intern<'a>, u64>>,
+
+    // the instances of generic defs, shared by every function in the module
+    instances: &'m RefCell<Instances<'a>>,
+
+    // the index of each string literal, shared by every function in the module
+    string_ids: &'m RefCell<FxHashMap<Intern<'a>, usize>>,
+
+    // the lifted bodies of closures, shared by every function in the module
+    closures: &'m RefCell<Vec<Cfg>>,
+
+    // what the type variables stand for, if this is an instance of a generic def
+    subst: FxHashMap<reifier::TypeVar, reifier::Type<'a>>,
+
+    // whether integer arithmetic is checked, as it is in the closures and specializations
+    // of a checked def too
+    checked: bool,
 
     // lookup tables for locals
     vals: FxHashMap<reifier::Symbol, Temp>,
@@ -49,9 +220,16 @@ struct Lowerer<'a> {
     // generations of variables
     generations: Vec<Temp>,
 
+    // the labels that `continue` and `break` jump to, for each loop we're in
+    loops: Vec<(LabelRef, LabelRef)>,
+
     // arguments
     args: Vec<Temp>,
 
+    // static expressions that were computed at specialization time, by their offset into
+    // the tuple of specialized arguments
+    hoisted: FxHashMap<*const reifier::Expr<'a>, u64>,
+
     // the cfg, as built so far
     labels: Vec<Option<BlockRef>>,
     blocks: Vec<Block<LabelRef>>,
@@ -61,29 +239,48 @@ struct Lowerer<'a> {
     insns: Vec<Insn>,
     branch: Option<Branch<LabelRef>>,
     live: FxHashMap<VarRef, Temp>,
+    // variables given a value in the current block, which it doesn't need as params
+    assigned: FxHashSet<VarRef>,
 }
 
 #[derive(Debug)]
 struct BlockMeta {
     param_vars: Vec<VarRef>,
     generations: Vec<Temp>,
+    // the variables the block gives a value to
+    assigned: FxHashSet<VarRef>,
 }
 
-impl<'a> Lowerer<'a> {
-    fn new(reified_module: &'a reifier::Module<'a>) -> Lowerer<'a> {
+impl<'m, 'a> Lowerer<'m, 'a> {
+    fn new(
+        reified_module: &'m reifier::Module<'a>,
+        variant_ids: &'m RefCell<FxHashMap<Intern<'a>, u64>>,
+        instances: &'m RefCell<Instances<'a>>,
+        string_ids: &'m RefCell<FxHashMap<Intern<'a>, usize>>,
+        closures: &'m RefCell<Vec<Cfg>>,
+        subst: FxHashMap<reifier::TypeVar, reifier::Type<'a>>,
+        checked: bool,
+    ) -> Lowerer<'m, 'a> {
         Lowerer {
             args: Vec::new(),
+            hoisted: FxHashMap::default(),
             labels: Vec::new(),
             blocks: Vec::new(),
             block_meta: Vec::new(),
             insns: Vec::new(),
             branch: None,
             live: FxHashMap::default(),
+            assigned: FxHashSet::default(),
             vals: FxHashMap::default(),
             vars: FxHashMap::default(),
             generations: Vec::new(),
-            variant_ids: FxHashMap::default(),
-            variant_id_counter: 0,
+            loops: Vec::new(),
+            variant_ids,
+            instances,
+            string_ids,
+            closures,
+            subst,
+            checked,
             temp_counter: 0,
             reified_module,
         }
@@ -96,14 +293,20 @@ impl<'a> Lowerer<'a> {
         body: &reifier::Expr<'a>,
     ) -> Cfg {
         if let Some(param) = param {
-            if let reifier::PatternKind::Tuple(items) = &param.kind {
-                for item in &**items {
-                    let temp = self.new_temp(Kind::of(&item.ty));
-                    self.args.push(temp);
-                    self.abstract_arg(item, temp);
+            // a tuple is passed as one argument per item, whatever the pattern looks like
+            if let reifier::Type::Tuple(tys) = self.ty(&param.ty) {
+                let temps: Vec<_> = tys.iter().map(|ty| self.new_temp(Kind::of(ty))).collect();
+                self.args.extend(&temps);
+                if let reifier::PatternKind::Tuple(items) = &param.kind {
+                    for (item, &temp) in items.iter().zip(&temps) {
+                        self.abstract_arg(item, temp);
+                    }
+                } else {
+                    let tuple = self.pack(&tys, &temps);
+                    self.abstract_arg(param, tuple);
                 }
             } else {
-                let temp = self.new_temp(Kind::of(&param.ty));
+                let temp = self.new_temp(self.kind(&param.ty));
                 self.args.push(temp);
                 self.abstract_arg(param, temp);
             }
@@ -113,12 +316,6 @@ impl<'a> Lowerer<'a> {
             let ret = self.expr(body, true).unwrap();
             self.ctrl(Ctrl::Return(ret));
         } else {
-            // 1. pack all the args into one tuple
-            let reifying_args = self.alloc(8 * self.args.len() as u64);
-            for (i, &arg) in self.args.clone().iter().enumerate() {
-                self.store(MemRef(reifying_args, i as u64 * 8), arg);
-            }
-
             let reifying_args_pat = param.expect("Cannot reify with empty arguments");
 
             // if this is a spec, the body must be an immediate lambda
@@ -126,26 +323,106 @@ impl<'a> Lowerer<'a> {
                 panic!("Cannot specialize non-function body")
             };
 
-            let mut lowerer = Self::new(self.reified_module);
+            // static parts of the body only need to be computed once, so they are computed
+            // here and passed along with the arguments
+            let mut hoisted = Vec::new();
+            self.collect_hoisted(body, &mut hoisted);
+
+            // 1. pack all the args (and hoisted values) into one object, which is called like a
+            // closure, so its first word is the generated function
+            let reifying_args_ty = self.ty(&reifying_args_pat.ty);
+            let arg_tys = match &reifying_args_ty {
+                reifier::Type::Tuple(items) => &items[..],
+                ty => std::slice::from_ref(ty),
+            };
+            let proc_ty = reifier::Type::Primitive(reifier::PrimitiveType::Integer);
+            let map = self.pointer_map(
+                [&proc_ty]
+                    .into_iter()
+                    .chain(arg_tys)
+                    .chain(hoisted.iter().map(|expr| &expr.ty)),
+            );
+            let words = 1 + self.args.len() + hoisted.len();
+            let reifying_args = self.alloc(8 * words as u64, map);
+            for (i, &arg) in self.args.clone().iter().enumerate() {
+                self.store(MemRef(reifying_args, (1 + i) as u64 * 8), arg);
+            }
 
-            // the inner proc gets one extra initial argument: the tuple of all the reified stuff
+            let mut lowerer = Self::new(
+                self.reified_module,
+                self.variant_ids,
+                self.instances,
+                self.string_ids,
+                self.closures,
+                self.subst.clone(),
+                self.checked,
+            );
+
+            for (i, expr) in hoisted.into_iter().enumerate() {
+                let offset = (1 + self.args.len() + i) as u64 * 8;
+                let temp = self.expr(expr, true).unwrap();
+                self.store(MemRef(reifying_args, offset), temp);
+                lowerer.hoisted.insert(expr, offset);
+            }
+
+            // the inner proc gets one extra initial argument: the object of all the reified
+            // stuff, which it takes its args back out of
             let arg_temp = lowerer.new_temp(Kind::Integer);
             lowerer.args.push(arg_temp);
-            lowerer.abstract_arg(reifying_args_pat, arg_temp);
+            let items: Vec<_> = arg_tys
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| {
+                    let memref = MemRef(arg_temp, (1 + i) as u64 * 8);
+                    lowerer.load(Producer::Memory(Kind::of(ty), memref))
+                })
+                .collect();
+            match (&reifying_args_ty, &reifying_args_pat.kind) {
+                (reifier::Type::Tuple(_), reifier::PatternKind::Tuple(pats)) => {
+                    for (pat, &item) in pats.iter().zip(&items) {
+                        lowerer.abstract_arg(pat, item);
+                    }
+                }
+                (reifier::Type::Tuple(tys), _) => {
+                    let tuple = lowerer.pack(tys, &items);
+                    lowerer.abstract_arg(reifying_args_pat, tuple);
+                }
+                _ => lowerer.abstract_arg(reifying_args_pat, items[0]),
+            }
 
-            let proc = lowerer.lower(*spec, arg.as_ref(), &body);
+            let proc = lowerer.lower(*spec, arg.as_ref(), body);
 
             let proc_temp = self.load(Producer::Ir(proc));
-            let spec_temp = self.load(Producer::Builtin(Builtin::Spec));
-            let spec_res_temp = self.load(Producer::Call(
-                spec_temp,
-                Box::new([proc_temp, arg_temp]),
-                Kind::Integer,
-            ));
+            self.store(MemRef(reifying_args, 0), proc_temp);
+            let spec_res_temp = self.call_builtin(Builtin::Spec, Box::new([reifying_args]));
             self.ctrl(Ctrl::Return(spec_res_temp))
         }
 
-        let mut blocks = Vec::new();
+        // a block only takes the variables that are given a value on every path into it, and
+        // blocks that no path leads to, like the code after a `return`, are left out
+        let available = self.available_vars();
+        let mut indices = Vec::with_capacity(available.len());
+        let mut reachable = 0;
+        for (i, available) in available.iter().enumerate() {
+            let Some(available) = available else {
+                indices.push(None);
+                continue;
+            };
+            indices.push(Some(BlockRef(reachable)));
+            reachable += 1;
+
+            let meta = &mut self.block_meta[i];
+            let (param_vars, params): (Vec<_>, Vec<_>) = meta
+                .param_vars
+                .iter()
+                .zip(&*self.blocks[i].params)
+                .filter(|(var, _)| available.contains(var))
+                .unzip();
+            meta.param_vars = param_vars;
+            self.blocks[i].params = params.into_boxed_slice();
+        }
+
+        let mut blocks = Vec::with_capacity(reachable);
         for (
             i,
             Block {
@@ -156,23 +433,20 @@ impl<'a> Lowerer<'a> {
             },
         ) in std::mem::take(&mut self.blocks).into_iter().enumerate()
         {
+            if indices[i].is_none() {
+                continue;
+            }
+
             blocks.push(Block {
                 params,
                 insns,
                 branch: if let Some(Branch(cmp, a, b, t)) = branch {
-                    Some(Branch(
-                        cmp,
-                        a,
-                        b,
-                        self.conv_target(BlockRef(i), self.labels[t.0].unwrap()),
-                    ))
+                    Some(Branch(cmp, a, b, self.conv_target(BlockRef(i), t, &indices)))
                 } else {
                     None
                 },
                 ctrl: match ctrl {
-                    Ctrl::Jump(t) => {
-                        Ctrl::Jump(self.conv_target(BlockRef(i), self.labels[t.0].unwrap()))
-                    }
+                    Ctrl::Jump(t) => Ctrl::Jump(self.conv_target(BlockRef(i), t, &indices)),
                     Ctrl::Return(r) => Ctrl::Return(r),
                 },
             })
@@ -186,23 +460,99 @@ impl<'a> Lowerer<'a> {
         }
     }
 
-    fn conv_target(&self, source: BlockRef, target: BlockRef) -> Target {
+    /// The block that `label` starts.
+    fn label_block(&self, label: LabelRef) -> BlockRef {
+        self.labels[label.0].unwrap()
+    }
+
+    /// For each block that can be reached from the entry, the variables that are given a
+    /// value on every path into it.
+    fn available_vars(&self) -> Vec<Option<FxHashSet<VarRef>>> {
+        let successors = |block: &Block<LabelRef>| {
+            let jump = match block.ctrl {
+                Ctrl::Jump(label) => Some(self.label_block(label)),
+                Ctrl::Return(_) => None,
+            };
+            let branch = block.branch.as_ref().map(|&Branch(.., label)| self.label_block(label));
+            jump.into_iter().chain(branch)
+        };
+
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut stack = vec![BlockRef(0)];
+        while let Some(block) = stack.pop() {
+            if std::mem::replace(&mut reachable[block.0], true) {
+                continue;
+            }
+            for succ in successors(&self.blocks[block.0]) {
+                predecessors[succ.0].push(block);
+                stack.push(succ);
+            }
+        }
+
+        // `None` stands for every variable, until a path shows otherwise
+        let mut available: Vec<Option<FxHashSet<VarRef>>> = vec![None; self.blocks.len()];
+        available[0] = Some(FxHashSet::default());
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in 1..self.blocks.len() {
+                let mut vars: Option<FxHashSet<VarRef>> = None;
+                for pred in &predecessors[block] {
+                    let Some(pred_vars) = &available[pred.0] else {
+                        continue;
+                    };
+                    let mut out = pred_vars.clone();
+                    out.extend(&self.block_meta[pred.0].assigned);
+                    vars = Some(match vars {
+                        Some(vars) => vars.intersection(&out).copied().collect(),
+                        None => out,
+                    });
+                }
+
+                if vars.is_some() && vars != available[block] {
+                    available[block] = vars;
+                    changed = true;
+                }
+            }
+        }
+
+        for (block, reachable) in reachable.into_iter().enumerate() {
+            if !reachable {
+                available[block] = None;
+            }
+        }
+        available
+    }
+
+    fn conv_target(
+        &self,
+        source: BlockRef,
+        target: LabelRef,
+        indices: &[Option<BlockRef>],
+    ) -> Target {
+        let target = self.label_block(target);
         let source_meta = &self.block_meta[source.0];
         let target_meta = &self.block_meta[target.0];
 
-        // we give all of the reqired params by taking the current value of each variable
+        // we give all of the reqired params by taking the current value of each variable,
+        // which every path into the target gives a value to
         let mut args = Vec::with_capacity(target_meta.param_vars.len());
         for var in &*target_meta.param_vars {
             args.push(source_meta.generations[var.0])
         }
 
         Target {
-            block: target,
+            block: indices[target.0].expect("jump from a reachable block to an unreachable one"),
             arguments: args.into_boxed_slice(),
         }
     }
 
     fn expr(&mut self, expr: &reifier::Expr<'a>, want_output: bool) -> Option<Temp> {
+        if let Some(temp) = self.load_hoisted(expr) {
+            return Some(temp);
+        }
+
         match &expr.kind {
             reifier::ExprKind::Scope(scope) => {
                 let len = scope.exprs.len();
@@ -215,12 +565,15 @@ impl<'a> Lowerer<'a> {
                 }
 
                 if want_output {
-                    Some(self.alloc(0))
+                    Some(self.alloc(0, 0))
                 } else {
                     None
                 }
             }
-            reifier::ExprKind::Abstract { .. } => todo!("general closures"),
+            reifier::ExprKind::Abstract { .. } if !want_output => None,
+            reifier::ExprKind::Abstract { spec, arg, body } => {
+                Some(self.closure(expr, *spec, arg.as_ref(), body))
+            }
             reifier::ExprKind::For {
                 init,
                 cond,
@@ -231,18 +584,24 @@ impl<'a> Lowerer<'a> {
                     self.expr(init, false);
                 }
                 let cond_lab = self.new_label();
-                let body_lab = self.new_label();
+                let next_lab = self.new_label();
+                let done_lab = self.new_label();
                 self.ctrl(Ctrl::Jump(cond_lab));
-                self.set_label_target(body_lab);
+                // the condition comes first, so what it binds is in scope in the body
+                self.set_label_target(cond_lab);
+                self.cond(cond, JumpCond::False(done_lab));
+                self.loops.push((next_lab, done_lab));
                 self.expr(body, false);
+                self.loops.pop();
+                self.set_label_target(next_lab);
                 if let Some(afterthought) = afterthought {
                     self.expr(afterthought, false);
                 }
-                self.set_label_target(cond_lab);
-                self.cond(&cond, JumpCond::True(body_lab));
+                self.ctrl(Ctrl::Jump(cond_lab));
+                self.set_label_target(done_lab);
 
                 if want_output {
-                    Some(self.alloc(0))
+                    Some(self.alloc(0, 0))
                 } else {
                     None
                 }
@@ -259,10 +618,13 @@ impl<'a> Lowerer<'a> {
                 let true_res = self.expr(on_true, want_output && on_false.is_some());
                 if let Some(on_false) = on_false {
                     if want_output {
-                        res = self.new_var(true_res.unwrap());
+                        let kind = self.kind(&expr.ty);
+                        let true_res = self.of_kind(true_res.unwrap(), kind);
+                        res = self.new_var(true_res);
                         self.ctrl(Ctrl::Jump(done_lab));
                         self.set_label_target(on_false_lab);
                         let false_res = self.expr(on_false, true).unwrap();
+                        let false_res = self.of_kind(false_res, kind);
                         self.set_var(res, false_res);
                         self.set_label_target(done_lab);
 
@@ -279,14 +641,31 @@ impl<'a> Lowerer<'a> {
                     self.set_label_target(on_false_lab);
 
                     if want_output {
-                        Some(self.alloc(0))
+                        Some(self.alloc(0, 0))
                     } else {
                         None
                     }
                 }
             }
+            reifier::ExprKind::Break | reifier::ExprKind::Continue => {
+                let &(next_lab, done_lab) = self.loops.last().unwrap();
+                let lab = match expr.kind {
+                    reifier::ExprKind::Break => done_lab,
+                    _ => next_lab,
+                };
+                self.ctrl(Ctrl::Jump(lab));
+
+                want_output.then(|| self.unreachable_value(Kind::Integer))
+            }
+            reifier::ExprKind::Return(value) => {
+                let value = self.expr(value, true).unwrap();
+                self.ctrl(Ctrl::Return(value));
+
+                want_output.then(|| self.unreachable_value(Kind::Integer))
+            }
             reifier::ExprKind::Tuple(items) => {
-                let out = self.alloc(items.len() as u64 * 8);
+                let map = self.pointer_map(items.iter().map(|it| &it.ty));
+                let out = self.alloc(items.len() as u64 * 8, map);
                 for (i, item) in items.iter().enumerate() {
                     let item_temp = self.expr(item, true).unwrap();
                     self.store(MemRef(out, i as u64 * 8), item_temp);
@@ -298,6 +677,67 @@ impl<'a> Lowerer<'a> {
                     None
                 }
             }
+            reifier::ExprKind::Record(fields) => {
+                let mut slot_tys = vec![None; fields.len()];
+                for (slot, value) in fields.iter() {
+                    slot_tys[*slot] = Some(&value.ty);
+                }
+                let map = self.pointer_map(slot_tys.into_iter().flatten());
+                let out = self.alloc(fields.len() as u64 * 8, map);
+                for (slot, value) in fields.iter() {
+                    let value_temp = self.expr(value, true).unwrap();
+                    self.store(MemRef(out, *slot as u64 * 8), value_temp);
+                }
+
+                if want_output {
+                    Some(out)
+                } else {
+                    None
+                }
+            }
+            reifier::ExprKind::Array(items) => {
+                // the length comes first, followed by the elements
+                let len_ty = reifier::Type::Primitive(reifier::PrimitiveType::Integer);
+                let tys = [&len_ty].into_iter().chain(items.iter().map(|it| &it.ty));
+                let map = self.pointer_map(tys);
+                let out = self.alloc((items.len() as u64 + 1) * 8, map);
+                let len = self.load(Producer::ConstI(items.len() as u64));
+                self.store(MemRef(out, 0), len);
+                for (i, item) in items.iter().enumerate() {
+                    let item_temp = self.expr(item, true).unwrap();
+                    self.store(MemRef(out, (i as u64 + 1) * 8), item_temp);
+                }
+
+                if want_output {
+                    Some(out)
+                } else {
+                    None
+                }
+            }
+            reifier::ExprKind::Index(array, index) => {
+                let array = self.expr(array, true).unwrap();
+                let index = self.expr(index, true).unwrap();
+                let element = self.element(array, index);
+                let kind = self.kind(&expr.ty);
+                let value = self.load(Producer::Memory(kind, element));
+
+                if want_output {
+                    Some(value)
+                } else {
+                    None
+                }
+            }
+            reifier::ExprKind::Field(record, slot) => {
+                let record = self.expr(record, true).unwrap();
+                let kind = self.kind(&expr.ty);
+                let value = self.load(Producer::Memory(kind, MemRef(record, *slot as u64 * 8)));
+
+                if want_output {
+                    Some(value)
+                } else {
+                    None
+                }
+            }
             reifier::ExprKind::StructuralEq(pat, expr) => {
                 let val = self.expr(expr, true).unwrap();
                 let on_false = self.new_label();
@@ -305,10 +745,7 @@ impl<'a> Lowerer<'a> {
                 let out;
                 let mut assignments = Vec::new();
                 self.structural_eq(pat, val, on_false, &mut assignments);
-                for (sym, temp) in assignments {
-                    let var = self.vars.get(&sym).unwrap();
-                    self.set_var(*var, temp);
-                }
+                self.assign(assignments);
                 if want_output {
                     let one = self.load(Producer::ConstI(1));
                     out = self.new_var(one);
@@ -379,30 +816,47 @@ impl<'a> Lowerer<'a> {
                 None
             }
             reifier::ExprKind::Binary(op, a, b) => {
-                let a_ty = &a.ty;
+                let ty = self.ty(&expr.ty);
+                let a_ty = self.ty(&a.ty);
                 let a = self.expr(a, true).unwrap();
                 let b = self.expr(b, true).unwrap();
+                // the only operators that take strings are comparisons, which compare the
+                // strings' ordering against zero instead
+                let (a, b) = if a_ty.is_string() {
+                    self.compare_strings(a, b)
+                } else {
+                    (a, b)
+                };
+                let int = ty.int_type();
+                let int_cmp = a_ty.int_type().is_some() || a_ty.is_string();
+                let unsigned = int.is_some_and(|int| !int.signed);
+                let unsigned_cmp = a_ty.int_type().is_some_and(|int| !int.signed);
 
                 let (op, flip) = match op {
-                    reifier::BinOp::Add if expr.ty.is_int() => (BinOp::AddI, false),
-                    reifier::BinOp::Sub if expr.ty.is_int() => (BinOp::SubI, false),
-                    reifier::BinOp::Mul if expr.ty.is_int() => (BinOp::MulI, false),
-                    reifier::BinOp::Div if expr.ty.is_int() => (BinOp::DivI, false),
-                    reifier::BinOp::Add if expr.ty.is_float() => (BinOp::AddF, false),
-                    reifier::BinOp::Sub if expr.ty.is_float() => (BinOp::SubF, false),
-                    reifier::BinOp::Mul if expr.ty.is_float() => (BinOp::MulF, false),
-                    reifier::BinOp::Div if expr.ty.is_float() => (BinOp::DivF, false),
+                    reifier::BinOp::Add if int.is_some() => (BinOp::AddI, false),
+                    reifier::BinOp::Sub if int.is_some() => (BinOp::SubI, false),
+                    reifier::BinOp::Mul if int.is_some() => (BinOp::MulI, false),
+                    reifier::BinOp::Div if unsigned => (BinOp::DivU, false),
+                    reifier::BinOp::Div if int.is_some() => (BinOp::DivI, false),
+                    reifier::BinOp::Add if ty.is_float() => (BinOp::AddF, false),
+                    reifier::BinOp::Sub if ty.is_float() => (BinOp::SubF, false),
+                    reifier::BinOp::Mul if ty.is_float() => (BinOp::MulF, false),
+                    reifier::BinOp::Div if ty.is_float() => (BinOp::DivF, false),
                     reifier::BinOp::Add
                     | reifier::BinOp::Sub
                     | reifier::BinOp::Mul
                     | reifier::BinOp::Div => unreachable!(),
                     reifier::BinOp::Or | reifier::BinOp::And => unreachable!(),
-                    reifier::BinOp::Eq if a_ty.is_int() => (BinOp::EqI, false),
-                    reifier::BinOp::Neq if a_ty.is_int() => (BinOp::NeqI, false),
-                    reifier::BinOp::Lt if a_ty.is_int() => (BinOp::LtI, false),
-                    reifier::BinOp::Leq if a_ty.is_int() => (BinOp::LeqI, false),
-                    reifier::BinOp::Gt if a_ty.is_int() => (BinOp::LtI, true),
-                    reifier::BinOp::Geq if a_ty.is_int() => (BinOp::LeqI, true),
+                    reifier::BinOp::Eq if int_cmp => (BinOp::EqI, false),
+                    reifier::BinOp::Neq if int_cmp => (BinOp::NeqI, false),
+                    reifier::BinOp::Lt if unsigned_cmp => (BinOp::LtU, false),
+                    reifier::BinOp::Leq if unsigned_cmp => (BinOp::LeqU, false),
+                    reifier::BinOp::Gt if unsigned_cmp => (BinOp::LtU, true),
+                    reifier::BinOp::Geq if unsigned_cmp => (BinOp::LeqU, true),
+                    reifier::BinOp::Lt if int_cmp => (BinOp::LtI, false),
+                    reifier::BinOp::Leq if int_cmp => (BinOp::LeqI, false),
+                    reifier::BinOp::Gt if int_cmp => (BinOp::LtI, true),
+                    reifier::BinOp::Geq if int_cmp => (BinOp::LeqI, true),
                     reifier::BinOp::Eq if a_ty.is_float() => (BinOp::EqF, false),
                     reifier::BinOp::Neq if a_ty.is_float() => (BinOp::NeqF, false),
                     reifier::BinOp::Lt if a_ty.is_float() => (BinOp::LtF, false),
@@ -415,27 +869,50 @@ impl<'a> Lowerer<'a> {
                     | reifier::BinOp::Leq
                     | reifier::BinOp::Gt
                     | reifier::BinOp::Geq => unreachable!(),
-                    reifier::BinOp::Recv => todo!(),
+                    reifier::BinOp::Recv => {
+                        unreachable!("receives are reified into structural equalities")
+                    }
                     reifier::BinOp::BitOr => (BinOp::BitOrI, false),
                     reifier::BinOp::BitXor => (BinOp::BitXorI, false),
                     reifier::BinOp::BitAnd => (BinOp::BitAndI, false),
                     reifier::BinOp::Shl => (BinOp::BitShlI, false),
+                    reifier::BinOp::Shr if unsigned => (BinOp::BitShrU, false),
                     reifier::BinOp::Shr => (BinOp::BitShrI, false),
+                    reifier::BinOp::Mod if unsigned => (BinOp::ModU, false),
                     reifier::BinOp::Mod => (BinOp::ModI, false),
                 };
 
-                if flip {
-                    Some(self.load(Producer::Binary(op, b, a)))
+                if self.checked
+                    && matches!(op, BinOp::DivI | BinOp::ModI | BinOp::DivU | BinOp::ModU)
+                {
+                    self.check_divisor(b, expr.span);
+                }
+                let out = if flip {
+                    self.load(Producer::Binary(op, b, a))
                 } else {
-                    Some(self.load(Producer::Binary(op, a, b)))
+                    self.load(Producer::Binary(op, a, b))
+                };
+                if let (true, Some(int)) = (self.checked, int) {
+                    self.check_overflow(op, (a, b), out, int, expr.span);
+                }
+                match (op, int) {
+                    // the other operations can't leave the range of their operands' type
+                    (
+                        BinOp::AddI | BinOp::SubI | BinOp::MulI | BinOp::DivI | BinOp::BitShlI,
+                        Some(int),
+                    ) => Some(self.wrap(out, int)),
+                    _ => Some(out),
                 }
             }
             reifier::ExprKind::Unary(reifier::UnOp::Neg, a) if want_output => {
-                if a.ty.is_int() {
+                if let Some(int) = self.ty(&a.ty).int_type() {
                     let a = self.expr(a, true).unwrap();
                     let out = self.load(Producer::Unary(UnOp::NegI, a));
-                    Some(out)
-                } else if a.ty.is_float() {
+                    if self.checked {
+                        self.check_negation(a, out, int, expr.span);
+                    }
+                    Some(self.wrap(out, int))
+                } else if self.ty(&a.ty).is_float() {
                     let a = self.expr(a, true).unwrap();
                     let out = self.load(Producer::Unary(UnOp::NegF, a));
                     Some(out)
@@ -451,30 +928,32 @@ impl<'a> Lowerer<'a> {
             reifier::ExprKind::Unary(_, a) => self.expr(a, false),
             reifier::ExprKind::Apply(base, arg) => {
                 if let reifier::ExprKind::Constructor(_) = &base.kind {
-                    self.expr(&arg, want_output)
+                    self.expr(arg, want_output)
                 } else {
+                    // how the argument is passed depends on the callee's parameter type, which
+                    // the argument's own type may only be a subtype of
+                    let reifier::Type::Function(param, ret) = self.ty(&base.ty) else {
+                        panic!("call of a non-function")
+                    };
                     let base = self.expr(base, true).unwrap();
-                    let args = if let reifier::Type::Tuple(items) = &arg.ty {
-                        if items.len() > 0 {
-                            let arg = self.expr(arg, true).unwrap();
-                            let mut args = Vec::with_capacity(items.len());
-                            for i in 0..items.len() {
-                                let temp = self.load(Producer::Memory(
-                                    Kind::of(&items[i]),
-                                    MemRef(arg, i as u64 * 8),
-                                ));
-                                args.push(temp);
-                            }
-
-                            args.into_boxed_slice()
-                        } else {
-                            Box::new([])
+                    let args: Box<[Temp]> = match (param.as_deref(), &arg.kind) {
+                        (Some(reifier::Type::Tuple(_)), reifier::ExprKind::Tuple(items)) => {
+                            items.iter().map(|item| self.expr(item, true).unwrap()).collect()
                         }
-                    } else {
-                        Box::new([self.expr(arg, true).unwrap()])
+                        (Some(reifier::Type::Tuple(tys)), _) => {
+                            let arg = self.expr(arg, !tys.is_empty());
+                            (0..tys.len())
+                                .map(|i| {
+                                    let memref = MemRef(arg.unwrap(), i as u64 * 8);
+                                    self.load(Producer::Memory(Kind::of(&tys[i]), memref))
+                                })
+                                .collect()
+                        }
+                        (Some(_), _) => Box::new([self.expr(arg, true).unwrap()]),
+                        (None, _) => Box::new([]),
                     };
 
-                    let out = self.load(Producer::Call(base, args, Kind::of(&arg.ty)));
+                    let out = self.load(Producer::Call(base, args, Kind::of(&ret)));
 
                     if want_output {
                         Some(out)
@@ -485,18 +964,19 @@ impl<'a> Lowerer<'a> {
             }
             reifier::ExprKind::Variant(name, data) => {
                 if want_output {
-                    let item_ty = VariantItemType {
-                        name: *name,
-                        inner: data.as_ref().map(|d| d.ty.clone()),
-                    };
-
-                    let id = self.variant_id(item_ty);
+                    let id = self.variant_id(*name);
 
-                    let out = self.alloc(if data.is_some() { 16 } else { 8 });
+                    let out = if let Some(data) = data {
+                        let id_ty = reifier::Type::Primitive(reifier::PrimitiveType::Integer);
+                        let map = self.pointer_map([&id_ty, &data.ty]);
+                        self.alloc(16, map)
+                    } else {
+                        self.alloc(8, 0)
+                    };
                     let id_temp = self.load(Producer::ConstI(id));
                     self.store(MemRef(out, 0), id_temp);
                     if let Some(data) = data {
-                        let data = self.expr(&data, true).unwrap();
+                        let data = self.expr(data, true).unwrap();
                         self.store(MemRef(out, 8), data);
                     }
 
@@ -511,30 +991,170 @@ impl<'a> Lowerer<'a> {
             }
             reifier::ExprKind::Load(_) | reifier::ExprKind::Constructor(_) if !want_output => None,
             reifier::ExprKind::Load(sym) | reifier::ExprKind::Constructor(sym) => {
-                Some(self.symbol(sym))
+                Some(self.symbol(sym, &expr.ty))
             }
             reifier::ExprKind::Literal(_) if !want_output => None,
-            reifier::ExprKind::Literal(lit) => Some(match lit {
-                &reifier::Literal::Boolean(b) => self.load(Producer::ConstI(b as u64)),
-                &reifier::Literal::Integer(i) => self.load(Producer::ConstI(i)),
-                &reifier::Literal::Float(f) => self.load(Producer::ConstF(f)),
-                _ => todo!(),
+            reifier::ExprKind::Literal(lit) => Some(match *lit {
+                reifier::Literal::Boolean(b) => self.load(Producer::ConstI(b as u64)),
+                reifier::Literal::Integer(i) => {
+                    let i = match self.ty(&expr.ty).int_type() {
+                        Some(int) => int.wrap(i),
+                        None => i,
+                    };
+                    self.load(Producer::ConstI(i))
+                }
+                reifier::Literal::Float(f) => self.load(Producer::ConstF(f)),
+                reifier::Literal::String(string) => {
+                    let id = self.string_id(string);
+                    self.load(Producer::String(id))
+                }
             }),
         }
     }
 
-    fn variant_id(&mut self, item_ty: VariantItemType<'a>) -> u64 {
-        if let Some(&id) = self.variant_ids.get(&item_ty) {
-            id
+    /// Lifts the body of the closure `expr` into its own function, and allocates a closure
+    /// that holds it, along with the value of every local the body uses from outside.
+    ///
+    /// Locals are captured by their value when the closure is created. The reifier makes sure
+    /// that neither the body nor the function around it `set`s a captured `var` afterwards, so
+    /// the copy is always the local's current value.
+    fn closure(
+        &mut self,
+        expr: &reifier::Expr<'a>,
+        spec: bool,
+        arg: Option<&reifier::Pattern<'a>>,
+        body: &reifier::Expr<'a>,
+    ) -> Temp {
+        let captured = self.reified_module.free_locals(expr);
+        let tys: Vec<_> = captured
+            .iter()
+            .map(|sym| self.ty(&self.reified_module.locals[sym].ty))
+            .collect();
+
+        let mut lowerer = Self::new(
+            self.reified_module,
+            self.variant_ids,
+            self.instances,
+            self.string_ids,
+            self.closures,
+            self.subst.clone(),
+            self.checked,
+        );
+
+        // the body gets the closure as an extra first argument, and loads what it captured
+        // back out of it
+        let closure_arg = lowerer.new_temp(Kind::Integer);
+        lowerer.args.push(closure_arg);
+        for (i, (sym, ty)) in captured.iter().zip(&tys).enumerate() {
+            let offset = (i + 1) as u64 * 8;
+            let temp = lowerer.load(Producer::Memory(Kind::of(ty), MemRef(closure_arg, offset)));
+            lowerer.vals.insert(*sym, temp);
+        }
+
+        let cfg = lowerer.lower(spec, arg, body);
+        let id = {
+            let mut closures = self.closures.borrow_mut();
+            closures.push(cfg);
+            closures.len() - 1
+        };
+
+        let handle_ty = reifier::Type::Primitive(reifier::PrimitiveType::Integer);
+        let map = self.pointer_map([&handle_ty].into_iter().chain(&tys));
+        let closure = self.alloc(8 * (captured.len() + 1) as u64, map);
+        let handle = self.load(Producer::Closure(id));
+        self.store(MemRef(closure, 0), handle);
+        for (i, (sym, ty)) in captured.iter().zip(&tys).enumerate() {
+            let value = self.symbol(sym, ty);
+            self.store(MemRef(closure, (i + 1) as u64 * 8), value);
+        }
+
+        closure
+    }
+
+    /// Collects the largest static subexpressions of `expr` that can be computed with only
+    /// the values available before specialization.
+    fn collect_hoisted<'e>(
+        &self,
+        expr: &'e reifier::Expr<'a>,
+        hoisted: &mut Vec<&'e reifier::Expr<'a>>,
+    ) {
+        let trivial = matches!(
+            expr.kind,
+            reifier::ExprKind::Load(_)
+                | reifier::ExprKind::Constructor(_)
+                | reifier::ExprKind::Literal(_)
+        );
+
+        if expr.bt == reifier::BindingTime::Static && !trivial && self.is_closed(expr) {
+            hoisted.push(expr);
         } else {
-            let id = self.variant_id_counter;
-            self.variant_id_counter += 1;
-            self.variant_ids.insert(item_ty.clone(), id);
-            id
+            // computing code that the generated code might skip could fail, or waste work,
+            // where the generated code never would, so only what always runs is hoisted
+            for child in always_run(expr) {
+                self.collect_hoisted(child, hoisted);
+            }
+        }
+    }
+
+    /// Whether every local `expr` refers to has already been lowered.
+    fn is_closed(&self, expr: &reifier::Expr<'a>) -> bool {
+        match &expr.kind {
+            reifier::ExprKind::Load(sym) => {
+                !self.reified_module.locals.contains_key(sym)
+                    || self.vals.contains_key(sym)
+                    || self.vars.contains_key(sym)
+            }
+            // patterns introduce new locals, which the hoisted code would need to bind
+            reifier::ExprKind::StructuralEq(..) => false,
+            _ => expr.children().into_iter().all(|child| self.is_closed(child)),
         }
     }
 
+    fn load_hoisted(&mut self, expr: &reifier::Expr<'a>) -> Option<Temp> {
+        let &offset = self.hoisted.get(&(expr as *const _))?;
+        let reifying_args = self.args[0];
+        Some(self.load(Producer::Memory(
+            self.kind(&expr.ty),
+            MemRef(reifying_args, offset),
+        )))
+    }
+
+    /// The id stored in the first word of every variant named `name`.
+    ///
+    /// Ids only depend on the name, since a variant's payload always takes up one word no
+    /// matter its type.
+    fn variant_id(&mut self, name: Intern<'a>) -> u64 {
+        let mut variant_ids = self.variant_ids.borrow_mut();
+        let next_id = variant_ids.len() as u64;
+        *variant_ids.entry(name).or_insert(next_id)
+    }
+
+    /// The index of the string literal `string` in [`Module::strings`].
+    fn string_id(&mut self, string: Intern<'a>) -> usize {
+        let mut string_ids = self.string_ids.borrow_mut();
+        let next_id = string_ids.len();
+        *string_ids.entry(string).or_insert(next_id)
+    }
+
+    /// Compares the strings `a` and `b`, giving an `Int` that's ordered against zero the
+    /// way `a` is ordered against `b`, along with that zero.
+    fn compare_strings(&mut self, a: Temp, b: Temp) -> (Temp, Temp) {
+        let ordering = self.call_builtin(Builtin::Compare, Box::new([a, b]));
+        let zero = self.load(Producer::ConstI(0));
+        (ordering, zero)
+    }
+
     fn cond(&mut self, expr: &reifier::Expr<'a>, jump_cond: JumpCond) {
+        if let Some(temp) = self.load_hoisted(expr) {
+            let zero = self.load(Producer::ConstI(0));
+            let (cmp, lab) = match jump_cond {
+                JumpCond::True(lab) => (BranchCmp::Neq, lab),
+                JumpCond::False(lab) => (BranchCmp::Eq, lab),
+            };
+            self.branch(Branch(cmp, temp, zero, lab));
+            return;
+        }
+
         match &expr.kind {
             reifier::ExprKind::Scope(scope) => {
                 let len = scope.exprs.len();
@@ -572,18 +1192,12 @@ impl<'a> Lowerer<'a> {
                 match jump_cond {
                     JumpCond::False(on_false) => {
                         self.structural_eq(pat, val, on_false, &mut assignments);
-                        for (sym, temp) in assignments {
-                            let var = self.vars.get(&sym).unwrap();
-                            self.set_var(*var, temp);
-                        }
+                        self.assign(assignments);
                     }
                     JumpCond::True(on_true) => {
                         let on_false = self.new_label();
                         self.structural_eq(pat, val, on_false, &mut assignments);
-                        for (sym, temp) in assignments {
-                            let var = self.vars.get(&sym).unwrap();
-                            self.set_var(*var, temp);
-                        }
+                        self.assign(assignments);
                         self.ctrl(Ctrl::Jump(on_true));
                         self.set_label_target(on_false);
                     }
@@ -623,8 +1237,34 @@ impl<'a> Lowerer<'a> {
                 a,
                 b,
             ) => {
+                let is_string = self.ty(&a.ty).is_string();
+                let is_float = self.ty(&a.ty).is_float();
+                let unsigned = self.ty(&a.ty).int_type().is_some_and(|int| !int.signed);
                 let a = self.expr(a, true).unwrap();
                 let b = self.expr(b, true).unwrap();
+                let (a, b) = if is_string {
+                    self.compare_strings(a, b)
+                } else {
+                    (a, b)
+                };
+
+                // every ordering of NaN is false, so unlike for integers, jumping when one is
+                // false can't be done by jumping when the opposite ordering is true
+                let ordering = !matches!(op, reifier::BinOp::Eq | reifier::BinOp::Neq);
+                if let (true, true, JumpCond::False(on_false)) = (is_float, ordering, jump_cond) {
+                    let (cmp, a, b) = match op {
+                        reifier::BinOp::Lt => (BranchCmp::Lt, a, b),
+                        reifier::BinOp::Leq => (BranchCmp::Geq, b, a),
+                        reifier::BinOp::Gt => (BranchCmp::Lt, b, a),
+                        reifier::BinOp::Geq => (BranchCmp::Geq, a, b),
+                        _ => unreachable!(),
+                    };
+                    let on_true = self.new_label();
+                    self.branch(Branch(cmp, a, b, on_true));
+                    self.ctrl(Ctrl::Jump(on_false));
+                    self.set_label_target(on_true);
+                    return;
+                }
 
                 let (cmp, a, b, lab) = match jump_cond {
                     JumpCond::False(on_false) => match op {
@@ -646,19 +1286,26 @@ impl<'a> Lowerer<'a> {
                         _ => unreachable!(),
                     },
                 };
+                let cmp = match cmp {
+                    BranchCmp::Lt if unsigned => BranchCmp::LtU,
+                    BranchCmp::Geq if unsigned => BranchCmp::GeqU,
+                    cmp => cmp,
+                };
 
                 self.branch(Branch(cmp, a, b, lab))
             }
             reifier::ExprKind::Unary(reifier::UnOp::Not, e) => {
                 self.cond(
-                    &e,
+                    e,
                     match jump_cond {
                         JumpCond::True(on_true) => JumpCond::False(on_true),
                         JumpCond::False(on_false) => JumpCond::True(on_false),
                     },
                 );
             }
-            reifier::ExprKind::Apply(..) => {
+            reifier::ExprKind::Apply(..)
+            | reifier::ExprKind::Field(..)
+            | reifier::ExprKind::Index(..) => {
                 let res = self.expr(expr, true).unwrap();
                 let zero = self.load(Producer::ConstI(0));
                 let (cmp, lab) = match jump_cond {
@@ -669,7 +1316,7 @@ impl<'a> Lowerer<'a> {
             }
             reifier::ExprKind::Load(sym) | reifier::ExprKind::Constructor(sym) => {
                 let zero = self.load(Producer::ConstI(0));
-                let temp = self.symbol(sym);
+                let temp = self.symbol(sym, &expr.ty);
                 let (cmp, lab) = match jump_cond {
                     JumpCond::True(lab) => (BranchCmp::Neq, lab),
                     JumpCond::False(lab) => (BranchCmp::Eq, lab),
@@ -692,8 +1339,16 @@ impl<'a> Lowerer<'a> {
             | reifier::ExprKind::Unary(reifier::UnOp::Neg, ..)
             | reifier::ExprKind::Binary(..)
             | reifier::ExprKind::Tuple(..)
+            | reifier::ExprKind::Record(..)
+            | reifier::ExprKind::Array(..)
             | reifier::ExprKind::For { .. }
             | reifier::ExprKind::Abstract { .. } => panic!("invalid type for cond? {expr:?}"),
+            // these jump elsewhere, so the condition is never decided
+            reifier::ExprKind::Break
+            | reifier::ExprKind::Continue
+            | reifier::ExprKind::Return(_) => {
+                self.expr(expr, false);
+            }
         }
     }
 
@@ -702,34 +1357,39 @@ impl<'a> Lowerer<'a> {
         pat: &reifier::Pattern<'a>,
         val: Temp,
         fail_lab: LabelRef,
-        assignments: &mut Vec<(reifier::Symbol, Temp)>,
+        assignments: &mut Vec<Assignment>,
     ) {
         match &pat.kind {
             reifier::PatternKind::Apply(_a, b) => self.structural_eq(b, val, fail_lab, assignments),
             reifier::PatternKind::Variant(name, data) => {
-                let item_ty = VariantItemType {
-                    name: *name,
-                    inner: data.as_ref().map(|d| d.ty.clone()),
-                };
-
-                let pat_id = self.variant_id(item_ty);
+                let pat_id = self.variant_id(*name);
                 let pat_id_temp = self.load(Producer::ConstI(pat_id));
                 let val_id_temp = self.load(Producer::Memory(Kind::Integer, MemRef(val, 0)));
                 self.branch(Branch(BranchCmp::Neq, pat_id_temp, val_id_temp, fail_lab));
                 if let Some(data) = &data {
-                    let val_data = self.load(Producer::Memory(Kind::of(&data.ty), MemRef(val, 8)));
+                    let val_data = self.load(Producer::Memory(self.kind(&data.ty), MemRef(val, 8)));
                     self.structural_eq(data, val_data, fail_lab, assignments);
                 }
             }
             reifier::PatternKind::Tuple(items) => {
                 for (i, item) in items.iter().enumerate() {
                     let item_val = self.load(Producer::Memory(
-                        Kind::of(&item.ty),
+                        self.kind(&item.ty),
                         MemRef(val, i as u64 * 8),
                     ));
                     self.structural_eq(item, item_val, fail_lab, assignments);
                 }
             }
+            reifier::PatternKind::Record(fields) => {
+                for (slot, field) in fields.iter().enumerate() {
+                    let Some(field) = field else { continue };
+                    let field_val = self.load(Producer::Memory(
+                        self.kind(&field.ty),
+                        MemRef(val, slot as u64 * 8),
+                    ));
+                    self.structural_eq(field, field_val, fail_lab, assignments);
+                }
+            }
             &reifier::PatternKind::Solve(reifier::SolveMarker::Val, sym) => {
                 self.vals.insert(sym, val);
             }
@@ -738,7 +1398,13 @@ impl<'a> Lowerer<'a> {
                 self.vars.insert(sym, var);
             }
             &reifier::PatternKind::Solve(reifier::SolveMarker::Set, sym) => {
-                assignments.push((sym, val));
+                assignments.push(Assignment::Var(sym, val));
+            }
+            reifier::PatternKind::SetIndex(array, index) => {
+                let array = self.expr(array, true).unwrap();
+                let index = self.expr(index, true).unwrap();
+                let element = self.element(array, index);
+                assignments.push(Assignment::Store(element, val));
             }
             reifier::PatternKind::Symbol(_) => panic!("unexpected symbol in pattern"),
         }
@@ -750,12 +1416,22 @@ impl<'a> Lowerer<'a> {
             reifier::PatternKind::Tuple(items) => {
                 for (i, item) in items.iter().enumerate() {
                     let item_val = self.load(Producer::Memory(
-                        Kind::of(&item.ty),
+                        self.kind(&item.ty),
                         MemRef(val, i as u64 * 8),
                     ));
                     self.abstract_arg(item, item_val);
                 }
             }
+            reifier::PatternKind::Record(fields) => {
+                for (slot, field) in fields.iter().enumerate() {
+                    let Some(field) = field else { continue };
+                    let field_val = self.load(Producer::Memory(
+                        self.kind(&field.ty),
+                        MemRef(val, slot as u64 * 8),
+                    ));
+                    self.abstract_arg(field, field_val);
+                }
+            }
             &reifier::PatternKind::Solve(reifier::SolveMarker::Val, sym) => {
                 self.vals.insert(sym, val);
             }
@@ -765,44 +1441,305 @@ impl<'a> Lowerer<'a> {
             }
             reifier::PatternKind::Symbol(_)
             | reifier::PatternKind::Solve(reifier::SolveMarker::Set, ..)
+            | reifier::PatternKind::SetIndex(..)
             | reifier::PatternKind::Variant(..) => {
                 panic!("unsupported in abstraction arg: {pat:?}")
             }
         }
     }
 
-    fn symbol(&mut self, sym: &reifier::Symbol) -> Temp {
+    fn assign(&mut self, assignments: Vec<Assignment>) {
+        for assignment in assignments {
+            match assignment {
+                Assignment::Var(sym, temp) => {
+                    // the reifier only lets `set` assign to the `var`s of the function it's in,
+                    // which are declared before it
+                    let var = self.vars[&sym];
+                    self.set_var(var, temp);
+                }
+                Assignment::Store(dest, temp) => self.store(dest, temp),
+            }
+        }
+    }
+
+    /// Where element `index` of `array` is stored, aborting the program if the array
+    /// doesn't have that element.
+    fn element(&mut self, array: Temp, index: Temp) -> MemRef {
+        let len = self.load(Producer::Memory(Kind::Integer, MemRef(array, 0)));
+        let zero = self.load(Producer::ConstI(0));
+        let out_of_bounds = self.new_label();
+        let in_bounds = self.new_label();
+        self.branch(Branch(BranchCmp::Lt, index, zero, out_of_bounds));
+        self.branch(Branch(BranchCmp::Geq, index, len, out_of_bounds));
+        self.ctrl(Ctrl::Jump(in_bounds));
+
+        self.set_label_target(out_of_bounds);
+        self.call_builtin(Builtin::OutOfBounds, Box::new([index, len]));
+        self.set_label_target(in_bounds);
+
+        // elements come after the length
+        let three = self.load(Producer::ConstI(3));
+        let offset = self.load(Producer::Binary(BinOp::BitShlI, index, three));
+        let addr = self.load(Producer::Binary(BinOp::AddI, array, offset));
+        MemRef(addr, 8)
+    }
+
+    /// Aborts the program if `divisor` is zero.
+    fn check_divisor(&mut self, divisor: Temp, span: Span) {
+        let zero = self.load(Producer::ConstI(0));
+        let is_zero = self.new_label();
+        let nonzero = self.new_label();
+        self.branch(Branch(BranchCmp::Eq, divisor, zero, is_zero));
+        self.ctrl(Ctrl::Jump(nonzero));
+
+        self.set_label_target(is_zero);
+        self.trap(Builtin::ZeroDivisor, span);
+        self.set_label_target(nonzero);
+    }
+
+    /// Aborts the program if `out`, the result of the integer operation `op` on `a` and `b`,
+    /// overflowed `int`.
+    fn check_overflow(
+        &mut self,
+        op: BinOp,
+        (a, b): (Temp, Temp),
+        out: Temp,
+        int: reifier::IntType,
+        span: Span,
+    ) {
+        // unsigned quotients and remainders are never bigger than what's divided
+        if !matches!(op, BinOp::AddI | BinOp::SubI | BinOp::MulI | BinOp::DivI | BinOp::ModI) {
+            return;
+        }
+
+        let overflow = self.new_label();
+        let ok = self.new_label();
+        if op == BinOp::ModI {
+            // the remainder of the most negative integer by -1 is 0, but it's only defined
+            // where the quotient is, which overflows
+            let minus_one = self.load(Producer::ConstI(-1i64 as u64));
+            let min = self.load(Producer::ConstI((-1i64 << (int.bits - 1)) as u64));
+            self.branch(Branch(BranchCmp::Neq, b, minus_one, ok));
+            self.branch(Branch(BranchCmp::Eq, a, min, overflow));
+        } else if int.bits < 64 {
+            // operations on narrower integers can't overflow a word, so the result only has
+            // to fit in their type
+            let wrapped = self.wrap(out, int);
+            self.branch(Branch(BranchCmp::Neq, out, wrapped, overflow));
+        } else {
+            let zero = self.load(Producer::ConstI(0));
+            match (op, int.signed) {
+                // the sum's sign differs from both operands'
+                (BinOp::AddI, true) => {
+                    let a_out = self.load(Producer::Binary(BinOp::BitXorI, a, out));
+                    let b_out = self.load(Producer::Binary(BinOp::BitXorI, b, out));
+                    let both = self.load(Producer::Binary(BinOp::BitAndI, a_out, b_out));
+                    self.branch(Branch(BranchCmp::Lt, both, zero, overflow));
+                }
+                // the operands' signs differ, and so do the difference's and the first's
+                (BinOp::SubI, true) => {
+                    let a_b = self.load(Producer::Binary(BinOp::BitXorI, a, b));
+                    let a_out = self.load(Producer::Binary(BinOp::BitXorI, a, out));
+                    let both = self.load(Producer::Binary(BinOp::BitAndI, a_b, a_out));
+                    self.branch(Branch(BranchCmp::Lt, both, zero, overflow));
+                }
+                (BinOp::AddI, false) => self.branch(Branch(BranchCmp::LtU, out, a, overflow)),
+                (BinOp::SubI, false) => self.branch(Branch(BranchCmp::LtU, a, b, overflow)),
+                // the upper word of the product isn't just the sign of the lower word
+                (BinOp::MulI, true) => {
+                    let high = self.load(Producer::Binary(BinOp::MulHiI, a, b));
+                    let shift = self.load(Producer::ConstI(63));
+                    let sign = self.load(Producer::Binary(BinOp::BitShrI, out, shift));
+                    self.branch(Branch(BranchCmp::Neq, high, sign, overflow));
+                }
+                (BinOp::MulI, false) => {
+                    let high = self.load(Producer::Binary(BinOp::MulHiU, a, b));
+                    self.branch(Branch(BranchCmp::Neq, high, zero, overflow));
+                }
+                // only the most negative integer divided by -1 overflows, since unsigned
+                // quotients are `DivU`s
+                (_, _) => {
+                    let minus_one = self.load(Producer::ConstI(-1i64 as u64));
+                    let min = self.load(Producer::ConstI(i64::MIN as u64));
+                    self.branch(Branch(BranchCmp::Neq, b, minus_one, ok));
+                    self.branch(Branch(BranchCmp::Eq, a, min, overflow));
+                }
+            }
+        }
+        self.ctrl(Ctrl::Jump(ok));
+
+        self.set_label_target(overflow);
+        self.trap(Builtin::Overflow, span);
+        self.set_label_target(ok);
+    }
+
+    /// Aborts the program if `out`, the negation of `a`, overflowed `int`.
+    fn check_negation(&mut self, a: Temp, out: Temp, int: reifier::IntType, span: Span) {
+        let overflow = self.new_label();
+        let ok = self.new_label();
+        if int.bits < 64 {
+            let wrapped = self.wrap(out, int);
+            self.branch(Branch(BranchCmp::Neq, out, wrapped, overflow));
+        } else if int.signed {
+            // the most negative integer is the only one without a positive counterpart
+            let min = self.load(Producer::ConstI(i64::MIN as u64));
+            self.branch(Branch(BranchCmp::Eq, a, min, overflow));
+        } else {
+            // and zero is the only unsigned integer whose negation is unsigned
+            let zero = self.load(Producer::ConstI(0));
+            self.branch(Branch(BranchCmp::Neq, a, zero, overflow));
+        }
+        self.ctrl(Ctrl::Jump(ok));
+
+        self.set_label_target(overflow);
+        self.trap(Builtin::Overflow, span);
+        self.set_label_target(ok);
+    }
+
+    /// Calls the builtin that aborts the program because of an error at `span`.
+    fn trap(&mut self, builtin: Builtin, span: Span) {
+        let start = self.load(Producer::ConstI(span.start as u64));
+        let end = self.load(Producer::ConstI(span.end as u64));
+        self.call_builtin(builtin, Box::new([start, end]));
+    }
+
+    /// Loads the value of `sym`, which is used as a `ty`.
+    fn symbol(&mut self, sym: &reifier::Symbol, ty: &reifier::Type<'a>) -> Temp {
         if let Some(local_temp) = self.vals.get(sym) {
             *local_temp
         } else if let Some(var) = self.vars.get(sym) {
             self.get_var(*var)
         } else if let Some(def) = self.reified_module.defs.get(sym) {
-            self.load(Producer::Symbol(Kind::of(&def.body.ty), *sym))
+            let is_function = matches!(def.body.kind, reifier::ExprKind::Abstract { .. });
+            if is_function && is_generic(&def.body.ty) {
+                let instance = self.instance(*sym, &def.body.ty, &self.ty(ty));
+                self.load(Producer::Instance(instance))
+            } else {
+                self.load(Producer::Symbol(self.kind(&def.body.ty), *sym))
+            }
         } else if let Some(builtin) = self.reified_module.builtins.get(sym) {
             self.load(Producer::Builtin(builtin.0))
+        } else if self.reified_module.externs.contains_key(sym) {
+            self.load(Producer::Extern(*sym))
         } else {
             panic!("symbol not found: {sym:?}")
         }
     }
 
-    fn alloc(&mut self, bytes: u64) -> Temp {
-        let alloc = self.load(Producer::Builtin(Builtin::Alloc));
+    /// The index of the instance of the generic def `sym`, whose type is `generic`, that
+    /// has the type `ty`.
+    fn instance(
+        &mut self,
+        sym: reifier::Symbol,
+        generic: &reifier::Type<'a>,
+        ty: &reifier::Type<'a>,
+    ) -> usize {
+        let mut subst = FxHashMap::default();
+        generic.match_vars(ty, &mut subst);
+
+        let mut vars = Vec::new();
+        generic.vars(&mut vars);
+        // variables that nothing pins down, like those of defs it's mutually recursive
+        // with, are left generic
+        let args = vars
+            .iter()
+            .map(|var| subst.get(var).cloned().unwrap_or(reifier::Type::Var(*var)))
+            .collect();
+
+        let instances = &mut *self.instances.borrow_mut();
+        let next = instances.queue.len();
+        *instances.ids.entry((sym, args)).or_insert_with(|| {
+            instances.queue.push((sym, subst));
+            next
+        })
+    }
+
+    /// `ty`, with what the type variables stand for in the instance being lowered.
+    fn ty(&self, ty: &reifier::Type<'a>) -> reifier::Type<'a> {
+        ty.substitute(&self.subst)
+    }
+
+    fn kind(&self, ty: &reifier::Type<'a>) -> Kind {
+        Kind::of(&self.ty(ty))
+    }
+
+    /// The pointer map of an object whose words hold values of `tys`, in order.
+    fn pointer_map<'t>(&self, tys: impl IntoIterator<Item = &'t reifier::Type<'a>>) -> u64
+    where
+        'a: 't,
+    {
+        let tys: Vec<_> = tys.into_iter().map(|ty| self.ty(ty)).collect();
+        runtime::pointer_map(self.reified_module, &tys)
+    }
+
+    /// Allocates an object, where `pointer_map` is as described in [`runtime::Heap`].
+    fn alloc(&mut self, bytes: u64, pointer_map: u64) -> Temp {
         let bytes = self.load(Producer::ConstI(bytes));
-        self.load(Producer::Call(alloc, Box::new([bytes]), Kind::Integer))
+        let pointer_map = self.load(Producer::ConstI(pointer_map));
+        self.call_builtin(Builtin::Alloc, Box::new([bytes, pointer_map]))
+    }
+
+    /// Allocates a tuple of `items`, whose types are `tys`.
+    fn pack(&mut self, tys: &[reifier::Type<'a>], items: &[Temp]) -> Temp {
+        let out = self.alloc(items.len() as u64 * 8, self.pointer_map(tys));
+        for (i, &item) in items.iter().enumerate() {
+            self.store(MemRef(out, i as u64 * 8), item);
+        }
+        out
+    }
+
+    /// Calls one of the builtins that only the lowerer uses.
+    fn call_builtin(&mut self, builtin: Builtin, args: Box<[Temp]>) -> Temp {
+        let f = self.load(Producer::Builtin(builtin));
+        self.load(Producer::Call(f, args, Kind::of_sig(builtin.info().ret)))
+    }
+
+    /// A placeholder for the value of an expression that never produces one, in the code
+    /// after it, which is never reached.
+    fn unreachable_value(&mut self, kind: Kind) -> Temp {
+        match kind {
+            Kind::Integer => self.load(Producer::ConstI(0)),
+            Kind::Float => self.load(Producer::ConstF(0.0)),
+        }
+    }
+
+    /// `temp`, as the value of an expression of `kind`. Only the placeholder of an expression
+    /// that never produces a value can be of another kind, and it's replaced.
+    fn of_kind(&mut self, temp: Temp, kind: Kind) -> Temp {
+        if temp.kind == kind {
+            temp
+        } else {
+            self.unreachable_value(kind)
+        }
+    }
+
+    /// `temp`, wrapped around to `int` if it's narrower than a word.
+    fn wrap(&mut self, temp: Temp, int: reifier::IntType) -> Temp {
+        match int {
+            reifier::IntType { bits: 64, .. } => temp,
+            reifier::IntType { signed: true, bits } => {
+                self.load(Producer::Unary(UnOp::SignExtend(bits), temp))
+            }
+            reifier::IntType { signed: false, bits } => {
+                self.load(Producer::Unary(UnOp::ZeroExtend(bits), temp))
+            }
+        }
     }
 
     fn new_var(&mut self, temp: Temp) -> VarRef {
         let r = VarRef(self.generations.len());
         self.generations.push(temp);
+        self.assigned.insert(r);
         r
     }
 
     fn set_var(&mut self, var: VarRef, temp: Temp) {
-        self.generations[var.0] = temp
+        self.generations[var.0] = temp;
+        self.assigned.insert(var);
     }
 
     fn get_var(&mut self, var: VarRef) -> Temp {
-        if self.live.contains_key(&var) {
+        if self.live.contains_key(&var) || self.assigned.contains(&var) {
             self.generations[var.0]
         } else {
             let kind = self.generations[var.0].kind;
@@ -825,7 +1762,19 @@ impl<'a> Lowerer<'a> {
         LabelRef(idx)
     }
 
+    /// Starts a new block at `label`, which any earlier or later code may jump to.
     fn set_label_target(&mut self, label: LabelRef) {
+        self.split_block(label);
+
+        // code lowered before this point may have set variables on paths that don't lead
+        // here, so every variable is passed in from each jump instead
+        for var in 0..self.generations.len() {
+            self.get_var(VarRef(var));
+        }
+    }
+
+    /// Starts a new block at `label`, which only the current block falls through to.
+    fn split_block(&mut self, label: LabelRef) {
         self.finalize_block(Ctrl::Jump(label));
         self.labels[label.0] = Some(BlockRef(self.blocks.len()));
     }
@@ -842,7 +1791,7 @@ impl<'a> Lowerer<'a> {
 
     fn insn(&mut self, insn: Insn) {
         let lab = self.new_label();
-        self.set_label_target(lab);
+        self.split_block(lab);
 
         self.insns.push(insn);
     }
@@ -853,7 +1802,7 @@ impl<'a> Lowerer<'a> {
 
     fn branch(&mut self, branch: Branch<LabelRef>) {
         let lab = self.new_label();
-        self.set_label_target(lab);
+        self.split_block(lab);
 
         self.branch = Some(branch);
     }
@@ -862,6 +1811,7 @@ impl<'a> Lowerer<'a> {
         let current_insns = std::mem::take(&mut self.insns);
         let current_branch = self.branch.take();
         let current_live = std::mem::take(&mut self.live);
+        let assigned = std::mem::take(&mut self.assigned);
         let mut param_vars = Vec::with_capacity(current_live.len());
         let mut params = Vec::with_capacity(current_live.len());
         for (var, temp) in current_live {
@@ -879,10 +1829,40 @@ impl<'a> Lowerer<'a> {
         self.block_meta.push(BlockMeta {
             param_vars,
             generations,
+            assigned,
         });
     }
 }
 
+/// The children of `expr` that run whenever it does, which stop at the first expression of a
+/// scope that may leave it early.
+fn always_run<'e, 'a>(expr: &'e reifier::Expr<'a>) -> Vec<&'e reifier::Expr<'a>> {
+    match &expr.kind {
+        reifier::ExprKind::Scope(scope) => {
+            let end = scope.exprs.iter().position(may_jump);
+            scope.exprs[..end.map_or(scope.exprs.len(), |i| i + 1)].iter().collect()
+        }
+        reifier::ExprKind::Abstract { .. } => vec![],
+        reifier::ExprKind::For { init, cond, .. } => {
+            init.iter().chain([cond]).map(|e| &**e).collect()
+        }
+        reifier::ExprKind::Case { cond, .. } => vec![cond],
+        reifier::ExprKind::Binary(reifier::BinOp::And | reifier::BinOp::Or, a, _) => vec![a],
+        _ => expr.children(),
+    }
+}
+
+/// Whether `expr` may `break`, `continue` or `return` out of the code around it.
+fn may_jump(expr: &reifier::Expr) -> bool {
+    match &expr.kind {
+        reifier::ExprKind::Break | reifier::ExprKind::Continue | reifier::ExprKind::Return(_) => {
+            true
+        }
+        reifier::ExprKind::Abstract { .. } => false,
+        _ => expr.children().into_iter().any(may_jump),
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum JumpCond {
     True(LabelRef),
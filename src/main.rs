@@ -1,27 +1,52 @@
-mod char_reader;
-mod errors;
-mod parser;
-mod strings;
-mod tokenizer;
-mod reifier;
-mod lowerer;
-mod optimizers;
-mod backends;
+use std::path::Path;
+
+use codef::{backends::interp::RuntimeError, errors, loader, lowerer, reifier, strings};
 
 // Current plan: Parser (done) -> Reifier+Typeck (done) -> TAC+CFG+SSA (done) -> opts (none so far) -> RISC-V (in progress - regalloc)
 
 fn main() {
-    let path = std::env::args().nth(1).unwrap();
+    let mut show_bta = false;
+    let mut run = false;
+    let mut checked = false;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match &*arg {
+            "--bta" => show_bta = true,
+            "--run" => run = true,
+            "--checked" => checked = true,
+            _ => path = Some(arg),
+        }
+    }
+    let path = path.unwrap();
+    let path = Path::new(&path);
     let strings = strings::Strings::new();
-    let toks = tokenizer::Tokens::of(
-        char_reader::IoCharReader::<256, _>::new(std::fs::File::open(path).unwrap()),
-        &strings,
-    );
+    if run {
+        let options = codef::Options { checked };
+        let mut program = codef::compile_file_with(&strings, path, options).unwrap();
+        let main = program.def("main").expect("no main function");
+        match program.call(&main, &[]) {
+            Ok(_) => (),
+            Err(codef::Error::Runtime(RuntimeError::Exit(status))) => {
+                std::process::exit(status as i32)
+            }
+            Err(err) => {
+                eprintln!("ERROR: {err:?}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let source = std::fs::read_to_string(path).unwrap();
     let errs = errors::ErrorStream::new();
-    let (strings, tree) = parser::parse(toks, &errs).unwrap();
-    //println!("{:#?}", tree);
-    let reified = reifier::reify(strings, &tree).unwrap();
+    let name = path.file_stem().and_then(|stem| stem.to_str());
+    let sources = loader::load(&strings, &errs, &source, name, path.parent().unwrap()).unwrap();
+    //println!("{:#?}", sources);
+    let reified = reifier::reify(&strings, &errs, &sources).unwrap();
     //println!("\n\n\n\nREIFIED:\n{reified:#?}");
-    let cfg = lowerer::lower(&reified);
+    if show_bta {
+        print!("{}", reifier::annotate_binding_times(&reified));
+        return;
+    }
+    let cfg = lowerer::lower(&reified, lowerer::Options { checked });
     dbg!(cfg);
 }
@@ -3,10 +3,9 @@ pub mod simplify;
 use crate::lowerer::*;
 
 pub fn optimize(module: &mut Module) {
-    for (_, def) in &mut module.defs {
-        match &mut def.value {
-            Value::Function(cfg) => simplify::Simplify {}.run(cfg),
-            _ => (),
+    for def in module.defs.values_mut() {
+        if let Value::Function(cfg) = &mut def.value {
+            simplify::Simplify {}.run(cfg);
         }
     }
 }
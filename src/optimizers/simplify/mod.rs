@@ -5,7 +5,7 @@ pub struct Simplify {
 }
 
 impl OptimizationPass for Simplify {
-    fn run(&mut self, cfg: &mut Cfg) {
+    fn run(&mut self, _cfg: &mut Cfg) {
         todo!()
     }
 }
@@ -4,6 +4,8 @@ use crate::{tokenizer::{Span}, strings::Intern};
 pub struct Scope<'s> {
     pub defs: Box<[Def<'s>]>,
     pub typedefs: Box<[Def<'s>]>,
+    pub externs: Box<[Extern<'s>]>,
+    pub uses: Box<[Use<'s>]>,
     pub exprs: Box<[Expr<'s>]>,
     pub discard: bool,
 }
@@ -12,9 +14,32 @@ pub struct Scope<'s> {
 pub struct Def<'s> {
     pub decl_span: Span,
     pub name: Intern<'s>,
+    /// Whether it was declared `pub`, so other modules can refer to it.
+    pub public: bool,
+    /// Whether it was declared `unchecked`, so its arithmetic is never checked.
+    pub unchecked: bool,
+    /// The type parameters in `[..]` after the name, if it's generic.
+    pub params: Box<[Intern<'s>]>,
     pub value: Box<Expr<'s>>,
 }
 
+/// A `use path::to::module;`, which brings the module into scope under its last name.
+#[derive(Debug)]
+pub struct Use<'s> {
+    pub decl_span: Span,
+    pub path: Box<[Intern<'s>]>,
+}
+
+/// A function implemented outside of codef, declared with `use`.
+#[derive(Debug)]
+pub struct Extern<'s> {
+    pub decl_span: Span,
+    pub name: Intern<'s>,
+    pub library: Option<Intern<'s>>,
+    pub arg: Box<Expr<'s>>,
+    pub ret: Option<Box<Expr<'s>>>,
+}
+
 #[derive(Debug)]
 pub struct Expr<'s> {
     pub kind: ExprKind<'s>,
@@ -36,6 +61,23 @@ pub enum ExprKind<'s> {
         afterthought: Option<Box<Expr<'s>>>,
         body: Box<Expr<'s>>,
     },
+    /// `for pattern in iterable`, which runs the body for each element of the iterable that
+    /// matches the pattern.
+    ForIn {
+        pattern: Box<Expr<'s>>,
+        iterable: Box<Expr<'s>>,
+        body: Box<Expr<'s>>,
+    },
+    /// `start..end`, the integers from `start` up to but not including `end`, which can only
+    /// be iterated over.
+    Range(Box<Expr<'s>>, Box<Expr<'s>>),
+    /// `break`, which leaves the innermost loop.
+    Break,
+    /// `continue`, which goes on to the next iteration of the innermost loop.
+    Continue,
+    /// `return value`, which leaves the innermost function with the value, or with `()` if
+    /// there's none.
+    Return(Option<Box<Expr<'s>>>),
     Case {
         cond: Box<Expr<'s>>,
         on_true: Box<Expr<'s>>,
@@ -53,6 +95,14 @@ pub enum ExprKind<'s> {
     Apply(Box<Expr<'s>>, Box<Expr<'s>>),
     Solve(SolveMarker, Intern<'s>),
     Variant(Box<[VariantItem<'s>]>),
+    Record(Box<[RecordField<'s>]>),
+    /// A record's field, named after the `.`.
+    Field(Box<Expr<'s>>, Intern<'s>),
+    Array(Box<[Expr<'s>]>),
+    /// An element of an array, `array[index]`.
+    Index(Box<Expr<'s>>, Box<Expr<'s>>),
+    /// `set array[index]`, which stores what it's matched against in the element.
+    SetIndex(Box<Expr<'s>>, Box<Expr<'s>>),
     Name(Intern<'s>),
     Literal(Literal<'s>),
 }
@@ -107,3 +157,11 @@ pub struct VariantItem<'s> {
     pub value: Option<Box<Expr<'s>>>,
     pub span: Span,
 }
+
+/// A `name = value` in a record, between `.{` and `}`.
+#[derive(Debug)]
+pub struct RecordField<'s> {
+    pub name: Intern<'s>,
+    pub value: Box<Expr<'s>>,
+    pub span: Span,
+}
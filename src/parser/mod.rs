@@ -1,7 +1,7 @@
 use crate::{
     char_reader::CharReader,
     errors::ErrorStream,
-    tokenizer::{Span, Token, TokenKind, TokenizationError, Tokens}, strings::Strings,
+    tokenizer::{Span, Token, TokenKind, TokenizationError, Tokens}, strings::{Intern, Strings},
 };
 
 mod ast;
@@ -35,19 +35,21 @@ type Result<'s, T> = std::result::Result<T, ParseError<'s>>;
 
 pub fn parse<'s>(
     tokens: Tokens<'s, impl CharReader>,
-    errors: &'s ErrorStream<'s>,
+    errors: &ErrorStream<'s>,
 ) -> Result<'s, (&'s Strings, Expr<'s>)> {
     let mut parser = Parser { tokens, errors };
     let res = parser.parse()?;
     Ok((parser.tokens.strings, res))    
 }
 
-struct Parser<'s, R> {
+struct Parser<'e, 's, R> {
     tokens: Tokens<'s, R>,
-    errors: &'s ErrorStream<'s>,
+    // nothing recovers from errors to report them here yet
+    #[allow(dead_code)]
+    errors: &'e ErrorStream<'s>,
 }
 
-impl<'i, 's, R: CharReader> Parser<'s, R> {
+impl<'e, 's, R: CharReader> Parser<'e, 's, R> {
     fn parse(&mut self) -> Result<'s, Expr<'s>> {
         self.scope(bpred!())
     }
@@ -57,23 +59,53 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
         let mut end = 0;
         let mut defs = Vec::new();
         let mut types = Vec::new();
+        let mut externs = Vec::new();
+        let mut uses = Vec::new();
         let mut exprs = Vec::with_capacity(1);
         let mut first = true;
         let mut discard = false;
         while self.tokens.peek()?.is_some() && !self.has_peek(&end_pred)? {
             let mut stop = false;
+            let public = self.eat(vpred!(:t: TokenKind::Pub => t.span))?;
+            let unchecked = self.eat(vpred!(:t: TokenKind::Unchecked => t.span))?;
             let span = if self.has_peek(bpred!(TokenKind::Def))? {
-                let def = self.def()?;
+                let def = self.def(public, unchecked)?;
                 let span = def.decl_span;
                 defs.push(def);
                 discard = true;
                 span
+            } else if unchecked.is_some() {
+                // only defs have arithmetic to check
+                return Err(ParseError {
+                    kind: ParseErrorKind::Unexpected(self.tokens.peek()?.cloned()),
+                    span: None,
+                });
             } else if self.has_peek(bpred!(TokenKind::Type))? {
-                let def = self.typedef()?;
+                let def = self.typedef(public)?;
                 let span = def.decl_span;
                 types.push(def);
                 discard = true;
                 span
+            } else if public.is_some() {
+                // only defs and types can be made public
+                return Err(ParseError {
+                    kind: ParseErrorKind::Unexpected(self.tokens.peek()?.cloned()),
+                    span: None,
+                });
+            } else if let Some(kw_tok) = self.eat(tpred!(TokenKind::Use))? {
+                if self.has_peek(bpred!(TokenKind::Name(_)))? {
+                    let use_ = self.use_(kw_tok.span)?;
+                    let span = use_.decl_span;
+                    uses.push(use_);
+                    discard = true;
+                    span
+                } else {
+                    let ext = self.extern_(kw_tok.span)?;
+                    let span = ext.decl_span;
+                    externs.push(ext);
+                    discard = true;
+                    span
+                }
             } else if self.has_peek(bpred!(TokenKind::Case))? {
                 let case = self.termcase()?;
                 let span = case.span;
@@ -109,9 +141,10 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
             }
         }
 
-        if defs.is_empty() && exprs.len() == 1 && !discard {
+        let no_decls = defs.is_empty() && types.is_empty() && externs.is_empty() && uses.is_empty();
+        if no_decls && exprs.len() == 1 && !discard {
             Ok(exprs.pop().unwrap())
-        } else if defs.is_empty() && exprs.is_empty() {
+        } else if no_decls && exprs.is_empty() {
             Ok(Expr {
                 span: Span { start, end },
                 kind: ExprKind::Tuple {
@@ -124,6 +157,8 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
                 kind: ExprKind::Scope(Scope {
                     defs: defs.into_boxed_slice(),
                     typedefs: types.into_boxed_slice(),
+                    externs: externs.into_boxed_slice(),
+                    uses: uses.into_boxed_slice(),
                     exprs: exprs.into_boxed_slice(),
                     discard,
                 }),
@@ -131,36 +166,104 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
         }
     }
 
-    fn def(&mut self) -> Result<'s, Def<'s>> {
+    fn def(&mut self, public: Option<Span>, unchecked: Option<Span>) -> Result<'s, Def<'s>> {
         let kw_tok = self.require(tpred!(TokenKind::Def))?;
         let (name_span, name) = self.require(vpred!(:t: TokenKind::Name(n) => (t.span, n)))?;
+        let params = self.type_params()?;
         let abs = self.termexpr()?;
 
         Ok(Def {
             decl_span: Span {
-                start: kw_tok.span.start,
+                start: public.or(unchecked).unwrap_or(kw_tok.span).start,
                 end: name_span.end,
             },
             name,
+            public: public.is_some(),
+            unchecked: unchecked.is_some(),
+            params,
             value: Box::new(abs),
         })
     }
 
-    fn typedef(&mut self) -> Result<'s, Def<'s>> {
+    fn typedef(&mut self, public: Option<Span>) -> Result<'s, Def<'s>> {
         let kw_tok = self.require(tpred!(TokenKind::Type))?;
         let (name_span, name) = self.require(vpred!(:t: TokenKind::Name(n) => (t.span, n)))?;
+        let params = self.type_params()?;
         let value = self.termexpr()?;
 
         Ok(Def {
             decl_span: Span {
-                start: kw_tok.span.start,
+                start: public.unwrap_or(kw_tok.span).start,
                 end: name_span.end,
             },
             name,
+            public: public.is_some(),
+            unchecked: false,
+            params,
             value: Box::new(value),
         })
     }
 
+    fn type_params(&mut self) -> Result<'s, Box<[Intern<'s>]>> {
+        let mut params = Vec::new();
+        if self.eat(bpred!(TokenKind::OpenBracket))?.is_some() {
+            while !self.has_peek(bpred!(TokenKind::CloseBracket))? {
+                params.push(self.require(vpred!(TokenKind::Name(n) => n))?);
+                if self.eat(bpred!(TokenKind::Comma))?.is_none() {
+                    break;
+                }
+            }
+            self.require(bpred!(TokenKind::CloseBracket))?;
+        }
+
+        Ok(params.into_boxed_slice())
+    }
+
+    fn use_(&mut self, kw_span: Span) -> Result<'s, Use<'s>> {
+        let mut path = vec![self.require(vpred!(TokenKind::Name(n) => n))?];
+        while self.eat(bpred!(TokenKind::ColonColon))?.is_some() {
+            path.push(self.require(vpred!(TokenKind::Name(n) => n))?);
+        }
+        let semi = self.require(tpred!(TokenKind::Semicolon))?;
+
+        Ok(Use {
+            decl_span: Span {
+                start: kw_span.start,
+                end: semi.span.end,
+            },
+            path: path.into_boxed_slice(),
+        })
+    }
+
+    fn extern_(&mut self, kw_span: Span) -> Result<'s, Extern<'s>> {
+        let library = self.eat(vpred!(TokenKind::String(s) => s))?;
+        self.require(bpred!(TokenKind::Def))?;
+        let name = self.require(vpred!(TokenKind::Name(n) => n))?;
+        let Some(arg) = self.maybe_atom(false)? else {
+            return Err(ParseError {
+                kind: ParseErrorKind::Unexpected(self.tokens.peek()?.cloned()),
+                span: None,
+            });
+        };
+        let ret = if self.eat(bpred!(TokenKind::ThinArrow))?.is_some() {
+            Some(self.logical()?)
+        } else {
+            None
+        };
+        let semi = self.require(tpred!(TokenKind::Semicolon))?;
+
+        Ok(Extern {
+            decl_span: Span {
+                start: kw_span.start,
+                end: semi.span.end,
+            },
+            name,
+            library,
+            arg: Box::new(arg),
+            ret: ret.map(Box::new),
+        })
+    }
+
     fn termexpr(&mut self) -> Result<'s, Expr<'s>> {
         if self.has_peek(bpred!(
             TokenKind::Dollar | TokenKind::ThinArrow | TokenKind::FatArrow | TokenKind::OpenBrace
@@ -294,6 +397,32 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
     fn termfor(&mut self) -> Result<'s, Expr<'s>> {
         let for_tok = self.require(tpred!(TokenKind::For))?;
         let first = self.logical()?;
+        if self.eat(bpred!(TokenKind::In))?.is_some() {
+            let mut iterable = self.logical()?;
+            if self.eat(bpred!(TokenKind::DotDot))?.is_some() {
+                let end = self.logical()?;
+                iterable = Expr {
+                    span: Span {
+                        start: iterable.span.start,
+                        end: end.span.end,
+                    },
+                    kind: ExprKind::Range(Box::new(iterable), Box::new(end)),
+                };
+            }
+            let body = self.termbody()?;
+
+            return Ok(Expr {
+                span: Span {
+                    start: for_tok.span.start,
+                    end: body.span.end,
+                },
+                kind: ExprKind::ForIn {
+                    pattern: Box::new(first),
+                    iterable: Box::new(iterable),
+                    body: Box::new(body),
+                },
+            });
+        }
         let mut second = None;
         let mut third = None;
         if self.eat(bpred!(TokenKind::Semicolon))?.is_some() {
@@ -358,8 +487,7 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
         let start = first.span.start;
         let mut end = first.span.end;
         let mut items = Vec::from([first]);
-        loop {
-            let Some(comma_tok) = self.eat(tpred!(TokenKind::Comma))? else { break };
+        while let Some(comma_tok) = self.eat(tpred!(TokenKind::Comma))? {
             end = comma_tok.span.end;
             if self.has_peek(&end_pred)? {
                 break;
@@ -446,14 +574,15 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
                 TokenKind::GtEq => BinOp::Geq,
                 TokenKind::Lt => BinOp::Lt,
                 TokenKind::LtEq => BinOp::Leq,
+                TokenKind::LeftArrow => BinOp::Recv,
             },
         )
     }
 
     fn assert(&mut self) -> Result<'s, Expr<'s>> {
-        let expr = self.arith()?;
+        let expr = self.bitor()?;
         if let Some(tok) = self.eat(tpred!(TokenKind::ColonColon))? {
-            let ty = self.arith()?;
+            let ty = self.bitor()?;
             Ok(Expr {
                 span: Span {
                     start: expr.span.start,
@@ -469,6 +598,43 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
         }
     }
 
+    fn bitor(&mut self) -> Result<'s, Expr<'s>> {
+        self.bin_op(
+            Self::bitxor,
+            vpred! {
+                TokenKind::Pipe => BinOp::BitOr,
+            },
+        )
+    }
+
+    fn bitxor(&mut self) -> Result<'s, Expr<'s>> {
+        self.bin_op(
+            Self::bitand,
+            vpred! {
+                TokenKind::Caret => BinOp::BitXor,
+            },
+        )
+    }
+
+    fn bitand(&mut self) -> Result<'s, Expr<'s>> {
+        self.bin_op(
+            Self::shift,
+            vpred! {
+                TokenKind::Amp => BinOp::BitAnd,
+            },
+        )
+    }
+
+    fn shift(&mut self) -> Result<'s, Expr<'s>> {
+        self.bin_op(
+            Self::arith,
+            vpred! {
+                TokenKind::LtLt => BinOp::Shl,
+                TokenKind::GtGt => BinOp::Shr,
+            },
+        )
+    }
+
     fn arith(&mut self) -> Result<'s, Expr<'s>> {
         self.bin_op(
             Self::term,
@@ -512,13 +678,69 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
             :t: TokenKind::Set => (t.span, SolveMarker::Set),
         })? {
             let (name_span, name) = self.require(vpred!(:t: TokenKind::Name(n) => (t.span, n)))?;
-
-            Ok(Expr {
+            let solve = Expr {
                 kind: ExprKind::Solve(marker, name),
                 span: Span {
                     start: marker_span.start,
                     end: name_span.end,
                 },
+            };
+            if marker != SolveMarker::Set {
+                return Ok(solve);
+            }
+
+            // `set` can also store into an element of an array
+            let target = Expr {
+                kind: ExprKind::Name(name),
+                span: name_span,
+            };
+            match self.postfix(target)? {
+                Expr {
+                    kind: ExprKind::Name(_),
+                    ..
+                } => Ok(solve),
+                Expr {
+                    kind: ExprKind::Index(array, index),
+                    span,
+                } => Ok(Expr {
+                    kind: ExprKind::SetIndex(array, index),
+                    span: Span {
+                        start: marker_span.start,
+                        end: span.end,
+                    },
+                }),
+                target => Err(ParseError {
+                    kind: ParseErrorKind::Unexpected(None),
+                    span: Some(target.span),
+                }),
+            }
+        } else if let Some((span, kind)) = self.eat(vpred! {
+            :t: TokenKind::Break => (t.span, ExprKind::Break),
+            :t: TokenKind::Continue => (t.span, ExprKind::Continue),
+        })? {
+            Ok(Expr { kind, span })
+        } else if let Some(return_span) = self.eat(vpred!(:t: TokenKind::Return => t.span))? {
+            // a bare `return` is followed by whatever ends the expression it's in
+            if self.has_peek(bpred!(
+                TokenKind::Semicolon
+                    | TokenKind::Comma
+                    | TokenKind::CloseParen
+                    | TokenKind::CloseBrace
+                    | TokenKind::CloseBracket
+            ))? {
+                return Ok(Expr {
+                    kind: ExprKind::Return(None),
+                    span: return_span,
+                });
+            }
+
+            let value = self.logical()?;
+            Ok(Expr {
+                span: Span {
+                    start: return_span.start,
+                    end: value.span.end,
+                },
+                kind: ExprKind::Return(Some(Box::new(value))),
             })
         } else {
             self.suffix()
@@ -533,24 +755,61 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
             })
         };
 
+        while let Some(arg) = self.maybe_atom(true)? {
+            a = Expr {
+                span: Span {
+                    start: a.span.start,
+                    end: arg.span.end,
+                },
+                kind: ExprKind::Apply(Box::new(a), Box::new(arg)),
+            }
+        }
+
+        Ok(a)
+    }
+
+    fn maybe_atom(&mut self, allow_variants: bool) -> Result<'s, Option<Expr<'s>>> {
+        let Some(atom) = self.maybe_primary(allow_variants)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(self.postfix(atom)?))
+    }
+
+    /// Parses the field accesses and indexing after `atom`. Indexing has to directly
+    /// follow what's indexed, since `f [a]` applies `f` to an array instead.
+    fn postfix(&mut self, mut atom: Expr<'s>) -> Result<'s, Expr<'s>> {
         loop {
-            if let Some(arg) = self.maybe_atom(true)? {
-                a = Expr {
+            if self.eat(bpred!(TokenKind::Dot))?.is_some() {
+                let (name_span, name) =
+                    self.require(vpred!(:t: TokenKind::Name(n) => (t.span, n)))?;
+                atom = Expr {
                     span: Span {
-                        start: a.span.start,
-                        end: arg.span.end,
+                        start: atom.span.start,
+                        end: name_span.end,
                     },
-                    kind: ExprKind::Apply(Box::new(a), Box::new(arg)),
-                }
+                    kind: ExprKind::Field(Box::new(atom), name),
+                };
+            } else if self.peek(vpred!(:t: TokenKind::OpenBracket => t.span.start))?
+                == Some(atom.span.end)
+            {
+                self.require(bpred!(TokenKind::OpenBracket))?;
+                let index = self.expr()?;
+                let close = self.require(tpred!(TokenKind::CloseBracket))?;
+                atom = Expr {
+                    span: Span {
+                        start: atom.span.start,
+                        end: close.span.end,
+                    },
+                    kind: ExprKind::Index(Box::new(atom), Box::new(index)),
+                };
             } else {
-                break;
+                return Ok(atom);
             }
         }
-
-        Ok(a)
     }
 
-    fn maybe_atom(&mut self, allow_variants: bool) -> Result<'s, Option<Expr<'s>>> {
+    fn maybe_primary(&mut self, allow_variants: bool) -> Result<'s, Option<Expr<'s>>> {
         if let Some(open) = self.eat(tpred!(TokenKind::OpenParen))? {
             let scope = self.scope(bpred!(TokenKind::CloseParen))?;
             let close = self.require(tpred!(TokenKind::CloseParen))?;
@@ -561,9 +820,29 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
                 },
                 kind: scope.kind,
             }))
+        } else if self.has_peek(bpred!(TokenKind::DotOpenBrace))? {
+            Ok(Some(self.record()?))
+        } else if let Some(open) = self.eat(tpred!(TokenKind::OpenBracket))? {
+            let mut items = Vec::new();
+            while !self.has_peek(bpred!(TokenKind::CloseBracket))? {
+                items.push(self.expr()?);
+                if self.eat(bpred!(TokenKind::Comma))?.is_none() {
+                    break;
+                }
+            }
+            let close = self.require(tpred!(TokenKind::CloseBracket))?;
+
+            Ok(Some(Expr {
+                span: Span {
+                    start: open.span.start,
+                    end: close.span.end,
+                },
+                kind: ExprKind::Array(items.into_boxed_slice()),
+            }))
         } else if allow_variants && self.has_peek(bpred!(TokenKind::Backslash))? {
             Ok(Some(self.variant()?))
         } else if let Some((span, kind)) = self.eat(vpred! {
+            :t: TokenKind::Boolean(b) => (t.span, ExprKind::Literal(Literal::Boolean(b))),
             :t: TokenKind::Float(f) => (t.span, ExprKind::Literal(Literal::Float(f))),
             :t: TokenKind::Integer(i) => (t.span, ExprKind::Literal(Literal::Integer(i))),
             :t: TokenKind::String(s) => (t.span, ExprKind::Literal(Literal::String(s))),
@@ -575,6 +854,37 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
         }
     }
 
+    fn record(&mut self) -> Result<'s, Expr<'s>> {
+        let open = self.require(tpred!(TokenKind::DotOpenBrace))?;
+        let mut fields = Vec::new();
+        while !self.has_peek(bpred!(TokenKind::CloseBrace))? {
+            let (name_span, name) = self.require(vpred!(:t: TokenKind::Name(n) => (t.span, n)))?;
+            self.require(bpred!(TokenKind::Equal))?;
+            let value = self.expr()?;
+
+            fields.push(RecordField {
+                name,
+                span: Span {
+                    start: name_span.start,
+                    end: value.span.end,
+                },
+                value: Box::new(value),
+            });
+            if self.eat(bpred!(TokenKind::Comma))?.is_none() {
+                break;
+            }
+        }
+        let close = self.require(tpred!(TokenKind::CloseBrace))?;
+
+        Ok(Expr {
+            span: Span {
+                start: open.span.start,
+                end: close.span.end,
+            },
+            kind: ExprKind::Record(fields.into_boxed_slice()),
+        })
+    }
+
     fn variant(&mut self) -> Result<'s, Expr<'s>> {
         let mut items = Vec::with_capacity(1);
         while let Some(slash) = self.eat(tpred!(TokenKind::Backslash))? {
@@ -611,7 +921,7 @@ impl<'i, 's, R: CharReader> Parser<'s, R> {
 
             let span = Span {
                 start: a.span.start,
-                end: a.span.end,
+                end: b.span.end,
             };
 
             a = Expr {
@@ -9,6 +9,7 @@ pub fn mergepreds<'s, T>(
     move |t| a(t).or_else(|| b(t))
 }
 
+#[allow(dead_code)]
 pub fn to_bpred<'s, T>(
     pred: impl Fn(&Token<'s>) -> Option<T>,
 ) -> impl Fn(&Token<'s>) -> Option<()> {
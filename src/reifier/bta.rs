@@ -0,0 +1,386 @@
+//! Binding-time analysis for the bodies of specialized (`$`) abstractions.
+//!
+//! An expression is *static* when its value only depends on the specialized parameters,
+//! meaning it can be computed once at specialization time instead of every time the
+//! generated code runs. Everything else is *dynamic*.
+
+use std::fmt::Write;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::*;
+
+/// Annotates every expression inside a `$` abstraction with its [`BindingTime`].
+pub(super) fn analyze(module: &mut Module) {
+    let Module { defs, locals, .. } = module;
+
+    let pure = pure_defs(defs);
+    for def in defs.values_mut() {
+        let ExprKind::Abstract {
+            spec: true,
+            arg,
+            body,
+        } = &mut def.body.kind
+        else {
+            continue;
+        };
+
+        let mut bta = Bta {
+            locals,
+            pure: &pure,
+            statics: FxHashSet::default(),
+        };
+
+        // the specialized parameters are the source of all static values
+        if let Some(arg) = arg {
+            bta.pattern(arg, BindingTime::Static);
+        }
+
+        bta.expr(body);
+    }
+}
+
+struct Bta<'m, 's> {
+    locals: &'m FxHashMap<Symbol, Local<'s>>,
+    pure: &'m FxHashSet<Symbol>,
+    statics: FxHashSet<Symbol>,
+}
+
+/// The defs that are safe to call at specialization time: the ones that only call each
+/// other, and never call builtins, externs or function values, or read or store into arrays.
+fn pure_defs(defs: &FxHashMap<Symbol, Def>) -> FxHashSet<Symbol> {
+    // every def starts out pure, so that recursive defs can stay that way
+    let mut pure: FxHashSet<Symbol> = defs.keys().copied().collect();
+    loop {
+        let impure: Vec<_> = pure
+            .iter()
+            .copied()
+            .filter(|sym| !is_pure(&defs[sym].body, &pure))
+            .collect();
+        if impure.is_empty() {
+            return pure;
+        }
+        for sym in impure {
+            pure.remove(&sym);
+        }
+    }
+}
+
+fn is_pure(expr: &Expr, pure: &FxHashSet<Symbol>) -> bool {
+    let here = match &expr.kind {
+        ExprKind::Apply(f, _) => match &f.kind {
+            ExprKind::Load(sym) => pure.contains(sym),
+            ExprKind::Constructor(_) => true,
+            _ => false,
+        },
+        ExprKind::StructuralEq(pat, _) => !stores(pat),
+        // arrays are mutable, so what a read returns depends on when it runs
+        ExprKind::Index(..) => false,
+        _ => true,
+    };
+
+    here && expr.children().into_iter().all(|child| is_pure(child, pure))
+}
+
+fn stores(pat: &Pattern) -> bool {
+    match &pat.kind {
+        PatternKind::Apply(a, b) => stores(a) || stores(b),
+        PatternKind::Variant(_, data) => data.as_deref().is_some_and(stores),
+        PatternKind::Tuple(items) => items.iter().any(stores),
+        PatternKind::Record(fields) => fields.iter().flatten().any(stores),
+        PatternKind::SetIndex(..) => true,
+        PatternKind::Solve(..) | PatternKind::Symbol(_) => false,
+    }
+}
+
+impl<'m, 's> Bta<'m, 's> {
+    fn expr(&mut self, expr: &mut Expr<'s>) -> BindingTime {
+        use BindingTime::*;
+
+        let bt = match &mut expr.kind {
+            ExprKind::Scope(scope) => {
+                let mut bt = Static;
+                for expr in scope.exprs.iter_mut() {
+                    bt = bt.join(self.expr(expr));
+                }
+                bt
+            }
+            ExprKind::Abstract { body, .. } => {
+                // the parameters of an inner abstraction are never bound at specialization
+                // time, so they are never added to `statics`
+                self.expr(body);
+                Dynamic
+            }
+            ExprKind::For {
+                init,
+                cond,
+                afterthought,
+                body,
+            } => {
+                // loops are always left to the generated code
+                for expr in init.iter_mut().chain([cond, body]).chain(afterthought) {
+                    self.expr(expr);
+                }
+                Dynamic
+            }
+            ExprKind::Case {
+                cond,
+                on_true,
+                on_false,
+            } => {
+                let mut bt = self.expr(cond).join(self.expr(on_true));
+                if let Some(on_false) = on_false {
+                    bt = bt.join(self.expr(on_false));
+                }
+                bt
+            }
+            ExprKind::Tuple(items) | ExprKind::Array(items) => {
+                let mut bt = Static;
+                for item in items.iter_mut() {
+                    bt = bt.join(self.expr(item));
+                }
+                bt
+            }
+            ExprKind::Record(fields) => {
+                let mut bt = Static;
+                for (_, value) in fields.iter_mut() {
+                    bt = bt.join(self.expr(value));
+                }
+                bt
+            }
+            ExprKind::Field(record, _) => self.expr(record),
+            ExprKind::Index(array, index) => {
+                self.expr(array);
+                self.expr(index);
+                // the generated code may run after the array has been stored into, so its
+                // elements are never known at specialization time
+                Dynamic
+            }
+            ExprKind::StructuralEq(pat, value) => {
+                let bt = self.expr(value);
+                if Self::assigns(pat) {
+                    // assigning to a `var` is a side effect the generated code must perform
+                    self.pattern(pat, Dynamic);
+                    Dynamic
+                } else {
+                    self.pattern(pat, bt);
+                    bt
+                }
+            }
+            ExprKind::Binary(_, a, b) => self.expr(a).join(self.expr(b)),
+            ExprKind::Unary(_, a) => self.expr(a),
+            ExprKind::Apply(f, arg) => {
+                let bt = self.expr(f).join(self.expr(arg));
+                // builtins and externs perform I/O and allocation, and other functions may
+                // call them, so only calls to pure defs are ever run early
+                if self.is_pure(f) {
+                    bt
+                } else {
+                    Dynamic
+                }
+            }
+            ExprKind::Variant(_, data) => {
+                if let Some(data) = data {
+                    self.expr(data)
+                } else {
+                    Static
+                }
+            }
+            ExprKind::Load(sym) => {
+                if self.statics.contains(sym) || !self.locals.contains_key(sym) {
+                    // defs and builtins are globals, so their values are always known
+                    Static
+                } else {
+                    Dynamic
+                }
+            }
+            ExprKind::Constructor(_) | ExprKind::Literal(_) => Static,
+            ExprKind::Return(value) => {
+                self.expr(value);
+                // control flow is left to the generated code, like loops
+                Dynamic
+            }
+            ExprKind::Break | ExprKind::Continue => Dynamic,
+        };
+
+        expr.bt = bt;
+        bt
+    }
+
+    fn pattern(&mut self, pat: &Pattern<'s>, bt: BindingTime) {
+        match &pat.kind {
+            PatternKind::Apply(a, b) => {
+                self.pattern(a, bt);
+                self.pattern(b, bt);
+            }
+            PatternKind::Variant(_, data) => {
+                if let Some(data) = data {
+                    self.pattern(data, bt);
+                }
+            }
+            PatternKind::Tuple(items) => {
+                for item in items.iter() {
+                    self.pattern(item, bt);
+                }
+            }
+            PatternKind::Record(fields) => {
+                for field in fields.iter().flatten() {
+                    self.pattern(field, bt);
+                }
+            }
+            &PatternKind::Solve(SolveMarker::Val, sym) => {
+                if bt == BindingTime::Static {
+                    self.statics.insert(sym);
+                }
+            }
+            // `var`s can be reassigned by the generated code, so they are always dynamic
+            PatternKind::Solve(SolveMarker::Var | SolveMarker::Set, _) => (),
+            // as are stores into arrays, along with where they store to
+            PatternKind::SetIndex(..) => (),
+            PatternKind::Symbol(_) => (),
+        }
+    }
+
+    fn assigns(pat: &Pattern<'s>) -> bool {
+        match &pat.kind {
+            PatternKind::Apply(a, b) => Self::assigns(a) || Self::assigns(b),
+            PatternKind::Variant(_, data) => data.as_deref().is_some_and(Self::assigns),
+            PatternKind::Tuple(items) => items.iter().any(Self::assigns),
+            PatternKind::Record(fields) => fields.iter().flatten().any(Self::assigns),
+            PatternKind::Solve(marker, _) => *marker == SolveMarker::Set,
+            PatternKind::SetIndex(..) => true,
+            PatternKind::Symbol(_) => false,
+        }
+    }
+
+    fn is_pure(&self, f: &Expr<'s>) -> bool {
+        match &f.kind {
+            ExprKind::Load(sym) => self.pure.contains(sym),
+            ExprKind::Constructor(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl BindingTime {
+    fn join(self, other: BindingTime) -> BindingTime {
+        match (self, other) {
+            (BindingTime::Static, BindingTime::Static) => BindingTime::Static,
+            _ => BindingTime::Dynamic,
+        }
+    }
+}
+
+/// Renders the binding times of every `$` abstraction in `module` as an indented tree,
+/// with each expression prefixed by `S` (static) or `D` (dynamic).
+pub fn annotate_binding_times(module: &Module) -> String {
+    let mut specs: Vec<_> = module
+        .defs
+        .values()
+        .filter(|def| matches!(def.body.kind, ExprKind::Abstract { spec: true, .. }))
+        .collect();
+    specs.sort_by_key(|def| def.decl_span.start);
+
+    let mut out = String::new();
+    for def in specs {
+        writeln!(out, "def {}", def.name.0).unwrap();
+        Annotator { module, out: &mut out }.expr(&def.body, 1);
+    }
+
+    out
+}
+
+struct Annotator<'a, 's> {
+    module: &'a Module<'s>,
+    out: &'a mut String,
+}
+
+impl<'a, 's> Annotator<'a, 's> {
+    fn expr(&mut self, expr: &Expr<'s>, depth: usize) {
+        let marker = match expr.bt {
+            BindingTime::Static => 'S',
+            BindingTime::Dynamic => 'D',
+        };
+        let label = match &expr.kind {
+            ExprKind::Scope(_) => "scope".to_string(),
+            ExprKind::Abstract { spec, arg, .. } => {
+                let arg = arg.as_ref().map(|a| self.pattern(a)).unwrap_or_default();
+                format!("abstract ({arg}) {}", if *spec { "$=>" } else { "=>" })
+            }
+            ExprKind::For { .. } => "for".to_string(),
+            ExprKind::Case { .. } => "case".to_string(),
+            ExprKind::Tuple(_) => "tuple".to_string(),
+            ExprKind::Record(_) => "record".to_string(),
+            ExprKind::Field(_, slot) => format!(".{slot}"),
+            ExprKind::Array(_) => "array".to_string(),
+            ExprKind::Index(..) => "index".to_string(),
+            ExprKind::StructuralEq(pat, _) => format!("{} =", self.pattern(pat)),
+            ExprKind::Binary(op, ..) => format!("{op:?}"),
+            ExprKind::Unary(op, _) => format!("{op:?}"),
+            ExprKind::Apply(..) => "apply".to_string(),
+            ExprKind::Variant(name, _) => format!("\\{}", name.0),
+            ExprKind::Constructor(sym) => format!("constructor {}", self.name(*sym)),
+            ExprKind::Load(sym) => self.name(*sym),
+            ExprKind::Literal(lit) => match lit {
+                Literal::Float(f) => f.to_string(),
+                Literal::Integer(i) => i.to_string(),
+                Literal::String(s) => format!("{:?}", s.0),
+                Literal::Boolean(b) => b.to_string(),
+            },
+            ExprKind::Break => "break".to_string(),
+            ExprKind::Continue => "continue".to_string(),
+            ExprKind::Return(_) => "return".to_string(),
+        };
+
+        writeln!(self.out, "{:indent$}{marker} {label}", "", indent = depth * 2).unwrap();
+
+        for child in expr.children() {
+            self.expr(child, depth + 1);
+        }
+    }
+
+    fn pattern(&self, pat: &Pattern<'s>) -> String {
+        match &pat.kind {
+            PatternKind::Apply(a, b) => format!("{}{}", self.pattern(a), self.pattern(b)),
+            PatternKind::Variant(name, data) => match data {
+                Some(data) => format!("\\{} {}", name.0, self.pattern(data)),
+                None => format!("\\{}", name.0),
+            },
+            PatternKind::Tuple(items) => {
+                let items: Vec<_> = items.iter().map(|it| self.pattern(it)).collect();
+                format!("({})", items.join(", "))
+            }
+            PatternKind::Record(fields) => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|field| field.as_ref().map_or("_".to_string(), |f| self.pattern(f)))
+                    .collect();
+                format!(".{{{}}}", fields.join(", "))
+            }
+            PatternKind::SetIndex(..) => "set []".to_string(),
+            PatternKind::Solve(marker, sym) => {
+                let marker = match marker {
+                    SolveMarker::Val => "val",
+                    SolveMarker::Var => "var",
+                    SolveMarker::Set => "set",
+                };
+                format!("{marker} {}", self.name(*sym))
+            }
+            PatternKind::Symbol(sym) => self.name(*sym),
+        }
+    }
+
+    fn name(&self, sym: Symbol) -> String {
+        if let Some(local) = self.module.locals.get(&sym) {
+            local.name.0.to_string()
+        } else if let Some(def) = self.module.defs.get(&sym) {
+            def.name.0.to_string()
+        } else if let Some((builtin, _)) = self.module.builtins.get(&sym) {
+            format!("{builtin:?}").to_lowercase()
+        } else if let Some(ext) = self.module.externs.get(&sym) {
+            ext.name.0.to_string()
+        } else {
+            format!("<{}>", sym.index())
+        }
+    }
+}
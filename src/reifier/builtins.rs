@@ -0,0 +1,287 @@
+//! The functions built into the language, described once for every stage that deals with them.
+//!
+//! The reifier defines every builtin with a name in [`BUILTINS`], the lowerer calls the
+//! unnamed ones itself, and each backend provides an implementation of all of them.
+
+use super::{IntType, PrimitiveType, Type};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Builtin {
+    Alloc,
+    /// Gives the function that a specialization generates, from an object holding the
+    /// generated code followed by the specialized arguments: an identical one made earlier,
+    /// if there is one, or else the object itself.
+    Spec,
+    Print,
+    Println,
+    /// Reads the rest of standard input.
+    Input,
+    Itoa,
+    Len,
+    /// Aborts the program because an array was indexed outside its bounds.
+    OutOfBounds,
+    /// Aborts the program because checked arithmetic overflowed, at the span between two
+    /// offsets into the source.
+    Overflow,
+    /// Aborts the program because checked arithmetic divided by zero, at the span between
+    /// two offsets into the source.
+    ZeroDivisor,
+    /// The length of a string in bytes.
+    StrLen,
+    /// The byte at an offset into a string.
+    ByteAt,
+    /// The code point of the character that starts at a byte offset into a string.
+    CharAt,
+    /// The string of the bytes between two offsets into a string.
+    Slice,
+    Concat,
+    /// Compares two strings byte by byte, giving -1, 0 or 1.
+    Compare,
+    Atoi,
+    /// The string holding just the character with a code point.
+    Chr,
+    /// Concatenates an array of strings, with a separator between each of them.
+    Join,
+    /// Stops the program with an exit status.
+    Exit,
+    /// Stops the program with an error.
+    Abort,
+    /// Stops the program with an error if a condition doesn't hold.
+    Assert,
+    Itof,
+    /// Converts a float to an integer, rounding towards zero. Floats beyond the range of
+    /// `Int` give its smallest or largest value, and NaN gives 0.
+    Ftoi,
+    Ftoa,
+    /// Parses a float, like `1.5`, `-2e10`, `inf` or `NaN`.
+    Atof,
+    /// Formats a float with a number of digits after the point.
+    FtoaFixed,
+    Sqrt,
+    Floor,
+    Ceil,
+    /// Rounds a float to the nearest integer, away from zero if it's halfway between two.
+    Round,
+    Pow,
+    Exp,
+    /// The natural logarithm.
+    Log,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    /// The angle of the point at `(x, y)`, given `y` and `x`, like `atan(y / x)` but in the
+    /// right quadrant.
+    Atan2,
+    IsNan,
+    /// Reads a line of standard input, without its line ending.
+    ReadLine,
+    /// The number of nanoseconds since some fixed point in time, which never goes backwards.
+    Clock,
+    /// Converts any integer to an `I8`, wrapping it around if it doesn't fit, like the other
+    /// integer conversions.
+    ToI8,
+    ToI16,
+    ToI32,
+    ToInt,
+    ToU8,
+    ToU16,
+    ToU32,
+    ToU64,
+}
+
+/// The builtin integer types other than `Int`, which `I64` is another name for.
+pub const SIZED_INTS: [(&str, bool, u32); 7] = [
+    ("I8", true, 8),
+    ("I16", true, 16),
+    ("I32", true, 32),
+    ("U8", false, 8),
+    ("U16", false, 16),
+    ("U32", false, 32),
+    ("U64", false, 64),
+];
+
+/// The type of one of a builtin's parameters or its result.
+#[derive(Debug, Clone, Copy)]
+pub enum Sig {
+    Int,
+    /// An integer type other than `Int`.
+    Sized(IntType),
+    /// Any integer type, which is what a generic builtin is instantiated with, like
+    /// [`Sig::Item`]. The reifier checks that it's instantiated with an integer.
+    Integer,
+    Float,
+    Bool,
+    String,
+    Unit,
+    /// The type a generic builtin is instantiated with; builtins have at most one.
+    Item,
+    Array(&'static Sig),
+}
+
+/// Everything about a builtin that doesn't depend on how it's implemented.
+#[derive(Debug)]
+pub struct BuiltinInfo {
+    pub builtin: Builtin,
+    /// What codef code calls the builtin by, if it can call it directly.
+    pub name: Option<&'static str>,
+    /// The types of the builtin's arguments, which are passed as a tuple if there's more
+    /// than one, or as a unit if there are none.
+    pub params: &'static [Sig],
+    pub ret: Sig,
+    /// The symbol that native code calls to run the builtin.
+    pub symbol: &'static str,
+}
+
+/// Every builtin, in the same order as the variants of [`Builtin`].
+///
+/// The builtins only the lowerer calls take and return raw words, which they describe as
+/// `Int`s.
+pub const BUILTINS: &[BuiltinInfo] = &[
+    builtin(Builtin::Alloc, None, &[Sig::Int, Sig::Int], Sig::Int, "codef_alloc"),
+    builtin(Builtin::Spec, None, &[Sig::Int], Sig::Int, "codef_spec"),
+    builtin(Builtin::Print, Some("print"), &[Sig::String], Sig::Unit, "codef_print"),
+    builtin(Builtin::Println, Some("println"), &[Sig::String], Sig::Unit, "codef_println"),
+    builtin(Builtin::Input, Some("input"), &[], Sig::String, "codef_input"),
+    builtin(Builtin::Itoa, Some("itoa"), &[Sig::Int], Sig::String, "codef_itoa"),
+    builtin(Builtin::Len, Some("len"), &[Sig::Array(&Sig::Item)], Sig::Int, "codef_len"),
+    builtin(Builtin::OutOfBounds, None, &[Sig::Int, Sig::Int], Sig::Unit, "codef_out_of_bounds"),
+    builtin(Builtin::Overflow, None, &[Sig::Int, Sig::Int], Sig::Unit, "codef_overflow"),
+    builtin(Builtin::ZeroDivisor, None, &[Sig::Int, Sig::Int], Sig::Unit, "codef_zero_divisor"),
+    builtin(Builtin::StrLen, Some("strlen"), &[Sig::String], Sig::Int, "codef_strlen"),
+    builtin(Builtin::ByteAt, Some("byte_at"), &[Sig::String, Sig::Int], Sig::Int, "codef_byte_at"),
+    builtin(Builtin::CharAt, Some("char_at"), &[Sig::String, Sig::Int], Sig::Int, "codef_char_at"),
+    builtin(
+        Builtin::Slice,
+        Some("slice"),
+        &[Sig::String, Sig::Int, Sig::Int],
+        Sig::String,
+        "codef_slice",
+    ),
+    builtin(
+        Builtin::Concat,
+        Some("concat"),
+        &[Sig::String, Sig::String],
+        Sig::String,
+        "codef_concat",
+    ),
+    builtin(Builtin::Compare, None, &[Sig::String, Sig::String], Sig::Int, "codef_compare"),
+    builtin(Builtin::Atoi, Some("atoi"), &[Sig::String], Sig::Int, "codef_atoi"),
+    builtin(Builtin::Chr, Some("chr"), &[Sig::Int], Sig::String, "codef_chr"),
+    builtin(
+        Builtin::Join,
+        Some("join"),
+        &[Sig::Array(&Sig::String), Sig::String],
+        Sig::String,
+        "codef_join",
+    ),
+    builtin(Builtin::Exit, Some("exit"), &[Sig::Int], Sig::Unit, "codef_exit"),
+    builtin(Builtin::Abort, Some("abort"), &[], Sig::Unit, "codef_abort"),
+    builtin(Builtin::Assert, Some("assert"), &[Sig::Bool], Sig::Unit, "codef_assert"),
+    builtin(Builtin::Itof, Some("itof"), &[Sig::Int], Sig::Float, "codef_itof"),
+    builtin(Builtin::Ftoi, Some("ftoi"), &[Sig::Float], Sig::Int, "codef_ftoi"),
+    builtin(Builtin::Ftoa, Some("ftoa"), &[Sig::Float], Sig::String, "codef_ftoa"),
+    builtin(Builtin::Atof, Some("atof"), &[Sig::String], Sig::Float, "codef_atof"),
+    builtin(
+        Builtin::FtoaFixed,
+        Some("ftoa_fixed"),
+        &[Sig::Float, Sig::Int],
+        Sig::String,
+        "codef_ftoa_fixed",
+    ),
+    builtin(Builtin::Sqrt, Some("sqrt"), &[Sig::Float], Sig::Float, "codef_sqrt"),
+    builtin(Builtin::Floor, Some("floor"), &[Sig::Float], Sig::Float, "codef_floor"),
+    builtin(Builtin::Ceil, Some("ceil"), &[Sig::Float], Sig::Float, "codef_ceil"),
+    builtin(Builtin::Round, Some("round"), &[Sig::Float], Sig::Float, "codef_round"),
+    builtin(Builtin::Pow, Some("pow"), &[Sig::Float, Sig::Float], Sig::Float, "codef_pow"),
+    builtin(Builtin::Exp, Some("exp"), &[Sig::Float], Sig::Float, "codef_exp"),
+    builtin(Builtin::Log, Some("log"), &[Sig::Float], Sig::Float, "codef_log"),
+    builtin(Builtin::Sin, Some("sin"), &[Sig::Float], Sig::Float, "codef_sin"),
+    builtin(Builtin::Cos, Some("cos"), &[Sig::Float], Sig::Float, "codef_cos"),
+    builtin(Builtin::Tan, Some("tan"), &[Sig::Float], Sig::Float, "codef_tan"),
+    builtin(Builtin::Asin, Some("asin"), &[Sig::Float], Sig::Float, "codef_asin"),
+    builtin(Builtin::Acos, Some("acos"), &[Sig::Float], Sig::Float, "codef_acos"),
+    builtin(Builtin::Atan, Some("atan"), &[Sig::Float], Sig::Float, "codef_atan"),
+    builtin(Builtin::Atan2, Some("atan2"), &[Sig::Float, Sig::Float], Sig::Float, "codef_atan2"),
+    builtin(Builtin::IsNan, Some("is_nan"), &[Sig::Float], Sig::Bool, "codef_is_nan"),
+    builtin(Builtin::ReadLine, Some("read_line"), &[], Sig::String, "codef_read_line"),
+    builtin(Builtin::Clock, Some("clock"), &[], Sig::Int, "codef_clock"),
+    builtin(Builtin::ToI8, Some("to_i8"), &[Sig::Integer], Sig::Sized(I8), "codef_to_i8"),
+    builtin(Builtin::ToI16, Some("to_i16"), &[Sig::Integer], Sig::Sized(I16), "codef_to_i16"),
+    builtin(Builtin::ToI32, Some("to_i32"), &[Sig::Integer], Sig::Sized(I32), "codef_to_i32"),
+    builtin(Builtin::ToInt, Some("to_int"), &[Sig::Integer], Sig::Int, "codef_to_int"),
+    builtin(Builtin::ToU8, Some("to_u8"), &[Sig::Integer], Sig::Sized(U8), "codef_to_u8"),
+    builtin(Builtin::ToU16, Some("to_u16"), &[Sig::Integer], Sig::Sized(U16), "codef_to_u16"),
+    builtin(Builtin::ToU32, Some("to_u32"), &[Sig::Integer], Sig::Sized(U32), "codef_to_u32"),
+    builtin(Builtin::ToU64, Some("to_u64"), &[Sig::Integer], Sig::Sized(U64), "codef_to_u64"),
+];
+
+const I8: IntType = IntType { signed: true, bits: 8 };
+const I16: IntType = IntType { signed: true, bits: 16 };
+const I32: IntType = IntType { signed: true, bits: 32 };
+const U8: IntType = IntType { signed: false, bits: 8 };
+const U16: IntType = IntType { signed: false, bits: 16 };
+const U32: IntType = IntType { signed: false, bits: 32 };
+const U64: IntType = IntType { signed: false, bits: 64 };
+
+const fn builtin(
+    builtin: Builtin,
+    name: Option<&'static str>,
+    params: &'static [Sig],
+    ret: Sig,
+    symbol: &'static str,
+) -> BuiltinInfo {
+    BuiltinInfo {
+        builtin,
+        name,
+        params,
+        ret,
+        symbol,
+    }
+}
+
+impl Builtin {
+    /// The integer type that the builtin converts integers to, if it's a conversion.
+    pub fn int_conversion(self) -> Option<IntType> {
+        match self.info() {
+            BuiltinInfo { params: [Sig::Integer], ret: Sig::Int, .. } => Some(IntType::INT),
+            BuiltinInfo { params: [Sig::Integer], ret: Sig::Sized(int), .. } => Some(*int),
+            _ => None,
+        }
+    }
+
+    pub fn info(self) -> &'static BuiltinInfo {
+        let info = &BUILTINS[self as usize];
+        debug_assert_eq!(info.builtin, self, "BUILTINS is out of order");
+        info
+    }
+}
+
+impl BuiltinInfo {
+    /// The type of the builtin as a function, where `item` is what [`Sig::Item`] stands for.
+    pub fn ty<'s>(&self, item: &Type<'s>) -> Type<'s> {
+        let param = match self.params {
+            [param] => param.ty(item),
+            params => Type::Tuple(params.iter().map(|param| param.ty(item)).collect()),
+        };
+
+        Type::Function(Some(Box::new(param)), Box::new(self.ret.ty(item)))
+    }
+}
+
+impl Sig {
+    pub fn ty<'s>(self, item: &Type<'s>) -> Type<'s> {
+        match self {
+            Sig::Int => Type::Primitive(PrimitiveType::Integer),
+            Sig::Sized(int) => Type::Primitive(PrimitiveType::Sized(int)),
+            Sig::Float => Type::Primitive(PrimitiveType::Float),
+            Sig::Bool => Type::Primitive(PrimitiveType::Boolean),
+            Sig::String => Type::Primitive(PrimitiveType::String),
+            Sig::Unit => Type::Tuple(Box::new([])),
+            Sig::Integer | Sig::Item => item.clone(),
+            Sig::Array(item_sig) => Type::Array(Box::new(item_sig.ty(item))),
+        }
+    }
+}
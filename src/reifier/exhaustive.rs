@@ -0,0 +1,233 @@
+//! Exhaustiveness and redundancy checking for `case` chains that match one value.
+//!
+//! This is the usefulness algorithm from Maranget's "Warnings for pattern matching": a
+//! pattern is useful after some others if there's a value that it matches and none of the
+//! others do. An arm whose pattern isn't useful can never run, and a chain is exhaustive if
+//! a pattern that matches anything wouldn't be useful after all of its arms.
+
+use std::slice;
+
+use crate::strings::Intern;
+
+use super::{Module, Pattern, PatternKind, Type};
+
+/// A pattern, reduced to the parts that decide which values it matches.
+#[derive(Debug, Clone)]
+enum Pat<'s> {
+    Wild,
+    Variant(Intern<'s>, Option<Box<Pat<'s>>>),
+    Tuple(Box<[Pat<'s>]>),
+}
+
+impl<'s> Pat<'s> {
+    fn of(pattern: &Pattern<'s>) -> Pat<'s> {
+        match &pattern.kind {
+            // instances are represented by what they wrap
+            PatternKind::Apply(_, inner) => Pat::of(inner),
+            PatternKind::Variant(name, inner) => {
+                Pat::Variant(*name, inner.as_deref().map(|inner| Box::new(Pat::of(inner))))
+            }
+            PatternKind::Tuple(items) => Pat::Tuple(items.iter().map(Pat::of).collect()),
+            // records are tuples of their slots, whether or not they're matched
+            PatternKind::Record(fields) => Pat::Tuple(
+                fields
+                    .iter()
+                    .map(|field| field.as_ref().map_or(Pat::Wild, Pat::of))
+                    .collect(),
+            ),
+            PatternKind::Solve(..) | PatternKind::Symbol(_) | PatternKind::SetIndex(..) => {
+                Pat::Wild
+            }
+        }
+    }
+}
+
+type Row<'s> = Vec<Pat<'s>>;
+
+/// What the arms of a chain cover of the values they're matched against.
+#[derive(Debug)]
+pub(super) struct Coverage<'s> {
+    /// The indices of the arms that no value can reach.
+    pub unreachable: Vec<usize>,
+    /// If some values don't match any arm, the variants of the matched type that they
+    /// can have.
+    pub missing: Option<Vec<Intern<'s>>>,
+}
+
+/// Checks the patterns of `arms`, in order, against a value of the already resolved `ty`.
+pub(super) fn check<'s>(module: &Module<'s>, ty: &Type<'s>, arms: &[&Pattern<'s>]) -> Coverage<'s> {
+    let checker = Checker { module };
+    let tys = slice::from_ref(ty);
+
+    let mut rows = Vec::with_capacity(arms.len());
+    let mut unreachable = Vec::new();
+    for (i, arm) in arms.iter().enumerate() {
+        let row = vec![Pat::of(arm)];
+        if !checker.useful(&rows, &row, tys) {
+            unreachable.push(i);
+        }
+        rows.push(row);
+    }
+
+    let missing = checker.useful(&rows, &[Pat::Wild], tys).then(|| match checker.unfold(ty) {
+        Type::Variant(items) => items
+            .iter()
+            .filter(|item| {
+                let inner = item.inner.as_ref().map(|_| Box::new(Pat::Wild));
+                checker.useful(&rows, &[Pat::Variant(item.name, inner)], tys)
+            })
+            .map(|item| item.name)
+            .collect(),
+        _ => Vec::new(),
+    });
+
+    Coverage {
+        unreachable,
+        missing,
+    }
+}
+
+struct Checker<'m, 's> {
+    module: &'m Module<'s>,
+}
+
+impl<'m, 's> Checker<'m, 's> {
+    fn unfold(&self, ty: &Type<'s>) -> Type<'s> {
+        match self.module.unfold(ty) {
+            Some(inner) => self.unfold(&inner),
+            None => ty.clone(),
+        }
+    }
+
+    /// Whether some values of `tys` match `row`, but none of `rows`.
+    fn useful(&self, rows: &[Row<'s>], row: &[Pat<'s>], tys: &[Type<'s>]) -> bool {
+        let Some((head, rest)) = row.split_first() else {
+            return rows.is_empty();
+        };
+        if rows.is_empty() {
+            return true;
+        }
+
+        let ty = self.unfold(&tys[0]);
+        match head {
+            Pat::Variant(name, inner) => {
+                self.useful_variant(rows, *name, inner.as_deref(), rest, &ty, &tys[1..])
+            }
+            Pat::Tuple(items) => self.useful_tuple(rows, items, rest, &ty, &tys[1..]),
+            // a wildcard is useful if it's useful as any of the values it stands for, unless
+            // the other rows don't tell those values apart either
+            Pat::Wild if rows.iter().any(|row| !matches!(row[0], Pat::Wild)) => match &ty {
+                Type::Variant(items) => items.iter().any(|item| {
+                    let inner = item.inner.as_ref().map(|_| Pat::Wild);
+                    self.useful_variant(rows, item.name, inner.as_ref(), rest, &ty, &tys[1..])
+                }),
+                Type::Tuple(items) => {
+                    let wilds = vec![Pat::Wild; items.len()];
+                    self.useful_tuple(rows, &wilds, rest, &ty, &tys[1..])
+                }
+                Type::Record(fields) => {
+                    let wilds = vec![Pat::Wild; fields.len()];
+                    self.useful_tuple(rows, &wilds, rest, &ty, &tys[1..])
+                }
+                // values of other types can't be told apart by patterns
+                _ => self.useful_default(rows, rest, &tys[1..]),
+            },
+            Pat::Wild => self.useful_default(rows, rest, &tys[1..]),
+        }
+    }
+
+    /// [`Self::useful`], for a row whose first pattern is a wildcard that doesn't need to
+    /// be split up, so only the rows that start with wildcards matter.
+    fn useful_default(&self, rows: &[Row<'s>], rest: &[Pat<'s>], rest_tys: &[Type<'s>]) -> bool {
+        let rows: Vec<_> = rows
+            .iter()
+            .filter(|row| matches!(row[0], Pat::Wild))
+            .map(|row| row[1..].to_vec())
+            .collect();
+
+        self.useful(&rows, rest, rest_tys)
+    }
+
+    /// [`Self::useful`], for a row whose first pattern is the variant `name`.
+    fn useful_variant(
+        &self,
+        rows: &[Row<'s>],
+        name: Intern<'s>,
+        inner: Option<&Pat<'s>>,
+        rest: &[Pat<'s>],
+        ty: &Type<'s>,
+        rest_tys: &[Type<'s>],
+    ) -> bool {
+        let inner_ty = match ty {
+            Type::Variant(items) => items
+                .iter()
+                .find(|item| item.name == name)
+                .and_then(|item| item.inner.clone()),
+            _ => None,
+        };
+
+        // the rows that match this variant, with its payload in front
+        let specialized: Vec<Row<'s>> = rows
+            .iter()
+            .filter_map(|row| {
+                let payload = match &row[0] {
+                    Pat::Variant(other, payload) if *other == name => {
+                        payload.as_deref().cloned().unwrap_or(Pat::Wild)
+                    }
+                    Pat::Wild => Pat::Wild,
+                    Pat::Variant(..) | Pat::Tuple(_) => return None,
+                };
+
+                let payload = inner.is_some().then_some(payload);
+                Some(payload.into_iter().chain(row[1..].iter().cloned()).collect())
+            })
+            .collect();
+
+        let row: Row<'s> = inner.cloned().into_iter().chain(rest.iter().cloned()).collect();
+        let tys: Vec<_> = inner
+            .map(|_| inner_ty.unwrap_or_else(|| ty.clone()))
+            .into_iter()
+            .chain(rest_tys.iter().cloned())
+            .collect();
+
+        self.useful(&specialized, &row, &tys)
+    }
+
+    /// [`Self::useful`], for a row whose first pattern is a tuple of `items`, or a record
+    /// with them in its slots.
+    fn useful_tuple(
+        &self,
+        rows: &[Row<'s>],
+        items: &[Pat<'s>],
+        rest: &[Pat<'s>],
+        ty: &Type<'s>,
+        rest_tys: &[Type<'s>],
+    ) -> bool {
+        let item_tys = match ty {
+            Type::Tuple(tys) if tys.len() == items.len() => tys.to_vec(),
+            Type::Record(fields) if fields.len() == items.len() => {
+                fields.iter().map(|field| field.ty.clone()).collect()
+            }
+            _ => vec![ty.clone(); items.len()],
+        };
+
+        // the rows with the tuple's items spread out in front
+        let specialized: Vec<Row<'s>> = rows
+            .iter()
+            .filter_map(|row| {
+                let front = match &row[0] {
+                    Pat::Tuple(items) => items.to_vec(),
+                    Pat::Wild => vec![Pat::Wild; items.len()],
+                    Pat::Variant(..) => return None,
+                };
+
+                Some(front.into_iter().chain(row[1..].iter().cloned()).collect())
+            })
+            .collect();
+
+        let row: Row<'s> = items.iter().chain(rest).cloned().collect();
+        let tys: Vec<_> = item_tys.into_iter().chain(rest_tys.iter().cloned()).collect();
+
+        self.useful(&specialized, &row, &tys)
+    }
+}
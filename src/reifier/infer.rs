@@ -0,0 +1,316 @@
+//! Type inference with unification variables.
+//!
+//! Types that aren't known yet are represented by [`Type::Var`]s, which get bound as
+//! constraints are discovered. Constraints are subtyping relations, so a variable is bound
+//! to the first type it's related to, which is either a lower or an upper bound. A lower
+//! bound widens whenever a type that doesn't fit in it flows into the variable, as long as
+//! the widened type still fits in every type the variable has flowed into, while an upper
+//! bound is an approximation that never changes.
+//!
+//! A `type`'s values are written the same way as values of what it was defined as, so an
+//! instance of a `type` and a tuple, record or variant type are related through the `type`'s
+//! definition.
+//!
+//! Variables are generalized with levels: every group of mutually recursive `def`s is
+//! inferred one level deeper than its surroundings, and any variable still unbound at that
+//! deeper level once the group is done can't be constrained by anything else, so it's
+//! quantified over in the `def`'s type.
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::{PrimitiveType, Symbol, Type, TypeVar};
+
+#[derive(Debug, Default)]
+pub(super) struct Inference<'s> {
+    vars: Vec<Var<'s>>,
+    level: u32,
+    /// Variables that have been quantified over by some `def`.
+    generalized: FxHashSet<TypeVar>,
+    /// Every change made to a variable since the inference started, so it can be undone.
+    trail: Vec<Change<'s>>,
+    /// What each `type` was defined as, with the variables its parameters stand for.
+    typedefs: FxHashMap<Symbol, (Box<[TypeVar]>, Type<'s>)>,
+}
+
+#[derive(Debug)]
+struct Var<'s> {
+    bound: Bound<'s>,
+    level: u32,
+    /// The types that the variable has flowed into while it was a lower bound, which it must
+    /// stay a subtype of as it widens.
+    uppers: Vec<Type<'s>>,
+}
+
+#[derive(Debug, Clone)]
+enum Bound<'s> {
+    Unbound,
+    /// The variable is the join of the types that have flowed into it so far.
+    Lower(Type<'s>),
+    /// The variable is exactly this type.
+    Exact(Type<'s>),
+}
+
+#[derive(Debug)]
+enum Change<'s> {
+    /// The variable was bound to something else, which it was bound to before.
+    Bound(TypeVar, Bound<'s>),
+    /// The variable flowed into another type.
+    Upper(TypeVar),
+}
+
+impl<'s> Bound<'s> {
+    fn ty(&self) -> Option<&Type<'s>> {
+        match self {
+            Bound::Unbound => None,
+            Bound::Lower(ty) | Bound::Exact(ty) => Some(ty),
+        }
+    }
+}
+
+/// The bindings made since some point, which [`Inference::rollback`] can undo.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Snapshot(usize);
+
+/// The types that couldn't be unified.
+pub(super) struct Mismatch;
+
+type Result<T> = std::result::Result<T, Mismatch>;
+
+impl<'s> Inference<'s> {
+    pub fn fresh(&mut self) -> Type<'s> {
+        let var = TypeVar(self.vars.len() as u32);
+        self.vars.push(Var {
+            bound: Bound::Unbound,
+            level: self.level,
+            uppers: Vec::new(),
+        });
+        Type::Var(var)
+    }
+
+    /// Starts inferring a group of `def`s whose types will be generalized.
+    pub fn enter(&mut self) {
+        self.level += 1;
+    }
+
+    pub fn exit(&mut self) {
+        self.level -= 1;
+    }
+
+    /// Records that instances of the `type` `sym` are `inner`, with `params` substituted.
+    pub fn define(&mut self, sym: Symbol, params: Box<[TypeVar]>, inner: Type<'s>) {
+        self.typedefs.insert(sym, (params, inner));
+    }
+
+    /// Whether `ty` is a variable that hasn't been bound yet.
+    pub fn is_unbound(&self, ty: &Type<'s>) -> bool {
+        matches!(self.shallow(ty), Type::Var(_))
+    }
+
+    /// Whether every variable left in the already [zonked](Self::zonk) `ty` has been
+    /// quantified over.
+    pub fn is_resolved(&self, ty: &Type<'s>) -> bool {
+        let mut vars = Vec::new();
+        ty.vars(&mut vars);
+        vars.iter().all(|var| self.generalized.contains(var))
+    }
+
+    /// Follows `ty` through the variables it's bound to, until it reaches a type that
+    /// isn't a bound variable.
+    pub fn shallow(&self, ty: &Type<'s>) -> Type<'s> {
+        let mut ty = ty;
+        while let &Type::Var(var) = ty {
+            match self.vars[var.0 as usize].bound.ty() {
+                Some(bound) => ty = bound,
+                None => break,
+            }
+        }
+
+        ty.clone()
+    }
+
+    /// Replaces every bound variable in `ty` by what it's bound to.
+    pub fn zonk(&self, ty: &Type<'s>) -> Type<'s> {
+        self.shallow(ty).map(&mut |ty| self.zonk(ty))
+    }
+
+    /// Relates `sub` and `sup` so that `sub` is a subtype of `sup`, binding any variables
+    /// needed to make that true.
+    pub fn constrain(&mut self, sub: &Type<'s>, sup: &Type<'s>) -> Result<()> {
+        if let Some(var) = self.lower_bounded(sup) {
+            return self.widen(var, sub);
+        }
+        if let Some(var) = self.lower_bounded(sub) {
+            self.vars[var.0 as usize].uppers.push(sup.clone());
+            self.trail.push(Change::Upper(var));
+        }
+
+        match (self.shallow(sub), self.shallow(sup)) {
+            // what never produces a value fits wherever any value is expected
+            (Type::Primitive(PrimitiveType::Never), _) => Ok(()),
+            (Type::Var(a), Type::Var(b)) if a == b => Ok(()),
+            (ty, Type::Var(a)) => self.bind(a, Bound::Lower(ty)),
+            (Type::Var(a), ty) => self.bind(a, Bound::Exact(ty)),
+            (Type::Function(aarg, aret), Type::Function(barg, bret)) => {
+                match (aarg, barg) {
+                    (Some(aarg), Some(barg)) => self.constrain(&barg, &aarg)?,
+                    (None, None) => (),
+                    _ => return Err(Mismatch),
+                }
+                self.constrain(&aret, &bret)
+            }
+            (Type::Variant(a), Type::Variant(b)) => {
+                for a in a.iter() {
+                    let Some(b) = b.iter().find(|b| b.name == a.name) else {
+                        return Err(Mismatch);
+                    };
+                    match (&a.inner, &b.inner) {
+                        (Some(ai), Some(bi)) => self.constrain(ai, bi)?,
+                        (None, None) => (),
+                        _ => return Err(Mismatch),
+                    }
+                }
+
+                Ok(())
+            }
+            (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => {
+                for (a, b) in a.iter().zip(b.iter()) {
+                    self.constrain(a, b)?;
+                }
+
+                Ok(())
+            }
+            (Type::Array(a), Type::Array(b)) => {
+                self.constrain(&a, &b)?;
+                self.constrain(&b, &a)
+            }
+            (Type::Record(a), Type::Record(b)) if Type::same_fields(&a, &b) => {
+                for (a, b) in a.iter().zip(b.iter()) {
+                    self.constrain(&a.ty, &b.ty)?;
+                }
+
+                Ok(())
+            }
+            (Type::Instance(a, aargs), Type::Instance(b, bargs)) if a == b => {
+                // arguments are invariant, so they're related both ways
+                for (a, b) in aargs.iter().zip(bargs.iter()) {
+                    self.constrain(a, b)?;
+                    self.constrain(b, a)?;
+                }
+
+                Ok(())
+            }
+            (Type::Primitive(a), Type::Primitive(b)) if a == b => Ok(()),
+            (Type::Instance(sym, args), ty) if ty.is_structural() => {
+                let inner = self.unfold(sym, &args)?;
+                self.constrain(&inner, &ty)
+            }
+            (ty, Type::Instance(sym, args)) if ty.is_structural() => {
+                let inner = self.unfold(sym, &args)?;
+                self.constrain(&ty, &inner)
+            }
+            _ => Err(Mismatch),
+        }
+    }
+
+    /// The variable that `ty` ends at once it's followed through bound variables, if that
+    /// variable is bound to a lower bound.
+    fn lower_bounded(&self, ty: &Type<'s>) -> Option<TypeVar> {
+        let mut ty = ty;
+        while let &Type::Var(var) = ty {
+            match &self.vars[var.0 as usize].bound {
+                Bound::Lower(bound) if !matches!(bound, Type::Var(_)) => return Some(var),
+                Bound::Lower(bound) | Bound::Exact(bound) => ty = bound,
+                Bound::Unbound => break,
+            }
+        }
+
+        None
+    }
+
+    /// Makes `sub` flow into the lower bounded `var`, widening it if `sub` doesn't fit.
+    fn widen(&mut self, var: TypeVar, sub: &Type<'s>) -> Result<()> {
+        let Some(bound) = self.vars[var.0 as usize].bound.ty().cloned() else {
+            unreachable!("lower bounded variables are bound");
+        };
+        let snapshot = self.snapshot();
+        if self.constrain(sub, &bound).is_ok() {
+            return Ok(());
+        }
+        self.rollback(snapshot);
+
+        let widened = self.zonk(&bound).widen(&self.zonk(sub)).ok_or(Mismatch)?;
+        // the wider type must still fit wherever the variable has been used
+        for upper in self.vars[var.0 as usize].uppers.clone() {
+            if self.constrain(&widened, &upper).is_err() {
+                self.rollback(snapshot);
+                return Err(Mismatch);
+            }
+        }
+
+        self.set(var, Bound::Lower(widened));
+        Ok(())
+    }
+
+    /// What the instance of the `type` `sym` with `args` was defined as.
+    fn unfold(&self, sym: Symbol, args: &[Type<'s>]) -> Result<Type<'s>> {
+        let (params, inner) = self.typedefs.get(&sym).ok_or(Mismatch)?;
+        let args = params.iter().copied().zip(args.iter().cloned()).collect();
+        Ok(inner.substitute(&args))
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(self.trail.len())
+    }
+
+    /// Undoes every binding made since `snapshot` was taken.
+    pub fn rollback(&mut self, snapshot: Snapshot) {
+        while self.trail.len() > snapshot.0 {
+            match self.trail.pop().unwrap() {
+                Change::Bound(var, bound) => self.vars[var.0 as usize].bound = bound,
+                Change::Upper(var) => {
+                    self.vars[var.0 as usize].uppers.pop();
+                }
+            }
+        }
+    }
+
+    /// The variables in `ty` that can be quantified over now that its group is done.
+    pub fn generalize(&mut self, ty: &Type<'s>) -> Box<[TypeVar]> {
+        let mut vars = Vec::new();
+        self.zonk(ty).vars(&mut vars);
+        vars.retain(|var| self.vars[var.0 as usize].level > self.level);
+        self.generalized.extend(vars.iter().copied());
+
+        vars.into_boxed_slice()
+    }
+
+    /// Replaces the quantified `vars` in `ty` with fresh variables.
+    pub fn instantiate(&mut self, ty: &Type<'s>, vars: &[TypeVar]) -> Type<'s> {
+        let fresh: FxHashMap<_, _> = vars.iter().map(|&var| (var, self.fresh())).collect();
+        self.zonk(ty).substitute(&fresh)
+    }
+
+    fn bind(&mut self, var: TypeVar, bound: Bound<'s>) -> Result<()> {
+        let mut vars = Vec::new();
+        self.zonk(bound.ty().unwrap()).vars(&mut vars);
+        if vars.contains(&var) {
+            // binding would make an infinite type
+            return Err(Mismatch);
+        }
+
+        // anything `var` is bound to is now visible wherever `var` is
+        let level = self.vars[var.0 as usize].level;
+        for free in vars {
+            let free_level = &mut self.vars[free.0 as usize].level;
+            *free_level = (*free_level).min(level);
+        }
+
+        self.set(var, bound);
+        Ok(())
+    }
+
+    fn set(&mut self, var: TypeVar, bound: Bound<'s>) {
+        let old = std::mem::replace(&mut self.vars[var.0 as usize].bound, bound);
+        self.trail.push(Change::Bound(var, old));
+    }
+}
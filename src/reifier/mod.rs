@@ -2,18 +2,26 @@
 
 use std::ops::Deref;
 
+mod bta;
+mod builtins;
+mod exhaustive;
+mod infer;
 mod rst;
 mod scoper;
+pub use bta::annotate_binding_times;
+pub use builtins::*;
 pub use rst::*;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
+    errors::ErrorStream,
+    loader::{self, Source},
     parser,
     strings::{Intern, Strings},
     tokenizer::Span,
 };
 
-use self::scoper::Scoper;
+use self::{infer::Inference, scoper::Scoper};
 
 #[derive(Debug)]
 pub struct ReifyError<'s> {
@@ -32,34 +40,159 @@ pub enum ReifyErrorKind<'s> {
     InvalidVariant,
     InvalidPattern,
     TypeAssertionConflict,
+    /// Only integers, floats and booleans can be passed to or returned from externs.
+    InvalidExternType,
+    /// Nothing constrains the type of the expression enough to know what it is.
+    UnresolvedType,
+    /// A `def`'s type parameter was inferred to be a specific type, or the same type as
+    /// another of its parameters.
+    ConstrainedTypeParameter(Intern<'s>),
+    /// The arm of a `case` chain can't be reached, since the arms before it match every
+    /// value it would.
+    UnreachableArm,
+    /// A `case` chain that matches on a value has no arm for some of its values, which can
+    /// be any of the listed variants.
+    NonExhaustiveCase(Box<[Intern<'s>]>),
+    /// A record has more than one field with the same name.
+    DuplicateField(Intern<'s>),
+    /// The value isn't known to be a record with a field of this name.
+    UnknownField(Intern<'s>),
+    /// The module has an item of this name, but it isn't `pub`.
+    PrivateItem(Intern<'s>),
+    /// A `use` of a module that isn't at the top-level of a module.
+    UnexpectedUse,
+    /// What's received from with `<-` isn't a function of no arguments that returns either
+    /// `\Some` value or `\None`.
+    InvalidSource,
+    /// What a `for` iterates over isn't a range, an array or a source.
+    NotIterable,
+    /// A `break` or `continue` isn't in a loop of the function it's in.
+    OutsideLoop,
+    /// A `return` isn't in a function.
+    OutsideFunction,
+    /// A `set` assigns to a `val`, which can't change.
+    SetVal(Intern<'s>),
+    /// A `set` assigns to a `def` or another item rather than a local.
+    SetItem(Intern<'s>),
+    /// A `set` in a closure assigns to a `var` of the function around it. Closures capture
+    /// the values of locals, so this would only change the closure's own copy; a value that
+    /// both change has to be boxed in an array, like `[0]`, instead.
+    SetCaptured(Intern<'s>),
+    /// A `set` assigns to a `var` that a closure has captured, either earlier in the function
+    /// or in a loop around both of them. The closure would keep the value it captured rather
+    /// than see the new one; a value that both share has to be boxed in an array instead.
+    SetAfterCapture(Intern<'s>),
+    /// A `set` assigns a value to a `var` whose type it doesn't fit in.
+    SetType {
+        name: Intern<'s>,
+        declared: Type<'s>,
+        assigned: Type<'s>,
+    },
+    /// An integer literal is too big for the integer type it's written as.
+    LiteralOutOfRange(u64),
+    /// What's passed to an integer conversion like `to_u8` isn't an integer.
+    NotInteger,
 }
 
 type Result<'s, T> = std::result::Result<T, ReifyError<'s>>;
 
+/// Reifies the modules of a program into one module, where each module comes after the
+/// modules it uses, as [`crate::loader::load`] returns them.
 pub fn reify<'s>(
     strings: &'s Strings,
-    expr: &parser::Expr<'s>,
+    errors: &ErrorStream<'s>,
+    sources: &[Source<'s>],
 ) -> Result<'s, Module<'s>> {
     Reifier {
         strings,
+        errors,
         scoper: Scoper::default(),
         module: Module::default(),
         def_types: FxHashMap::default(),
+        schemes: FxHashMap::default(),
+        type_params: FxHashMap::default(),
+        type_arities: FxHashMap::default(),
         builtin_types: FxHashMap::default(),
+        infer: Inference::default(),
+        prefix: String::new(),
+        returns: Vec::new(),
+        loops: 0,
+        depths: FxHashMap::default(),
+        captures: FxHashSet::default(),
+        loop_vars: Vec::new(),
+        loop_depths: FxHashMap::default(),
     }
-    .reify(expr)
+    .reify(sources)
 }
 
-struct Reifier<'s> {
+struct Reifier<'e, 's> {
     strings: &'s Strings,
+    errors: &'e ErrorStream<'s>,
     scoper: Scoper<'s>,
     module: Module<'s>,
     def_types: FxHashMap<Symbol, Type<'s>>,
+    /// The variables each generic `def`'s type is quantified over.
+    schemes: FxHashMap<Symbol, Box<[TypeVar]>>,
+    /// The variable that each type parameter in scope stands for.
+    type_params: FxHashMap<Symbol, Type<'s>>,
+    /// How many type arguments each `type` takes.
+    type_arities: FxHashMap<Symbol, usize>,
     builtin_types: FxHashMap<Symbol, Type<'s>>,
+    infer: Inference<'s>,
+    /// What the names of the current module's items are qualified with, like `a::b::`.
+    prefix: String,
+    /// What each function being reified returns, innermost last, which `return`s are
+    /// checked against.
+    returns: Vec<Type<'s>>,
+    /// How many loops the innermost function being reified is in.
+    loops: usize,
+    /// How many functions each local is declared inside of, to tell the locals of a closure
+    /// from the ones it captures.
+    depths: FxHashMap<Symbol, usize>,
+    /// The `var`s that closures have captured so far, which can't be `set` anymore.
+    captures: FxHashSet<Symbol>,
+    /// The `var`s that are `set` or captured inside each loop being reified, innermost last.
+    loop_vars: Vec<LoopVars>,
+    /// How many of `loop_vars` each local is declared inside of, since a local that's
+    /// declared in a loop is a new one in each iteration.
+    loop_depths: FxHashMap<Symbol, usize>,
+}
+
+/// The `var`s that are declared outside a loop but `set` or captured by a closure inside it.
+/// A closure can't capture a `var` that the loop also `set`s, because the next iteration
+/// could change it after the closure captured it.
+#[derive(Default)]
+struct LoopVars {
+    sets: FxHashMap<Symbol, Span>,
+    captures: FxHashSet<Symbol>,
 }
 
-impl<'s> Reifier<'s> {
-    fn reify(mut self, expr: &parser::Expr<'s>) -> Result<'s, Module<'s>> {
+impl<'e, 's> Reifier<'e, 's> {
+    fn reify(mut self, sources: &[Source<'s>]) -> Result<'s, Module<'s>> {
+        self.scoper.push();
+        self.define_builtins();
+        let mut namespaces = FxHashMap::default();
+        for source in sources {
+            let namespace = self.module_(source, &namespaces)?;
+            namespaces.insert(&*source.path, namespace);
+        }
+        self.scoper.pop();
+
+        self.resolve_types()?;
+        bta::analyze(&mut self.module);
+
+        Ok(self.module)
+    }
+
+    /// Reifies the items of a module, returning the namespace they're exported into. The
+    /// modules of the standard library that are already reified are in scope by their names,
+    /// as if the module `use`d them.
+    fn module_(
+        &mut self,
+        source: &Source<'s>,
+        namespaces: &FxHashMap<&[Intern<'s>], Symbol>,
+    ) -> Result<'s, Symbol> {
+        let expr = &source.tree;
         // The top-level should be a scope
         let parser::Expr {
             kind: parser::ExprKind::Scope(scope),
@@ -80,138 +213,922 @@ impl<'s> Reifier<'s> {
         }
 
         self.scoper.push();
-        self.define_builtins();
-        self.scope(scope)?;
+        for (path, &module) in namespaces {
+            if let [std, name] = path {
+                if std.0 == loader::STD {
+                    self.scoper.bind(*name, module);
+                }
+            }
+        }
+        for use_ in &*scope.uses {
+            let &name = use_.path.last().unwrap();
+            let Some(&module) = namespaces.get(&*use_.path) else {
+                return Err(ReifyError {
+                    kind: ReifyErrorKind::UndefinedSymbol(name),
+                    span: Some(use_.decl_span),
+                });
+            };
+            self.scoper.bind(name, module);
+        }
+
+        self.prefix = source.path.iter().map(|name| format!("{}::", name.0)).collect();
+        let tail = self.infer.fresh();
+        self.scope_items(scope, &tail)?;
+
+        let namespace = self.scoper.new_namespace();
+        for def in scope.typedefs.iter().chain(&*scope.defs) {
+            let sym = self.scoper.lookup(def.name).unwrap();
+            self.scoper.export(namespace, def.name, sym, def.public);
+        }
         self.scoper.pop();
 
-        Ok(self.module)
+        Ok(namespace)
     }
 
     fn define_builtins(&mut self) {
         self.builtin_type("Int", Type::Primitive(PrimitiveType::Integer));
+        self.builtin_type("I64", Type::Primitive(PrimitiveType::Integer));
+        for (name, signed, bits) in SIZED_INTS {
+            let int = IntType { signed, bits };
+            self.builtin_type(name, Type::Primitive(PrimitiveType::Sized(int)));
+        }
         self.builtin_type("Float", Type::Primitive(PrimitiveType::Float));
         self.builtin_type("String", Type::Primitive(PrimitiveType::String));
         self.builtin_type("Bool", Type::Primitive(PrimitiveType::Boolean));
-        self.builtin_def(
-            "print",
-            Builtin::Print,
-            Type::Function(
-                Some(Box::new(Type::Primitive(PrimitiveType::String))),
-                Box::new(Type::Tuple(Box::new([]))),
-            ),
-        );
-        self.builtin_def(
-            "println",
-            Builtin::Print,
-            Type::Function(
-                Some(Box::new(Type::Primitive(PrimitiveType::String))),
-                Box::new(Type::Tuple(Box::new([]))),
-            ),
-        );
-        self.builtin_def(
-            "input",
-            Builtin::Print,
-            Type::Function(
-                Some(Box::new(Type::Tuple(Box::new([])))),
-                Box::new(Type::Primitive(PrimitiveType::String)),
-            ),
-        );
-        self.builtin_def(
-            "itoa",
-            Builtin::Print,
-            Type::Function(
-                Some(Box::new(Type::Primitive(PrimitiveType::Integer))),
-                Box::new(Type::Primitive(PrimitiveType::String)),
-            ),
-        );
+        let item = self.infer.fresh();
+        self.builtin_type("Array", Type::Array(Box::new(item)));
+        for info in BUILTINS {
+            let Some(name) = info.name else { continue };
+            let item = self.infer.fresh();
+            self.builtin_def(name, info.builtin, info.ty(&item));
+        }
     }
 
+    /// Defines a builtin type, which takes a parameter for each variable in `kind`.
     fn builtin_type(&mut self, name: &str, kind: Type<'s>) {
         let name = self.strings.intern(name.into());
-        self.builtin_types
-            .insert(self.scoper.new_symbol(name), kind);
+        let sym = self.scoper.new_symbol(name);
+        let mut vars = Vec::new();
+        kind.vars(&mut vars);
+        if !vars.is_empty() {
+            self.type_arities.insert(sym, vars.len());
+        }
+        self.builtin_types.insert(sym, kind);
     }
 
+    /// Defines a builtin function, which is generic over the variables in `ty`.
     fn builtin_def(&mut self, name: &str, which: Builtin, ty: Type<'s>) {
         let name = self.strings.intern(name.into());
-        self.module
-            .builtins
-            .insert(self.scoper.new_symbol(name), (which, ty));
+        let sym = self.scoper.new_symbol(name);
+        let mut vars = Vec::new();
+        ty.vars(&mut vars);
+        if !vars.is_empty() {
+            self.schemes.insert(sym, vars.into_boxed_slice());
+        }
+        self.module.builtins.insert(sym, (which, ty));
     }
 
-    fn scope(&mut self, scope: &parser::Scope<'s>) -> Result<'s, Scope<'s>> {
+    /// Reifies `scope`, whose last expression, unless it's discarded, must fit in `tail`.
+    fn scope(&mut self, scope: &parser::Scope<'s>, tail: &Type<'s>) -> Result<'s, Scope<'s>> {
+        if let [use_, ..] = &*scope.uses {
+            return Err(ReifyError {
+                kind: ReifyErrorKind::UnexpectedUse,
+                span: Some(use_.decl_span),
+            });
+        }
+
         self.scoper.push();
+        let scope = self.scope_items(scope, tail);
+        self.scoper.pop();
+        scope
+    }
 
+    /// Reifies the items and statements of `scope` into the current scope.
+    fn scope_items(
+        &mut self,
+        scope: &parser::Scope<'s>,
+        tail: &Type<'s>,
+    ) -> Result<'s, Scope<'s>> {
+        // every type in the scope is declared before any are defined, so they can refer
+        // to each other
+        let mut type_symbols = Vec::with_capacity(scope.typedefs.len());
         for def in &*scope.typedefs {
-            self.typedef(def.decl_span, def.name, &def.value)?;
+            let sym = self.scoper.new_symbol(def.name);
+            self.type_arities.insert(sym, def.params.len());
+            type_symbols.push(sym);
+        }
+
+        for (&sym, def) in type_symbols.iter().zip(&*scope.typedefs) {
+            self.typedef(sym, def)?;
+        }
+
+        for ext in &*scope.externs {
+            self.extern_(ext)?;
         }
 
         let mut assigned_symbols = Vec::with_capacity(scope.defs.len());
         for def in &*scope.defs {
-            let sym = self.scoper.new_symbol(def.name);
-            self.def_types.insert(sym, Type::Unknown);
-            assigned_symbols.push(sym);
+            assigned_symbols.push(self.scoper.new_symbol(def.name));
         }
-        for (sym, def) in assigned_symbols.into_iter().zip(scope.defs.iter()) {
-            self.def(sym, &def)?;
+
+        // each group of mutually recursive defs is inferred before the defs that use it, so
+        // they can be used generically
+        for group in Self::def_groups(&scope.defs) {
+            self.infer.enter();
+            for &i in &group {
+                let ty = self.infer.fresh();
+                self.def_types.insert(assigned_symbols[i], ty);
+            }
+            let mut params = Vec::with_capacity(group.len());
+            for &i in &group {
+                params.push(self.def(assigned_symbols[i], &scope.defs[i])?);
+            }
+            self.infer.exit();
+
+            for (&i, params) in group.iter().zip(params) {
+                let sym = assigned_symbols[i];
+                let vars = self.infer.generalize(&self.def_types[&sym]);
+
+                // the declared type parameters must have stayed generic, and distinct
+                let mut seen = Vec::with_capacity(params.len());
+                for (param, &name) in params.iter().zip(&*scope.defs[i].params) {
+                    match self.infer.zonk(param) {
+                        Type::Var(var) if vars.contains(&var) && !seen.contains(&var) => {
+                            seen.push(var)
+                        }
+                        _ => {
+                            return Err(ReifyError {
+                                kind: ReifyErrorKind::ConstrainedTypeParameter(name),
+                                span: Some(scope.defs[i].decl_span),
+                            })
+                        }
+                    }
+                }
+
+                if !vars.is_empty() {
+                    self.schemes.insert(sym, vars);
+                }
+            }
         }
 
         let mut exprs = Vec::with_capacity(scope.exprs.len());
-        for expr in &*scope.exprs {
-            exprs.push(self.expr(expr, &Type::Unknown)?)
+        for (i, expr) in scope.exprs.iter().enumerate() {
+            // what the scope is expected to be is what its value, the last expression, is
+            // expected to be, so a `type` it's checked against is unfolded for that
+            let ty = if !scope.discard && i + 1 == scope.exprs.len() {
+                tail.clone()
+            } else {
+                self.infer.fresh()
+            };
+            exprs.push(self.expr(expr, &ty)?)
         }
 
-        self.scoper.pop();
-
         Ok(Scope {
             exprs: exprs.into_boxed_slice(),
             discard: scope.discard,
         })
     }
 
-    fn def(&mut self, sym: Symbol, def: &parser::Def<'s>) -> Result<'s, ()> {
-        let body = self.expr(&def.value, &Type::Unknown)?;
+    /// Reifies the body of `def`, returning the variables its type parameters stand for.
+    fn def(&mut self, sym: Symbol, def: &parser::Def<'s>) -> Result<'s, Vec<Type<'s>>> {
+        self.scoper.push();
+        let params = self.type_params(&def.params);
+        let ty = self.def_types[&sym].clone();
+        let body = self.expr(&def.value, &ty);
+        self.scoper.pop();
+        let body = body?;
 
-        self.def_types.insert(sym, body.ty.clone());
         self.module.defs.insert(
             sym,
             Def {
                 decl_span: def.decl_span,
-                name: def.name,
+                name: self.qualify(def.name),
+                unchecked: def.unchecked,
                 body,
             },
         );
 
-        Ok(())
+        Ok(params)
     }
 
-    fn typedef(
+    /// The name of the current module's item `name`, as it's known outside of the module.
+    fn qualify(&self, name: Intern<'s>) -> Intern<'s> {
+        if self.prefix.is_empty() {
+            name
+        } else {
+            self.strings.intern(format!("{}{}", self.prefix, name.0).into_boxed_str())
+        }
+    }
+
+    /// Brings the type parameters `names` into the current scope, as fresh variables.
+    fn type_params(&mut self, names: &[Intern<'s>]) -> Vec<Type<'s>> {
+        let mut params = Vec::with_capacity(names.len());
+        for &name in names {
+            let var = self.infer.fresh();
+            let sym = self.scoper.new_symbol(name);
+            self.type_params.insert(sym, var.clone());
+            params.push(var);
+        }
+
+        params
+    }
+
+    /// Reifies a chain of `case` arms, checking the leading arms that match on the same
+    /// value for exhaustiveness and redundancy.
+    fn case(
         &mut self,
-        init_span: Span,
-        name: Intern<'s>,
-        value: &parser::Expr<'s>,
-    ) -> Result<'s, ()> {
-        let sym = self.scoper.new_symbol(name);
-        let inner = self.type_(value)?;
+        expr: &parser::Expr<'s>,
+        superty: &Type<'s>,
+    ) -> Result<'s, (ExprKind<'s>, Type<'s>)> {
+        let mut arms = Vec::new();
+        let mut rest = expr;
+        let otherwise = loop {
+            let parser::ExprKind::Case {
+                cond,
+                on_true,
+                on_false,
+            } = &rest.kind
+            else {
+                break Some(rest);
+            };
+            arms.push((&**cond, &**on_true, rest.span));
+            match on_false {
+                Some(on_false) => rest = on_false,
+                None => break None,
+            }
+        };
+
+        // without an expected type, each arm is inferred on its own and then widened
+        let expected = !self.infer.is_unbound(superty);
+        let arm_ty = |this: &mut Self| {
+            if expected {
+                superty.clone()
+            } else {
+                this.infer.fresh()
+            }
+        };
+
+        let mut reified = Vec::with_capacity(arms.len());
+        for (cond, on_true, span) in arms {
+            let ty = arm_ty(self);
+            self.scoper.push();
+            let cond = self.expr(cond, &Type::Primitive(PrimitiveType::Boolean))?;
+            let on_true = self.expr(on_true, &ty)?;
+            self.scoper.pop();
+            reified.push((cond, on_true, span));
+        }
+        let mut on_false = match otherwise {
+            Some(otherwise) => {
+                let ty = arm_ty(self);
+                Some(self.expr(otherwise, &ty)?)
+            }
+            None => None,
+        };
+
+        let scrutinee = Self::matched(&reified[0].0).map(|(sym, _, ty)| (sym, self.infer.zonk(ty)));
+        let mut total = false;
+        if let Some((sym, ty)) = scrutinee {
+            let patterns: Vec<_> = reified
+                .iter()
+                .map_while(|(cond, ..)| Self::matched(cond).filter(|m| m.0 == sym).map(|m| m.1))
+                .collect();
+            let matching = patterns.len();
+            let coverage = exhaustive::check(&self.module, &ty, &patterns);
+
+            for i in coverage.unreachable {
+                self.errors.warning(ReifyError {
+                    kind: ReifyErrorKind::UnreachableArm,
+                    span: Some(reified[i].0.span),
+                });
+            }
+
+            let rest_span = match reified.get(matching) {
+                Some((cond, ..)) => Some(cond.span),
+                None => on_false.as_ref().map(|on_false| on_false.span),
+            };
+            match (coverage.missing, rest_span) {
+                (None, Some(span)) => self.errors.warning(ReifyError {
+                    kind: ReifyErrorKind::UnreachableArm,
+                    span: Some(span),
+                }),
+                (None, None) => total = true,
+                (Some(missing), None) => {
+                    let error = ReifyError {
+                        kind: ReifyErrorKind::NonExhaustiveCase(missing.into_boxed_slice()),
+                        span: Some(expr.span),
+                    };
+                    // without a value to produce, a single arm just runs if it matches
+                    if expected && !self.infer.zonk(superty).is_unit() {
+                        return Err(error);
+                    } else if matching > 1 {
+                        self.errors.warning(error);
+                    }
+                }
+                (Some(_), Some(_)) => (),
+            }
+        }
+
+        // the last arm of a total chain runs whenever the others don't, so its pattern only
+        // has to bind its values
+        if total {
+            let (cond, on_true, span) = reified.pop().unwrap();
+            on_false = Some(Expr {
+                ty: on_true.ty.clone(),
+                kind: ExprKind::Scope(Scope {
+                    exprs: Box::new([cond, on_true]),
+                    discard: false,
+                }),
+                span,
+                bt: BindingTime::Dynamic,
+            });
+        }
+
+        for (cond, on_true, span) in reified.into_iter().rev() {
+            let ty = match &on_false {
+                Some(on_false) => {
+                    let Some(widened) = self.widen(&on_true.ty, &on_false.ty) else {
+                        return Err(ReifyError {
+                            kind: dbg!(ReifyErrorKind::InvalidType),
+                            span: Some(span),
+                        });
+                    };
+
+                    widened
+                }
+                None => Type::Tuple(Box::new([])),
+            };
+
+            on_false = Some(Expr {
+                kind: ExprKind::Case {
+                    cond: Box::new(cond),
+                    on_true: Box::new(on_true),
+                    on_false: on_false.map(Box::new),
+                },
+                span,
+                ty,
+                bt: BindingTime::Dynamic,
+            });
+        }
+
+        let case = on_false.unwrap();
+        Ok((case.kind, case.ty))
+    }
+
+    /// The local that `cond` matches a pattern against, along with the pattern and the
+    /// local's type.
+    fn matched<'c>(cond: &'c Expr<'s>) -> Option<(Symbol, &'c Pattern<'s>, &'c Type<'s>)> {
+        match &cond.kind {
+            ExprKind::StructuralEq(pattern, value) => match value.kind {
+                ExprKind::Load(sym) => Some((sym, &**pattern, &value.ty)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn extern_(&mut self, ext: &parser::Extern<'s>) -> Result<'s, ()> {
+        let param = self.type_(&ext.arg)?;
+        let ret = if let Some(ret) = &ext.ret {
+            self.type_(ret)?
+        } else {
+            Type::Tuple(Box::new([]))
+        };
+
+        let is_scalar = |ty: &Type| ty.is_int() || ty.is_float() || ty.is_bool();
+        let params_scalar = match &param {
+            Type::Tuple(items) => items.iter().all(is_scalar),
+            param => is_scalar(param),
+        };
+        if !params_scalar || !(is_scalar(&ret) || ret.is_unit()) {
+            return Err(ReifyError {
+                kind: ReifyErrorKind::InvalidExternType,
+                span: Some(ext.decl_span),
+            });
+        }
+
+        let sym = self.scoper.new_symbol(ext.name);
+        self.module.externs.insert(
+            sym,
+            Extern {
+                decl_span: ext.decl_span,
+                name: ext.name,
+                library: ext.library,
+                ty: Type::Function(Some(Box::new(param)), Box::new(ret)),
+            },
+        );
+
+        Ok(())
+    }
+
+    fn typedef(&mut self, sym: Symbol, def: &parser::Def<'s>) -> Result<'s, ()> {
+        // the parameters are one level deeper, so they're generalized like a def's
+        self.scoper.push();
+        self.infer.enter();
+        let args = self.type_params(&def.params);
+        let inner = self.type_(&def.value);
+        self.infer.exit();
+        self.scoper.pop();
+        let inner = inner?;
+
+        let params: Box<[TypeVar]> = args
+            .iter()
+            .map(|arg| match arg {
+                &Type::Var(var) => var,
+                _ => unreachable!("type parameters are fresh variables"),
+            })
+            .collect();
+        let ty = Type::Function(
+            Some(Box::new(inner.clone())),
+            Box::new(Type::Instance(sym, args.into_boxed_slice())),
+        );
+        let vars = self.infer.generalize(&ty);
+        if !vars.is_empty() {
+            self.schemes.insert(sym, vars);
+        }
+
+        self.infer.define(sym, params.clone(), inner.clone());
+        self.module.typedefs.insert(
+            sym,
+            TypeDef {
+                decl_span: def.decl_span,
+                name: self.qualify(def.name),
+                params,
+                inner,
+            },
+        );
         self.module.defs.insert(
             sym,
             Def {
-                decl_span: init_span,
-                name,
+                decl_span: def.decl_span,
+                name: self.qualify(def.name),
+                unchecked: false,
                 body: Expr {
                     kind: ExprKind::Constructor(sym),
-                    span: value.span,
-                    ty: Type::Function(Some(Box::new(inner)), Box::new(Type::Instance(sym))),
+                    span: def.value.span,
+                    ty,
+                    bt: BindingTime::Dynamic,
                 },
             },
         );
         Ok(())
     }
 
+    /// Reifies a call to the `source` of a receive, along with the type of the values it
+    /// sends. A source is called with no arguments for each value, and returns `\Some` value,
+    /// or `\None` once it has no more.
+    fn receive(&mut self, source: Expr<'s>, span: Span) -> Result<'s, (Expr<'s>, Type<'s>)> {
+        let unit = Type::Tuple(Box::new([]));
+        let ret = self.infer.fresh();
+        let fn_ty = Type::Function(Some(Box::new(unit.clone())), Box::new(ret.clone()));
+        if self.infer.constrain(&source.ty, &fn_ty).is_err() {
+            return Err(ReifyError {
+                kind: ReifyErrorKind::InvalidSource,
+                span: Some(span),
+            });
+        }
+
+        let item_ty = match self.unfold_all(&ret) {
+            Type::Var(_) => {
+                let item_ty = self.infer.fresh();
+                let variant = Type::Variant(Box::new([
+                    VariantItemType {
+                        name: self.strings.intern("Some".into()),
+                        inner: Some(item_ty.clone()),
+                    },
+                    VariantItemType {
+                        name: self.strings.intern("None".into()),
+                        inner: None,
+                    },
+                ]));
+                // a variable can be bound to anything
+                let _ = self.infer.constrain(&variant, &ret);
+                item_ty
+            }
+            Type::Variant(items) => {
+                let some = items.iter().find(|item| item.name.0 == "Some");
+                let other = items.iter().find(|item| !matches!(item.name.0, "Some" | "None"));
+                match (some, other) {
+                    (Some(VariantItemType { inner: Some(inner), .. }), None) => inner.clone(),
+                    _ => {
+                        return Err(ReifyError {
+                            kind: ReifyErrorKind::InvalidSource,
+                            span: Some(span),
+                        })
+                    }
+                }
+            }
+            _ => {
+                return Err(ReifyError {
+                    kind: ReifyErrorKind::InvalidSource,
+                    span: Some(span),
+                })
+            }
+        };
+
+        let call = Expr {
+            kind: ExprKind::Apply(
+                Box::new(source),
+                Box::new(Expr {
+                    kind: ExprKind::Tuple(Box::new([])),
+                    span,
+                    ty: unit,
+                    bt: BindingTime::Dynamic,
+                }),
+            ),
+            span,
+            ty: ret,
+            bt: BindingTime::Dynamic,
+        };
+
+        Ok((call, item_ty))
+    }
+
+    /// Desugars `for pattern in iterable body` into a `for` with an initializer, a condition
+    /// and an afterthought, which keep their state in locals the program can't name. Ranges
+    /// and arrays are walked by index, and sources are received from until they're done.
+    /// Elements that don't match the pattern are skipped.
+    fn for_in(
+        &mut self,
+        pattern: &parser::Expr<'s>,
+        iterable: &parser::Expr<'s>,
+        body: &parser::Expr<'s>,
+        span: Span,
+    ) -> Result<'s, (ExprKind<'s>, Type<'s>)> {
+        let unit = Type::Tuple(Box::new([]));
+        let int = Type::Primitive(PrimitiveType::Integer);
+        let boolean = Type::Primitive(PrimitiveType::Boolean);
+
+        self.scoper.push();
+
+        let (init, cond, afterthought, item) = if let parser::ExprKind::Range(start, end) =
+            &iterable.kind
+        {
+            let start = self.expr(start, &int)?;
+            let end = self.expr(end, &int)?;
+            let index = self.hidden_local("index", int.clone(), true, span);
+            let last = self.hidden_local("end", int.clone(), false, span);
+
+            let init = Self::synthetic(
+                ExprKind::Scope(Scope {
+                    exprs: Box::new([
+                        Self::bind(SolveMarker::Var, index, start, span),
+                        Self::bind(SolveMarker::Val, last, end, span),
+                    ]),
+                    discard: true,
+                }),
+                unit.clone(),
+                span,
+            );
+            let cond = Self::synthetic(
+                ExprKind::Binary(
+                    BinOp::Lt,
+                    Box::new(Self::synthetic(ExprKind::Load(index), int.clone(), span)),
+                    Box::new(Self::synthetic(ExprKind::Load(last), int.clone(), span)),
+                ),
+                boolean,
+                span,
+            );
+            let afterthought = Self::increment(index, span);
+            let item = Self::synthetic(ExprKind::Load(index), int, span);
+
+            (Some(init), cond, Some(afterthought), item)
+        } else {
+            let ty = self.infer.fresh();
+            let iterable = self.expr(iterable, &ty)?;
+
+            match self.unfold_all(&iterable.ty) {
+                Type::Array(item_ty) => {
+                    let array_ty = iterable.ty.clone();
+                    let array = self.hidden_local("array", array_ty.clone(), false, span);
+                    let index = self.hidden_local("index", int.clone(), true, span);
+                    let zero = Self::synthetic(
+                        ExprKind::Literal(Literal::Integer(0)),
+                        int.clone(),
+                        span,
+                    );
+
+                    let init = Self::synthetic(
+                        ExprKind::Scope(Scope {
+                            exprs: Box::new([
+                                Self::bind(SolveMarker::Val, array, iterable, span),
+                                Self::bind(SolveMarker::Var, index, zero, span),
+                            ]),
+                            discard: true,
+                        }),
+                        unit.clone(),
+                        span,
+                    );
+                    let len = self
+                        .module
+                        .builtins
+                        .iter()
+                        .find(|(_, (builtin, _))| *builtin == Builtin::Len)
+                        .map(|(&sym, _)| sym)
+                        .unwrap();
+                    let len_ty =
+                        Type::Function(Some(Box::new(array_ty.clone())), Box::new(int.clone()));
+                    let cond = Self::synthetic(
+                        ExprKind::Binary(
+                            BinOp::Lt,
+                            Box::new(Self::synthetic(ExprKind::Load(index), int.clone(), span)),
+                            Box::new(Self::synthetic(
+                                ExprKind::Apply(
+                                    Box::new(Self::synthetic(ExprKind::Load(len), len_ty, span)),
+                                    Box::new(Self::synthetic(
+                                        ExprKind::Load(array),
+                                        array_ty.clone(),
+                                        span,
+                                    )),
+                                ),
+                                int.clone(),
+                                span,
+                            )),
+                        ),
+                        boolean,
+                        span,
+                    );
+                    let afterthought = Self::increment(index, span);
+                    let item = Self::synthetic(
+                        ExprKind::Index(
+                            Box::new(Self::synthetic(ExprKind::Load(array), array_ty, span)),
+                            Box::new(Self::synthetic(ExprKind::Load(index), int, span)),
+                        ),
+                        *item_ty,
+                        span,
+                    );
+
+                    (Some(init), cond, Some(afterthought), item)
+                }
+                Type::Function(..) => {
+                    let source_ty = iterable.ty.clone();
+                    let source = self.hidden_local("source", source_ty.clone(), false, span);
+                    let init = Self::bind(SolveMarker::Val, source, iterable, span);
+                    let load = Self::synthetic(ExprKind::Load(source), source_ty, span);
+                    let (call, item_ty) = self.receive(load, span)?;
+                    let next = self.hidden_local("item", item_ty.clone(), false, span);
+
+                    let some = self.strings.intern("Some".into());
+                    let ty = match self.infer.shallow(&call.ty) {
+                        ty @ Type::Instance(..) => ty,
+                        _ => Type::Variant(Box::new([VariantItemType {
+                            name: some,
+                            inner: Some(item_ty.clone()),
+                        }])),
+                    };
+                    let pattern = Pattern {
+                        kind: PatternKind::Variant(
+                            some,
+                            Some(Box::new(Pattern {
+                                kind: PatternKind::Solve(SolveMarker::Val, next),
+                                span,
+                                ty: item_ty.clone(),
+                            })),
+                        ),
+                        span,
+                        ty,
+                    };
+                    let cond = Self::synthetic(
+                        ExprKind::StructuralEq(Box::new(pattern), Box::new(call)),
+                        boolean,
+                        span,
+                    );
+                    let item = Self::synthetic(ExprKind::Load(next), item_ty, span);
+
+                    (Some(init), cond, None, item)
+                }
+                _ => {
+                    return Err(ReifyError {
+                        kind: ReifyErrorKind::NotIterable,
+                        span: Some(iterable.span),
+                    })
+                }
+            }
+        };
+
+        let (pattern, body) = self.repeated(|this| {
+            let pattern = this.pattern(pattern, &item.ty)?;
+            this.loops += 1;
+            let body = this.expr(body, &unit)?;
+            this.loops -= 1;
+            Ok((pattern, body))
+        })?;
+
+        self.scoper.pop();
+
+        let body = Self::synthetic(
+            ExprKind::Case {
+                cond: Box::new(Self::synthetic(
+                    ExprKind::StructuralEq(Box::new(pattern), Box::new(item)),
+                    Type::Primitive(PrimitiveType::Boolean),
+                    span,
+                )),
+                on_true: Box::new(body),
+                on_false: None,
+            },
+            unit.clone(),
+            span,
+        );
+
+        Ok((
+            ExprKind::For {
+                init: init.map(Box::new),
+                cond: Box::new(cond),
+                afterthought: afterthought.map(Box::new),
+                body: Box::new(body),
+            },
+            unit,
+        ))
+    }
+
+    /// A local that the program can't refer to by name, which a desugaring keeps a value in.
+    fn hidden_local(&mut self, name: &str, ty: Type<'s>, mutable: bool, span: Span) -> Symbol {
+        let sym = self.scoper.new_anonymous_symbol();
+        self.module.locals.insert(
+            sym,
+            Local {
+                decl_span: span,
+                name: self.strings.intern(name.into()),
+                mutable,
+                ty,
+            },
+        );
+
+        sym
+    }
+
+    /// An expression that a desugaring puts in place of the one at `span`.
+    fn synthetic(kind: ExprKind<'s>, ty: Type<'s>, span: Span) -> Expr<'s> {
+        Expr {
+            kind,
+            span,
+            ty,
+            bt: BindingTime::Dynamic,
+        }
+    }
+
+    /// `val sym = value`, `var sym = value` or `set sym = value`.
+    fn bind(marker: SolveMarker, sym: Symbol, value: Expr<'s>, span: Span) -> Expr<'s> {
+        let pattern = Pattern {
+            kind: PatternKind::Solve(marker, sym),
+            span,
+            ty: value.ty.clone(),
+        };
+
+        Self::synthetic(
+            ExprKind::StructuralEq(Box::new(pattern), Box::new(value)),
+            Type::Primitive(PrimitiveType::Boolean),
+            span,
+        )
+    }
+
+    /// `set sym = sym + 1`, for an integer local `sym`.
+    fn increment(sym: Symbol, span: Span) -> Expr<'s> {
+        let int = Type::Primitive(PrimitiveType::Integer);
+        let sum = Self::synthetic(
+            ExprKind::Binary(
+                BinOp::Add,
+                Box::new(Self::synthetic(ExprKind::Load(sym), int.clone(), span)),
+                Box::new(Self::synthetic(
+                    ExprKind::Literal(Literal::Integer(1)),
+                    int.clone(),
+                    span,
+                )),
+            ),
+            int,
+            span,
+        );
+
+        Self::bind(SolveMarker::Set, sym, sum, span)
+    }
+
+    /// Whether `expr` is a `module.name` that refers to an item of a module, rather than a
+    /// field of a record.
+    fn is_qualified(&mut self, expr: &parser::Expr<'s>) -> bool {
+        let parser::ExprKind::Field(module, _) = &expr.kind else {
+            return false;
+        };
+        let &parser::ExprKind::Name(module) = &module.kind else {
+            return false;
+        };
+
+        self.scoper
+            .lookup(module)
+            .is_some_and(|sym| self.scoper.is_namespace(sym))
+    }
+
+    /// The item that the qualified name `module.name` refers to.
+    fn qualified(&mut self, expr: &parser::Expr<'s>) -> Result<'s, Symbol> {
+        let parser::ExprKind::Field(module, name) = &expr.kind else {
+            unreachable!("qualified names are fields of modules")
+        };
+        let &parser::ExprKind::Name(module) = &module.kind else {
+            unreachable!("qualified names are fields of modules")
+        };
+        let namespace = self.scoper.lookup(module).unwrap();
+
+        match self.scoper.lookup_in(namespace, *name) {
+            Some((sym, true)) => Ok(sym),
+            Some((_, false)) => Err(ReifyError {
+                kind: ReifyErrorKind::PrivateItem(*name),
+                span: Some(expr.span),
+            }),
+            None => Err(ReifyError {
+                kind: ReifyErrorKind::UndefinedSymbol(*name),
+                span: Some(expr.span),
+            }),
+        }
+    }
+
+    /// Reifies a reference to the item or local `sym`.
+    fn load(&mut self, sym: Symbol, span: Span) -> Result<'s, (ExprKind<'s>, Type<'s>)> {
+        let mut kind = ExprKind::Load(sym);
+        let ty = if let Some(local) = self.module.locals.get(&sym) {
+            local.ty.clone()
+        } else if let Some(def_ty) = self.def_types.get(&sym) {
+            // every use of a generic def gets its own instance of the type
+            match self.schemes.get(&sym) {
+                Some(vars) => self.infer.instantiate(def_ty, vars),
+                None => def_ty.clone(),
+            }
+        } else if let Some(def) = self.module.defs.get(&sym) {
+            // the only defs that are already reified are constructors
+            kind = ExprKind::Constructor(sym);
+            match self.schemes.get(&sym) {
+                Some(vars) => self.infer.instantiate(&def.body.ty, vars),
+                None => def.body.ty.clone(),
+            }
+        } else if let Some((_, ty)) = self.module.builtins.get(&sym) {
+            match self.schemes.get(&sym) {
+                Some(vars) => self.infer.instantiate(ty, vars),
+                None => ty.clone(),
+            }
+        } else if let Some(ext) = self.module.externs.get(&sym) {
+            ext.ty.clone()
+        } else {
+            return Err(ReifyError {
+                kind: dbg!(ReifyErrorKind::InvalidType),
+                span: Some(span),
+            });
+        };
+
+        let captured = self.module.locals.get(&sym).is_some_and(|local| local.mutable)
+            && self.depths[&sym] < self.returns.len();
+        if captured {
+            self.captures.insert(sym);
+            for vars in &mut self.loop_vars[self.loop_depths[&sym]..] {
+                vars.captures.insert(sym);
+            }
+        }
+
+        Ok((kind, ty))
+    }
+
+    /// Reifies the part of a loop that runs in every iteration with `reify`, and checks that
+    /// it doesn't both capture and `set` a `var` from outside the loop.
+    fn repeated<T>(&mut self, reify: impl FnOnce(&mut Self) -> Result<'s, T>) -> Result<'s, T> {
+        self.loop_vars.push(LoopVars::default());
+        let result = reify(self);
+        let vars = self.loop_vars.pop().unwrap();
+        let result = result?;
+
+        match vars.sets.iter().find(|(sym, _)| vars.captures.contains(sym)) {
+            Some((sym, &span)) => Err(ReifyError {
+                kind: ReifyErrorKind::SetAfterCapture(self.module.locals[sym].name),
+                span: Some(span),
+            }),
+            None => Ok(result),
+        }
+    }
+
     fn expr(&mut self, expr: &parser::Expr<'s>, superty: &Type<'s>) -> Result<'s, Expr<'s>> {
+        let value = self.hinted(expr, superty)?;
+        // the value is constrained against `superty` itself, which may be a variable that can
+        // still widen to fit it
+        if self.infer.constrain(&value.ty, superty).is_err() {
+            return Err(ReifyError {
+                kind: dbg!(ReifyErrorKind::InvalidType),
+                span: Some(expr.span),
+            });
+        }
+
+        Ok(value)
+    }
+
+    /// Reifies `expr` as something that's expected to fit in `superty`, without checking
+    /// that it does.
+    fn hinted(&mut self, expr: &parser::Expr<'s>, superty: &Type<'s>) -> Result<'s, Expr<'s>> {
+        let superty = &self.infer.shallow(superty);
+        if let Some(inner) = self.unfold_for(expr, superty) {
+            let expr = self.expr(expr, &inner)?;
+            return Ok(Expr {
+                ty: superty.clone(),
+                ..expr
+            });
+        }
+
         let (kind, ty) = match &expr.kind {
             parser::ExprKind::Scope(scope) => {
-                let scope = self.scope(scope)?;
+                let scope = self.scope(scope, superty)?;
 
                 match (scope.discard, scope.exprs.last()) {
                     (false, Some(last)) => {
@@ -227,21 +1144,39 @@ impl<'s> Reifier<'s> {
                 body,
                 ret,
             } => {
-                let arg = if let Some(arg) = arg {
-                    Some(self.pattern(arg, &Type::Unknown)?)
-                } else {
-                    None
+                let (param_ty, ret_ty) = match superty {
+                    Type::Function(param, ret) => (param.as_deref().cloned(), Some(ret.deref().clone())),
+                    _ => (None, None),
                 };
 
                 let body_ty = if let Some(ret) = ret {
-                    self.type_(&ret)?
+                    self.type_(ret)?
                 } else {
-                    Type::Unknown
+                    ret_ty.unwrap_or_else(|| self.infer.fresh())
+                };
+
+                // loops outside the function can't be left from inside it
+                let loops = std::mem::take(&mut self.loops);
+                self.returns.push(body_ty.clone());
+
+                let arg = if let Some(arg) = arg {
+                    let param_ty = param_ty.unwrap_or_else(|| self.infer.fresh());
+                    Some(self.pattern(arg, &param_ty)?)
+                } else {
+                    None
                 };
 
                 let body = self.expr(body, &body_ty)?;
+                self.returns.pop();
+                self.loops = loops;
+
                 let arg_ty = arg.as_ref().map(|a| Box::new(a.ty.clone()));
-                let body_ty = Box::new(body.ty.clone());
+                // a body that always `return`s still returns what its `return`s do
+                let body_ty = if body.ty.is_never() {
+                    Box::new(body_ty)
+                } else {
+                    Box::new(body.ty.clone())
+                };
 
                 (
                     ExprKind::Abstract {
@@ -261,17 +1196,25 @@ impl<'s> Reifier<'s> {
                 self.scoper.push();
 
                 let init = if let Some(init) = init {
-                    Some(Box::new(self.expr(init, &Type::Unknown)?))
+                    let ty = self.infer.fresh();
+                    Some(Box::new(self.expr(init, &ty)?))
                 } else {
                     None
                 };
-                let cond = Box::new(self.expr(cond, &Type::Primitive(PrimitiveType::Boolean))?);
-                let afterthought = if let Some(afterthought) = afterthought {
-                    Some(Box::new(self.expr(afterthought, &Type::Unknown)?))
-                } else {
-                    None
-                };
-                let body = Box::new(self.expr(body, &Type::Tuple(Box::new([])))?);
+                let (cond, afterthought, body) = self.repeated(|this| {
+                    let boolean = Type::Primitive(PrimitiveType::Boolean);
+                    let cond = Box::new(this.expr(cond, &boolean)?);
+                    let afterthought = if let Some(afterthought) = afterthought {
+                        let ty = this.infer.fresh();
+                        Some(Box::new(this.expr(afterthought, &ty)?))
+                    } else {
+                        None
+                    };
+                    this.loops += 1;
+                    let body = Box::new(this.expr(body, &Type::Tuple(Box::new([])))?);
+                    this.loops -= 1;
+                    Ok((cond, afterthought, body))
+                })?;
 
                 self.scoper.pop();
 
@@ -285,58 +1228,65 @@ impl<'s> Reifier<'s> {
                     Type::Tuple(Box::new([])),
                 )
             }
-            parser::ExprKind::Case {
-                cond,
-                on_true,
-                on_false,
-            } => {
-                self.scoper.push();
-                let cond = Box::new(self.expr(cond, &Type::Primitive(PrimitiveType::Boolean))?);
-                let on_true = Box::new(self.expr(on_true, superty)?);
-                self.scoper.pop();
-
-                if let Some(on_false) = on_false {
-                    let on_false = self.expr(&on_false, superty)?;
-
-                    let Some(widened) = on_true.ty.widen(&on_false.ty) else {
-                        return Err(ReifyError {
-                            kind: dbg!(ReifyErrorKind::InvalidType),
-                            span: Some(expr.span),
-                        });
-                    };
-
-                    (
-                        ExprKind::Case {
-                            cond,
-                            on_true,
-                            on_false: Some(Box::new(on_false)),
-                        },
-                        widened,
-                    )
-                } else {
-                    (
-                        ExprKind::Case {
-                            cond,
-                            on_true,
-                            on_false: None,
-                        },
-                        Type::Tuple(Box::new([])),
-                    )
+            parser::ExprKind::ForIn {
+                pattern,
+                iterable,
+                body,
+            } => self.for_in(pattern, iterable, body, expr.span)?,
+            parser::ExprKind::Range(..) => {
+                unreachable!("ranges are only parsed as what a `for` iterates over")
+            }
+            parser::ExprKind::Break | parser::ExprKind::Continue => {
+                if self.loops == 0 {
+                    return Err(ReifyError {
+                        kind: ReifyErrorKind::OutsideLoop,
+                        span: Some(expr.span),
+                    });
                 }
+
+                let kind = match expr.kind {
+                    parser::ExprKind::Break => ExprKind::Break,
+                    _ => ExprKind::Continue,
+                };
+                (kind, Type::Primitive(PrimitiveType::Never))
+            }
+            parser::ExprKind::Return(value) => {
+                let Some(ret) = self.returns.last().cloned() else {
+                    return Err(ReifyError {
+                        kind: ReifyErrorKind::OutsideFunction,
+                        span: Some(expr.span),
+                    });
+                };
+
+                let value = match value {
+                    Some(value) => self.expr(value, &ret)?,
+                    None => {
+                        let unit = Type::Tuple(Box::new([]));
+                        if self.infer.constrain(&unit, &ret).is_err() {
+                            return Err(ReifyError {
+                                kind: ReifyErrorKind::InvalidType,
+                                span: Some(expr.span),
+                            });
+                        }
+                        Self::synthetic(ExprKind::Tuple(Box::new([])), unit, expr.span)
+                    }
+                };
+                (
+                    ExprKind::Return(Box::new(value)),
+                    Type::Primitive(PrimitiveType::Never),
+                )
             }
+            parser::ExprKind::Case { .. } => self.case(expr, superty)?,
             parser::ExprKind::Tuple { items } => {
-                let supertys = if let Type::Tuple(tys) = superty {
-                    &**tys
-                } else {
-                    &[]
-                }
-                .iter()
-                .chain(std::iter::repeat(&Type::Unknown));
+                let supertys = match superty {
+                    Type::Tuple(tys) if tys.len() == items.len() => tys.to_vec(),
+                    _ => items.iter().map(|_| self.infer.fresh()).collect(),
+                };
 
                 let mut reified_items = Vec::with_capacity(items.len());
                 let mut tys = Vec::with_capacity(items.len());
-                for (item, superty) in items.iter().zip(supertys) {
-                    let reified = self.expr(&item, superty)?;
+                for (item, superty) in items.iter().zip(&supertys) {
+                    let reified = self.expr(item, superty)?;
                     tys.push(reified.ty.clone());
                     reified_items.push(reified);
                 }
@@ -346,11 +1296,117 @@ impl<'s> Reifier<'s> {
                     Type::Tuple(tys.into_boxed_slice()),
                 )
             }
+            parser::ExprKind::Record(fields) => {
+                let names = Self::field_names(fields)?;
+                // the fields are checked against the record's type if it has the same ones
+                let supertys: Vec<_> = match superty {
+                    Type::Record(tys) if tys.iter().map(|ty| ty.name).eq(names.iter().copied()) => {
+                        tys.iter().map(|ty| ty.ty.clone()).collect()
+                    }
+                    _ => names.iter().map(|_| self.infer.fresh()).collect(),
+                };
+
+                let mut values = Vec::with_capacity(fields.len());
+                let mut tys = supertys.clone();
+                for field in &**fields {
+                    let slot = names.iter().position(|&name| name == field.name).unwrap();
+                    let value = self.expr(&field.value, &supertys[slot])?;
+                    tys[slot] = value.ty.clone();
+                    values.push((slot, value));
+                }
+
+                let tys = names
+                    .into_iter()
+                    .zip(tys)
+                    .map(|(name, ty)| RecordFieldType { name, ty });
+                (ExprKind::Record(values.into_boxed_slice()), Type::Record(tys.collect()))
+            }
+            parser::ExprKind::Field(..) if self.is_qualified(expr) => {
+                let sym = self.qualified(expr)?;
+                self.load(sym, expr.span)?
+            }
+            &parser::ExprKind::Field(ref record, name) => {
+                let ty = self.infer.fresh();
+                let record = self.expr(record, &ty)?;
+                let record_ty = self.unfold_all(&record.ty);
+                let Some((slot, ty)) = record_ty.field(name) else {
+                    return Err(ReifyError {
+                        kind: ReifyErrorKind::UnknownField(name),
+                        span: Some(expr.span),
+                    });
+                };
+
+                (ExprKind::Field(Box::new(record), slot), ty.clone())
+            }
+            parser::ExprKind::Array(items) => {
+                let item_ty = match superty {
+                    Type::Array(item_ty) => Some(item_ty.deref().clone()),
+                    _ => None,
+                };
+
+                // without a known element type, the elements are widened into one
+                let mut reified_items = Vec::with_capacity(items.len());
+                let mut widened = item_ty.clone();
+                for item in &**items {
+                    let ty = item_ty.clone().unwrap_or_else(|| self.infer.fresh());
+                    let item = self.expr(item, &ty)?;
+                    widened = match widened {
+                        Some(widened) => Some(self.widen(&widened, &item.ty).ok_or(ReifyError {
+                            kind: ReifyErrorKind::InvalidType,
+                            span: Some(item.span),
+                        })?),
+                        None => Some(item.ty.clone()),
+                    };
+                    reified_items.push(item);
+                }
+
+                let item_ty = widened.unwrap_or_else(|| self.infer.fresh());
+                (
+                    ExprKind::Array(reified_items.into_boxed_slice()),
+                    Type::Array(Box::new(item_ty)),
+                )
+            }
+            parser::ExprKind::Index(array, index) => {
+                let (array, index, item_ty) = self.index(array, index)?;
+                (ExprKind::Index(array, index), item_ty)
+            }
+            parser::ExprKind::SetIndex(..) => {
+                return Err(ReifyError {
+                    kind: ReifyErrorKind::UnexpectedMarker(SolveMarker::Set),
+                    span: Some(expr.span),
+                })
+            }
             parser::ExprKind::Assert { expr, ty } => {
                 let asserted = self.type_(ty)?;
                 let expr = self.expr(expr, &asserted)?;
                 (expr.kind, expr.ty)
             }
+            &parser::ExprKind::Binary(BinOp::Recv, ref pattern, ref source) => {
+                // `pattern <- source` matches `source()` against `\Some pattern`
+                let ty = self.infer.fresh();
+                let source = self.expr(source, &ty)?;
+                let (call, item_ty) = self.receive(source, expr.span)?;
+                let pattern = self.pattern(pattern, &item_ty)?;
+
+                let some = self.strings.intern("Some".into());
+                let ty = match self.infer.shallow(&call.ty) {
+                    ty @ Type::Instance(..) => ty,
+                    _ => Type::Variant(Box::new([VariantItemType {
+                        name: some,
+                        inner: Some(pattern.ty.clone()),
+                    }])),
+                };
+                let pattern = Pattern {
+                    kind: PatternKind::Variant(some, Some(Box::new(pattern))),
+                    span: expr.span,
+                    ty,
+                };
+
+                (
+                    ExprKind::StructuralEq(Box::new(pattern), Box::new(call)),
+                    Type::Primitive(PrimitiveType::Boolean),
+                )
+            }
             &parser::ExprKind::Binary(BinOp::Eq, ref a, ref b) => {
                 let kind = match (Self::has_solve(a)?, Self::has_solve(b)?) {
                     (true, true) => {
@@ -359,19 +1415,25 @@ impl<'s> Reifier<'s> {
                             span: Some(expr.span),
                         })
                     }
-                    (true, false) => {
-                        let b = Box::new(self.expr(b, &Type::Unknown)?);
-                        ExprKind::StructuralEq(Box::new(self.pattern(a, &b.ty)?), b)
-                    }
-                    (false, true) => {
-                        let a = Box::new(self.expr(a, &Type::Unknown)?);
+                    (true, false) => self.solved(a, b)?,
+                    (false, true) => self.solved(b, a)?,
+                    // variants are compared by their contents, not their address
+                    (false, false) if self.is_variant_pattern(b) => {
+                        let ty = self.infer.fresh();
+                        let a = Box::new(self.expr(a, &ty)?);
                         ExprKind::StructuralEq(Box::new(self.pattern(b, &a.ty)?), a)
                     }
+                    (false, false) if self.is_variant_pattern(a) => {
+                        let ty = self.infer.fresh();
+                        let b = Box::new(self.expr(b, &ty)?);
+                        ExprKind::StructuralEq(Box::new(self.pattern(a, &b.ty)?), b)
+                    }
                     (false, false) => {
-                        let a = Box::new(self.expr(a, &Type::Unknown)?);
-                        let b = Box::new(self.expr(b, &Type::Unknown)?);
+                        let (a_ty, b_ty) = (self.infer.fresh(), self.infer.fresh());
+                        let a = Box::new(self.expr(a, &a_ty)?);
+                        let b = Box::new(self.expr(b, &b_ty)?);
 
-                        if a.ty.widen(&b.ty).is_none() {
+                        if self.widen(&a.ty, &b.ty).is_none() {
                             return Err(ReifyError {
                                 kind: dbg!(ReifyErrorKind::InvalidType),
                                 span: Some(expr.span),
@@ -387,10 +1449,10 @@ impl<'s> Reifier<'s> {
             &parser::ExprKind::Binary(op, ref a, ref b) => {
                 let (ty, a, b) = match op {
                     BinOp::Eq | BinOp::Neq => {
-                        let a = Box::new(self.expr(a, &Type::Unknown)?);
-                        let b = Box::new(self.expr(b, &Type::Unknown)?);
+                        let (a_ty, b_ty) = (self.infer.fresh(), self.infer.fresh());
+                        let (a, b) = self.operands(a, b, &a_ty, &b_ty)?;
 
-                        if a.ty.widen(&b.ty).is_none() {
+                        if self.widen(&a.ty, &b.ty).is_none() {
                             return Err(ReifyError {
                                 kind: dbg!(ReifyErrorKind::InvalidType),
                                 span: Some(expr.span),
@@ -400,24 +1462,12 @@ impl<'s> Reifier<'s> {
                         (Type::Primitive(PrimitiveType::Boolean), a, b)
                     }
                     BinOp::Lt | BinOp::Leq | BinOp::Gt | BinOp::Geq => {
-                        let a = Box::new(self.expr(a, &Type::Unknown)?);
-                        let b = Box::new(self.expr(b, &Type::Unknown)?);
+                        let (a_ty, b_ty) = (self.infer.fresh(), self.infer.fresh());
+                        let (a, b) = self.operands(a, b, &a_ty, &b_ty)?;
 
-                        if !a.ty.is_int() && !a.ty.is_float() {
-                            return Err(ReifyError {
-                                kind: dbg!(ReifyErrorKind::InvalidType),
-                                span: Some(expr.span),
-                            });
-                        }
-                        if !b.ty.is_int() && !b.ty.is_float() {
-                            return Err(ReifyError {
-                                kind: dbg!(ReifyErrorKind::InvalidType),
-                                span: Some(expr.span),
-                            });
-                        }
-                        if a.ty != b.ty {
+                        if !self.ordered(&a.ty, &b.ty) {
                             return Err(ReifyError {
-                                kind: dbg!(ReifyErrorKind::InvalidType),
+                                kind: ReifyErrorKind::InvalidType,
                                 span: Some(expr.span),
                             });
                         }
@@ -430,111 +1480,121 @@ impl<'s> Reifier<'s> {
                     | BinOp::Shl
                     | BinOp::Shr
                     | BinOp::Mod => {
-                        let a = Box::new(self.expr(a, &Type::Primitive(PrimitiveType::Integer))?);
-                        let b = Box::new(self.expr(b, &Type::Primitive(PrimitiveType::Integer))?);
+                        let (a, b) = self.operands(a, b, superty, superty)?;
 
-                        (Type::Primitive(PrimitiveType::Integer), a, b)
-                    }
-                    BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => {
-                        let a = Box::new(self.expr(a, superty)?);
-                        let b = Box::new(self.expr(b, superty)?);
-
-                        if !a.ty.is_int() && !a.ty.is_float() {
-                            return Err(ReifyError {
-                                kind: dbg!(ReifyErrorKind::InvalidType),
-                                span: Some(expr.span),
-                            });
-                        }
-                        if !b.ty.is_int() && !b.ty.is_float() {
+                        let ty = self.numeric(&a.ty, &b.ty).filter(|ty| ty.int_type().is_some());
+                        let Some(ty) = ty else {
                             return Err(ReifyError {
-                                kind: dbg!(ReifyErrorKind::InvalidType),
+                                kind: ReifyErrorKind::InvalidType,
                                 span: Some(expr.span),
                             });
-                        }
-                        if a.ty != b.ty {
+                        };
+
+                        (ty, a, b)
+                    }
+                    BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => {
+                        let (a, b) = self.operands(a, b, superty, superty)?;
+
+                        let Some(ty) = self.numeric(&a.ty, &b.ty) else {
                             return Err(ReifyError {
-                                kind: dbg!(ReifyErrorKind::InvalidType),
+                                kind: ReifyErrorKind::InvalidType,
                                 span: Some(expr.span),
                             });
-                        }
+                        };
 
-                        (a.ty.clone(), a, b)
+                        (ty, a, b)
                     }
-                    _ => todo!(),
+                    BinOp::And | BinOp::Or => {
+                        let boolean = Type::Primitive(PrimitiveType::Boolean);
+                        // the names that the operands of `&&` bind are in scope after it, since
+                        // both operands match when it does, but either operand of `||` may not
+                        let scoped = matches!(op, BinOp::Or);
+                        let a = self.operand(a, &boolean, scoped)?;
+                        let b = self.operand(b, &boolean, scoped)?;
+
+                        (boolean, a, b)
+                    }
+                    BinOp::Recv => unreachable!("receives are reified as patterns above"),
                 };
 
                 (ExprKind::Binary(op, a, b), ty)
             }
             &parser::ExprKind::Unary(op, ref a) => {
-                let a = Box::new(self.expr(a, &Type::Unknown)?);
+                let ty = self.infer.fresh();
+                // so that a negative integer literal is of the integer type it's written as
+                let ty = match op {
+                    UnOp::Neg => self.int_context(superty, &ty),
+                    UnOp::Not => ty,
+                };
+                let a = Box::new(self.expr(a, &ty)?);
 
                 let ty = match op {
                     UnOp::Neg => {
-                        if !a.ty.is_int() && !a.ty.is_float() {
+                        let Some(ty) = self.numeric(&a.ty, &a.ty) else {
                             return Err(ReifyError {
-                                kind: dbg!(ReifyErrorKind::InvalidType),
+                                kind: ReifyErrorKind::InvalidType,
                                 span: Some(expr.span),
                             });
-                        }
+                        };
 
-                        a.ty.clone()
+                        ty
                     }
                     UnOp::Not => {
-                        if !a.ty.is_bool() {
+                        let bool_ty = Type::Primitive(PrimitiveType::Boolean);
+                        if self.infer.constrain(&a.ty, &bool_ty).is_err() {
                             return Err(ReifyError {
-                                kind: dbg!(ReifyErrorKind::InvalidType),
+                                kind: ReifyErrorKind::InvalidType,
                                 span: Some(expr.span),
                             });
                         }
 
-                        a.ty.clone()
+                        bool_ty
                     }
                 };
 
                 (ExprKind::Unary(op, a), ty)
             }
             parser::ExprKind::Apply(a, b) => {
-                if !superty.is_unknown() {
-                    let b = Box::new(self.expr(b, &Type::Unknown)?);
-                    let a_ty =
-                        Type::Function(Some(Box::new(b.ty.clone())), Box::new(superty.clone()));
-                    let a = Box::new(self.expr(&a, &a_ty)?);
-                    let Type::Function(_, ret) = &a.ty else {
-                        return Err(ReifyError {
-                            kind: dbg!(ReifyErrorKind::InvalidType),
-                            span: Some(expr.span),
-                        });
-                    };
-
-                    let ret = ret.deref().clone();
-                    (ExprKind::Apply(a, b), ret)
-                } else {
-                    let a = Box::new(self.expr(a, &Type::Unknown)?);
-
-                    match &a.ty {
-                        Type::Function(param, ret) => {
-                            let Some(param) = param else {
-                                return Err(ReifyError {
-                                    kind: dbg!(ReifyErrorKind::InvalidType),
-                                    span: Some(expr.span),
-                                });
-                            };
+                // the argument is reified as the function's parameter type, so that what's
+                // expected of it, like the integer type of a literal, is known
+                let a_ty = self.infer.fresh();
+                let a = Box::new(self.expr(a, &a_ty)?);
+
+                match self.infer.shallow(&a.ty) {
+                    Type::Function(param, ret) => {
+                        let Some(param) = param else {
+                            return Err(ReifyError {
+                                kind: dbg!(ReifyErrorKind::InvalidType),
+                                span: Some(expr.span),
+                            });
+                        };
 
-                            let b = Box::new(self.expr(b, &*param)?);
+                        let b = Box::new(self.expr(b, &param)?);
 
-                            let ret = ret.deref().clone();
-                            (ExprKind::Apply(a, b), ret)
-                        }
-                        Type::Unknown => (
-                            ExprKind::Apply(a, Box::new(self.expr(b, &Type::Unknown)?)),
-                            Type::Unknown,
-                        ),
-                        _ => {
+                        let ret = ret.deref().clone();
+                        (ExprKind::Apply(a, b), ret)
+                    }
+                    Type::Var(_) => {
+                        // the function's type is inferred from how it's called
+                        let (param, ret) = (self.infer.fresh(), self.infer.fresh());
+                        let fn_ty = Type::Function(
+                            Some(Box::new(param.clone())),
+                            Box::new(ret.clone()),
+                        );
+                        if self.infer.constrain(&a.ty, &fn_ty).is_err() {
                             return Err(ReifyError {
-                                kind: dbg!(ReifyErrorKind::InvalidType),
+                                kind: ReifyErrorKind::InvalidType,
                                 span: Some(expr.span),
-                            })
+                            });
                         }
+
+                        (ExprKind::Apply(a, Box::new(self.expr(b, &param)?)), ret)
+                    }
+                    _ => {
+                        return Err(ReifyError {
+                            kind: dbg!(ReifyErrorKind::InvalidType),
+                            span: Some(expr.span),
+                        })
                     }
                 }
             }
@@ -547,7 +1607,16 @@ impl<'s> Reifier<'s> {
             parser::ExprKind::Variant(items) => match &**items {
                 [item] => {
                     if let Some(value) = &item.value {
-                        let value = self.expr(&value, &Type::Unknown)?;
+                        // the payload is checked against what the variant expects, if known
+                        let ty = match superty {
+                            Type::Variant(items) => items
+                                .iter()
+                                .find(|it| it.name == item.name)
+                                .and_then(|it| it.inner.clone()),
+                            _ => None,
+                        };
+                        let ty = ty.unwrap_or_else(|| self.infer.fresh());
+                        let value = self.expr(value, &ty)?;
                         let ty = Type::Variant(Box::new([VariantItemType {
                             name: item.name,
                             inner: Some(value.ty.clone()),
@@ -572,41 +1641,7 @@ impl<'s> Reifier<'s> {
             },
             &parser::ExprKind::Name(name) => {
                 if let Some(sym) = self.scoper.lookup(name) {
-                    let kind = ExprKind::Load(sym);
-                    let ty = if let Some(local) = self.module.locals.get(&sym) {
-                        if !superty.is_unknown() && superty.is_subtype(&local.ty) {
-                            let local = self.module.locals.get_mut(&sym).unwrap();
-                            local.ty = superty.clone();
-                            local.ty.clone()
-                        } else {
-                            local.ty.clone()
-                        }
-                    } else if let Some(def) = self.module.defs.get(&sym) {
-                        if !superty.is_unknown() && superty.is_subtype(&def.body.ty) {
-                            let def = self.module.defs.get_mut(&sym).unwrap();
-                            def.body.ty = superty.clone();
-                            def.body.ty.clone()
-                        } else {
-                            def.body.ty.clone()
-                        }
-                    } else if let Some(def_ty) = self.def_types.get(&sym) {
-                        if !superty.is_unknown() && superty.is_subtype(def_ty) {
-                            let def_ty = self.def_types.get_mut(&sym).unwrap();
-                            *def_ty = superty.clone();
-                            def_ty.clone()
-                        } else {
-                            def_ty.clone()
-                        }
-                    } else if let Some((_, ty)) = self.module.builtins.get(&sym) {
-                        ty.clone()
-                    } else {
-                        return Err(ReifyError {
-                            kind: dbg!(ReifyErrorKind::InvalidType),
-                            span: Some(expr.span),
-                        });
-                    };
-
-                    (kind, ty)
+                    self.load(sym, expr.span)?
                 } else {
                     return Err(ReifyError {
                         kind: ReifyErrorKind::UndefinedSymbol(name),
@@ -614,31 +1649,366 @@ impl<'s> Reifier<'s> {
                     });
                 }
             }
-            &parser::ExprKind::Literal(lit) => {
-                let kind = ExprKind::Literal(lit);
-                let ty = match lit {
-                    Literal::Float(_) => Type::Primitive(PrimitiveType::Float),
-                    Literal::Integer(_) => Type::Primitive(PrimitiveType::Integer),
-                    Literal::String(_) => Type::Primitive(PrimitiveType::String),
-                    Literal::Boolean(_) => Type::Primitive(PrimitiveType::Boolean),
-                };
+            &parser::ExprKind::Literal(lit) => {
+                let kind = ExprKind::Literal(lit);
+                let ty = match lit {
+                    Literal::Float(_) => Type::Primitive(PrimitiveType::Float),
+                    Literal::Integer(i) => match self.infer.shallow(superty) {
+                        ty @ Type::Primitive(PrimitiveType::Sized(int)) => {
+                            if !int.fits(i) {
+                                return Err(ReifyError {
+                                    kind: ReifyErrorKind::LiteralOutOfRange(i),
+                                    span: Some(expr.span),
+                                });
+                            }
+
+                            ty
+                        }
+                        _ => Type::Primitive(PrimitiveType::Integer),
+                    },
+                    Literal::String(_) => Type::Primitive(PrimitiveType::String),
+                    Literal::Boolean(_) => Type::Primitive(PrimitiveType::Boolean),
+                };
+
+                (kind, ty)
+            }
+        };
+
+        Ok(Expr {
+            kind,
+            span: expr.span,
+            ty,
+            bt: BindingTime::Dynamic,
+        })
+    }
+
+    /// What the `type` instance `superty` was defined as, if `expr` is a variant or tuple
+    /// that has to be checked against it: values of a `type` are written the same way as
+    /// values of what it was defined as, without naming it.
+    fn unfold_for(&self, expr: &parser::Expr<'s>, superty: &Type<'s>) -> Option<Type<'s>> {
+        match &expr.kind {
+            parser::ExprKind::Variant(_)
+            | parser::ExprKind::Tuple { .. }
+            | parser::ExprKind::Record(_) => {
+                let inner = self.module.unfold(superty)?;
+                Some(self.infer.shallow(&inner))
+            }
+            _ => None,
+        }
+    }
+
+    /// Reifies `array[index]`, along with the type of the array's elements.
+    fn index(
+        &mut self,
+        array: &parser::Expr<'s>,
+        index: &parser::Expr<'s>,
+    ) -> Result<'s, (Box<Expr<'s>>, Box<Expr<'s>>, Type<'s>)> {
+        let array_ty = self.infer.fresh();
+        let array = self.expr(array, &array_ty)?;
+        let item_ty = match self.unfold_all(&array.ty) {
+            Type::Array(item_ty) => *item_ty,
+            ty => {
+                // something only known to be indexed is an array
+                let item_ty = self.infer.fresh();
+                if self.infer.constrain(&ty, &Type::Array(Box::new(item_ty.clone()))).is_err() {
+                    return Err(ReifyError {
+                        kind: ReifyErrorKind::InvalidType,
+                        span: Some(array.span),
+                    });
+                }
+
+                item_ty
+            }
+        };
+        let index = self.expr(index, &Type::Primitive(PrimitiveType::Integer))?;
+
+        Ok((Box::new(array), Box::new(index), item_ty))
+    }
+
+    /// What `ty` is once it's followed through bound variables and `type` definitions.
+    fn unfold_all(&self, ty: &Type<'s>) -> Type<'s> {
+        let mut ty = self.infer.shallow(ty);
+        while let Some(inner) = self.module.unfold(&ty) {
+            ty = self.infer.shallow(&inner);
+        }
+
+        ty
+    }
+
+    /// The names of a record's `fields`, sorted into the order of their slots.
+    fn field_names(fields: &[parser::RecordField<'s>]) -> Result<'s, Vec<Intern<'s>>> {
+        let mut names: Vec<_> = fields.iter().map(|field| field.name).collect();
+        names.sort_by_key(|name| name.0);
+
+        for field in fields {
+            if names.iter().filter(|&&name| name == field.name).count() > 1 {
+                return Err(ReifyError {
+                    kind: ReifyErrorKind::DuplicateField(field.name),
+                    span: Some(field.span),
+                });
+            }
+        }
+
+        Ok(names)
+    }
+
+    /// The type of an arithmetic operation on `a` and `b`, which must both be the same
+    /// number type. Operands whose type isn't known otherwise are taken to be `Int`s.
+    fn numeric(&mut self, a: &Type<'s>, b: &Type<'s>) -> Option<Type<'s>> {
+        self.infer.constrain(a, b).ok()?;
+        self.infer.constrain(b, a).ok()?;
+
+        let ty = self.infer.shallow(a);
+        if let Type::Var(_) = ty {
+            let int = Type::Primitive(PrimitiveType::Integer);
+            self.infer.constrain(&ty, &int).ok()?;
+            Some(int)
+        } else if ty.int_type().is_some() || ty.is_float() {
+            Some(ty)
+        } else {
+            None
+        }
+    }
+
+    /// Reifies the operands of a binary operator as `a_ty` and `b_ty`. An integer literal is
+    /// reified after the other operand, so that it's of the same integer type.
+    fn operands(
+        &mut self,
+        a: &parser::Expr<'s>,
+        b: &parser::Expr<'s>,
+        a_ty: &Type<'s>,
+        b_ty: &Type<'s>,
+    ) -> Result<'s, (Box<Expr<'s>>, Box<Expr<'s>>)> {
+        if Self::is_int_literal(a) && !Self::is_int_literal(b) {
+            let b = Box::new(self.expr(b, b_ty)?);
+            let a_ty = self.int_context(&b.ty, a_ty);
+            let a = Box::new(self.expr(a, &a_ty)?);
+            Ok((a, b))
+        } else {
+            let a = Box::new(self.expr(a, a_ty)?);
+            let b_ty = self.int_context(&a.ty, b_ty);
+            let b = Box::new(self.expr(b, &b_ty)?);
+            Ok((a, b))
+        }
+    }
+
+    /// Reifies an operand of `&&` or `||`, in a scope of its own if it's `scoped`.
+    fn operand(
+        &mut self,
+        expr: &parser::Expr<'s>,
+        ty: &Type<'s>,
+        scoped: bool,
+    ) -> Result<'s, Box<Expr<'s>>> {
+        if scoped {
+            self.scoper.push();
+        }
+        let expr = self.expr(expr, ty)?;
+        if scoped {
+            self.scoper.pop();
+        }
+
+        Ok(Box::new(expr))
+    }
+
+    /// `context` if it's an integer type other than `Int`, which integer literals reified as
+    /// it take, or `ty` otherwise.
+    fn int_context(&mut self, context: &Type<'s>, ty: &Type<'s>) -> Type<'s> {
+        match self.infer.shallow(context) {
+            sized @ Type::Primitive(PrimitiveType::Sized(_)) => sized,
+            _ => ty.clone(),
+        }
+    }
+
+    fn is_int_literal(expr: &parser::Expr) -> bool {
+        match &expr.kind {
+            parser::ExprKind::Literal(Literal::Integer(_)) => true,
+            parser::ExprKind::Unary(UnOp::Neg, a) => Self::is_int_literal(a),
+            _ => false,
+        }
+    }
+
+    /// Whether `a` and `b` can be compared with `<` and friends: they must both be the same
+    /// number type, or both be strings, which are ordered byte by byte.
+    fn ordered(&mut self, a: &Type<'s>, b: &Type<'s>) -> bool {
+        if self.infer.shallow(a).is_string() || self.infer.shallow(b).is_string() {
+            let string = Type::Primitive(PrimitiveType::String);
+            return self.infer.constrain(a, &string).is_ok()
+                && self.infer.constrain(b, &string).is_ok();
+        }
+
+        self.numeric(a, b).is_some()
+    }
+
+    /// Like [`Type::widen`], but also binds variables that are still being inferred.
+    fn widen(&mut self, a: &Type<'s>, b: &Type<'s>) -> Option<Type<'s>> {
+        let (a, b) = (self.infer.zonk(a), self.infer.zonk(b));
+        if let Some(widened) = a.widen(&b) {
+            return Some(widened);
+        }
+
+        for (sub, sup) in [(&a, &b), (&b, &a)] {
+            let snapshot = self.infer.snapshot();
+            if self.infer.constrain(sub, sup).is_ok() {
+                return Some(self.infer.zonk(sup));
+            }
+            self.infer.rollback(snapshot);
+        }
+
+        None
+    }
+
+    /// Replaces every variable in the module's types by what it was inferred to be.
+    fn resolve_types(&mut self) -> Result<'s, ()> {
+        let mut defs = std::mem::take(&mut self.module.defs);
+        for def in defs.values_mut() {
+            self.resolve_expr(&mut def.body)?;
+        }
+        self.module.defs = defs;
+
+        let mut locals = std::mem::take(&mut self.module.locals);
+        for local in locals.values_mut() {
+            local.ty = self.resolve_type(&local.ty, local.decl_span)?;
+        }
+        self.module.locals = locals;
+
+        Ok(())
+    }
+
+    fn resolve_expr(&self, expr: &mut Expr<'s>) -> Result<'s, ()> {
+        expr.ty = self.resolve_type(&expr.ty, expr.span)?;
+
+        match &mut expr.kind {
+            ExprKind::Scope(scope) => {
+                for expr in scope.exprs.iter_mut() {
+                    self.resolve_expr(expr)?;
+                }
+            }
+            ExprKind::Abstract { arg, body, .. } => {
+                if let Some(arg) = arg {
+                    self.resolve_pattern(arg)?;
+                }
+                self.resolve_expr(body)?;
+            }
+            ExprKind::For {
+                init,
+                cond,
+                afterthought,
+                body,
+            } => {
+                for expr in init.iter_mut().chain([cond, body]).chain(afterthought) {
+                    self.resolve_expr(expr)?;
+                }
+            }
+            ExprKind::Case {
+                cond,
+                on_true,
+                on_false,
+            } => {
+                for expr in [cond, on_true].into_iter().chain(on_false) {
+                    self.resolve_expr(expr)?;
+                }
+            }
+            ExprKind::Tuple(items) | ExprKind::Array(items) => {
+                for item in items.iter_mut() {
+                    self.resolve_expr(item)?;
+                }
+            }
+            ExprKind::Record(fields) => {
+                for (_, value) in fields.iter_mut() {
+                    self.resolve_expr(value)?;
+                }
+            }
+            ExprKind::Field(record, _) => self.resolve_expr(record)?,
+            ExprKind::StructuralEq(pat, value) => {
+                self.resolve_pattern(pat)?;
+                self.resolve_expr(value)?;
+            }
+            ExprKind::Binary(_, a, b) | ExprKind::Apply(a, b) | ExprKind::Index(a, b) => {
+                self.resolve_expr(a)?;
+                self.resolve_expr(b)?;
+            }
+            ExprKind::Unary(_, a) => self.resolve_expr(a)?,
+            ExprKind::Variant(_, data) => {
+                if let Some(data) = data {
+                    self.resolve_expr(data)?;
+                }
+            }
+            ExprKind::Return(value) => self.resolve_expr(value)?,
+            &mut ExprKind::Load(sym) => self.check_conversion(sym, &expr.ty, expr.span)?,
+            ExprKind::Constructor(_)
+            | ExprKind::Literal(_)
+            | ExprKind::Break
+            | ExprKind::Continue => (),
+        }
 
-                (kind, ty)
-            }
+        Ok(())
+    }
+
+    /// Checks that an integer conversion like `to_u8` is only ever given integers, now that
+    /// the type of each use of it is known.
+    fn check_conversion(&self, sym: Symbol, ty: &Type<'s>, span: Span) -> Result<'s, ()> {
+        let Some(&(builtin, _)) = self.module.builtins.get(&sym) else {
+            return Ok(());
         };
 
-        if !ty.is_subtype(superty) {
-            return Err(ReifyError {
-                kind: dbg!(ReifyErrorKind::InvalidType),
-                span: Some(expr.span),
-            });
+        match ty {
+            Type::Function(Some(param), _)
+                if builtin.int_conversion().is_some() && param.int_type().is_none() =>
+            {
+                Err(ReifyError {
+                    kind: ReifyErrorKind::NotInteger,
+                    span: Some(span),
+                })
+            }
+            _ => Ok(()),
         }
+    }
 
-        Ok(Expr {
-            kind,
-            span: expr.span,
-            ty,
-        })
+    fn resolve_pattern(&self, pat: &mut Pattern<'s>) -> Result<'s, ()> {
+        pat.ty = self.resolve_type(&pat.ty, pat.span)?;
+
+        match &mut pat.kind {
+            PatternKind::Apply(a, b) => {
+                self.resolve_pattern(a)?;
+                self.resolve_pattern(b)?;
+            }
+            PatternKind::Variant(_, data) => {
+                if let Some(data) = data {
+                    self.resolve_pattern(data)?;
+                }
+            }
+            PatternKind::Tuple(items) => {
+                for item in items.iter_mut() {
+                    self.resolve_pattern(item)?;
+                }
+            }
+            PatternKind::Record(fields) => {
+                for field in fields.iter_mut().flatten() {
+                    self.resolve_pattern(field)?;
+                }
+            }
+            PatternKind::SetIndex(array, index) => {
+                self.resolve_expr(array)?;
+                self.resolve_expr(index)?;
+            }
+            PatternKind::Solve(..) | PatternKind::Symbol(_) => (),
+        }
+
+        Ok(())
+    }
+
+    /// Substitutes what `ty` was inferred to be. The only variables left over are those a
+    /// generic `def` is quantified over; anything else was never constrained.
+    fn resolve_type(&self, ty: &Type<'s>, span: Span) -> Result<'s, Type<'s>> {
+        let ty = self.infer.zonk(ty);
+        if self.infer.is_resolved(&ty) {
+            Ok(ty)
+        } else {
+            Err(ReifyError {
+                kind: ReifyErrorKind::UnresolvedType,
+                span: Some(span),
+            })
+        }
     }
 
     fn type_(&mut self, expr: &parser::Expr<'s>) -> Result<'s, Type<'s>> {
@@ -659,50 +2029,71 @@ impl<'s> Reifier<'s> {
 
                 new_items.into_boxed_slice()
             }),
-            parser::ExprKind::Apply(a, b) => {
-                let parser::ExprKind::Name(name) = &a.kind else {
-                    return Err(ReifyError {
-                        kind: dbg!(ReifyErrorKind::InvalidType),
-                        span: Some(expr.span),
-                    });
-                };
-                let Some(sym) = self.scoper.lookup(*name) else {
-                    return Err(ReifyError {
-                        kind: ReifyErrorKind::UndefinedSymbol(*name),
-                        span: Some(a.span),
+            parser::ExprKind::Record(fields) => {
+                let names = Self::field_names(fields)?;
+                let mut new_fields = Vec::with_capacity(fields.len());
+                for name in names {
+                    let field = fields.iter().find(|field| field.name == name).unwrap();
+                    new_fields.push(RecordFieldType {
+                        name,
+                        ty: self.type_(&field.value)?,
                     });
-                };
+                }
 
-                let arg_type = self.type_(b)?;
-                let Some(def) = self.module.defs.get(&sym) else {
-                    return Err(ReifyError {
-                        kind: dbg!(ReifyErrorKind::InvalidType),
-                        span: Some(expr.span),
-                    });
+                Type::Record(new_fields.into_boxed_slice())
+            }
+            parser::ExprKind::Apply(a, b) => {
+                let sym = match &a.kind {
+                    parser::ExprKind::Name(name) => match self.scoper.lookup(*name) {
+                        Some(sym) => sym,
+                        None => {
+                            return Err(ReifyError {
+                                kind: ReifyErrorKind::UndefinedSymbol(*name),
+                                span: Some(a.span),
+                            })
+                        }
+                    },
+                    parser::ExprKind::Field(..) if self.is_qualified(a) => self.qualified(a)?,
+                    _ => {
+                        return Err(ReifyError {
+                            kind: dbg!(ReifyErrorKind::InvalidType),
+                            span: Some(expr.span),
+                        })
+                    }
                 };
-                let Expr { kind: ExprKind::Load(_), ty: Type::Function(Some(a_param), _), .. } = &def.body else {
-                    return Err(ReifyError {
-                        kind: dbg!(ReifyErrorKind::InvalidType),
-                        span: Some(expr.span),
-                    });
+
+                // a type with several parameters takes its arguments as a tuple
+                let arity = self.type_arities.get(&sym).copied().unwrap_or(0);
+                let args = match &b.kind {
+                    parser::ExprKind::Tuple { items } if arity > 1 && items.len() == arity => {
+                        let mut args = Vec::with_capacity(arity);
+                        for item in &**items {
+                            args.push(self.type_(item)?);
+                        }
+
+                        args
+                    }
+                    _ if arity == 1 => vec![self.type_(b)?],
+                    _ => {
+                        return Err(ReifyError {
+                            kind: dbg!(ReifyErrorKind::InvalidType),
+                            span: Some(expr.span),
+                        })
+                    }
                 };
 
-                if !arg_type.is_subtype(&a_param) {
-                    return Err(ReifyError {
-                        kind: dbg!(ReifyErrorKind::InvalidType),
-                        span: Some(expr.span),
-                    });
+                match self.builtin_types.get(&sym) {
+                    Some(builtin) => {
+                        let mut vars = Vec::new();
+                        builtin.vars(&mut vars);
+                        builtin.substitute(&vars.into_iter().zip(args).collect())
+                    }
+                    None => Type::Instance(sym, args.into_boxed_slice()),
                 }
-
-                Type::Instance(sym)
             }
             parser::ExprKind::Name(name) => {
                 if let Some(symbol) = self.scoper.lookup(*name) {
-                    if let Some(builtin) = self.builtin_types.get(&symbol) {
-                        builtin.clone()
-                    } else {
-                        Type::Instance(symbol)
-                    }
+                    self.named_type(symbol, expr.span)?
                 } else {
                     return Err(ReifyError {
                         kind: ReifyErrorKind::UndefinedSymbol(*name),
@@ -710,13 +2101,17 @@ impl<'s> Reifier<'s> {
                     });
                 }
             }
+            parser::ExprKind::Field(..) if self.is_qualified(expr) => {
+                let symbol = self.qualified(expr)?;
+                self.named_type(symbol, expr.span)?
+            }
             parser::ExprKind::Variant(items) => Type::Variant({
                 let mut new_items = Vec::with_capacity(items.len());
                 for item in &**items {
                     new_items.push(VariantItemType {
                         name: item.name,
                         inner: if let Some(value) = &item.value {
-                            Some(self.type_(&value)?)
+                            Some(self.type_(value)?)
                         } else {
                             None
                         },
@@ -736,6 +2131,24 @@ impl<'s> Reifier<'s> {
         Ok(kind)
     }
 
+    /// The type that the name of `symbol` stands for, without any type arguments.
+    fn named_type(&mut self, symbol: Symbol, span: Span) -> Result<'s, Type<'s>> {
+        if let Some(param) = self.type_params.get(&symbol) {
+            Ok(param.clone())
+        } else if self.type_arities.get(&symbol).is_some_and(|&n| n > 0) {
+            // generic types can't be used without their arguments
+            Err(ReifyError {
+                kind: dbg!(ReifyErrorKind::InvalidType),
+                span: Some(span),
+            })
+        } else if let Some(builtin) = self.builtin_types.get(&symbol) {
+            Ok(builtin.clone())
+        } else {
+            Ok(Type::Instance(symbol, Box::new([])))
+        }
+    }
+
+    #[allow(dead_code, unnecessary_transmutes, clippy::missing_transmute_annotations)]
     fn x() {
         let _: (i32, _) = {
             let x = unsafe { std::mem::transmute::<u32, _>(0) };
@@ -751,46 +2164,60 @@ impl<'s> Reifier<'s> {
         expr: &parser::Expr<'s>,
         superty: &Type<'s>,
     ) -> Result<'s, Pattern<'s>> {
+        // a `var` keeps what it's matched against itself, which may be a variable that can
+        // still widen as it's assigned to
+        let declared = superty;
+        let superty = &self.infer.shallow(superty);
+        if let Some(inner) = self.unfold_for(expr, superty) {
+            let pattern = self.pattern(expr, &inner)?;
+            return Ok(Pattern {
+                ty: superty.clone(),
+                ..pattern
+            });
+        }
+
         let (kind, ty) = match &expr.kind {
             parser::ExprKind::Apply(a, b) => {
-                let a_ty = if !superty.is_unknown() {
-                    let Type::Instance(sym) = superty else {
+                let a_ty = if !self.infer.is_unbound(superty) {
+                    let Type::Instance(sym, _) = superty else {
                         return Err(ReifyError {
                             kind: dbg!(ReifyErrorKind::InvalidType),
                             span: Some(a.span),
                         });
                     };
 
-                    let constructor = self.module.defs.get(&sym).unwrap();
+                    // the constructor of the instance's type arguments in particular
+                    let constructor = &self.module.defs.get(sym).unwrap().body.ty;
+                    let mut args = FxHashMap::default();
+                    if let Type::Function(_, ret) = constructor {
+                        ret.match_vars(superty, &mut args);
+                    }
 
-                    constructor.body.ty.clone()
+                    constructor.substitute(&args)
                 } else {
-                    Type::Unknown
+                    self.infer.fresh()
                 };
 
-                let a = self.pattern(&a, &a_ty)?;
-                let Type::Function(Some(param), ret) = &a.ty else {
+                let a = self.pattern(a, &a_ty)?;
+                let Type::Function(Some(param), ret) = self.infer.shallow(&a.ty) else {
                     return Err(ReifyError {
                         kind: dbg!(ReifyErrorKind::InvalidType),
                         span: Some(a.span),
                     });
                 };
-                let &Type::Instance(sym) = &**ret else {
+                let Type::Instance(..) = *ret else {
                     return Err(ReifyError {
                         kind: dbg!(ReifyErrorKind::InvalidType),
                         span: Some(a.span),
                     });
                 };
-                let b = self.pattern(&b, &param)?;
+                let b = self.pattern(b, &param)?;
 
-                (
-                    PatternKind::Apply(Box::new(a), Box::new(b)),
-                    Type::Instance(sym),
-                )
+                (PatternKind::Apply(Box::new(a), Box::new(b)), *ret)
             }
             parser::ExprKind::Variant(items) => match &**items {
                 [item] => {
-                    let value_ty = if !superty.is_unknown() {
+                    let value_ty = if !self.infer.is_unbound(superty) {
                         let Type::Variant(items) = superty else {
                             return Err(ReifyError {
                                 kind: dbg!(ReifyErrorKind::InvalidType),
@@ -798,7 +2225,7 @@ impl<'s> Reifier<'s> {
                             });
                         };
 
-                        let Some(variant) = items.iter().filter(|it| it.name == item.name).next()
+                        let Some(variant) = items.iter().find(|it| it.name == item.name)
                         else {
                             return Err(ReifyError {
                                 kind: dbg!(ReifyErrorKind::InvalidType),
@@ -806,13 +2233,16 @@ impl<'s> Reifier<'s> {
                             });
                         };
 
-                        variant.inner.as_ref().unwrap_or(&Type::Unknown).clone()
+                        match &variant.inner {
+                            Some(inner) => inner.clone(),
+                            None => self.infer.fresh(),
+                        }
                     } else {
-                        Type::Unknown
+                        self.infer.fresh()
                     };
 
                     if let Some(value) = &item.value {
-                        let value = self.pattern(&value, &value_ty)?;
+                        let value = self.pattern(value, &value_ty)?;
                         let inner_ty = Some(value.ty.clone());
 
                         (
@@ -840,7 +2270,7 @@ impl<'s> Reifier<'s> {
                 }
             },
             parser::ExprKind::Tuple { items } => {
-                if !superty.is_unknown() {
+                if !self.infer.is_unbound(superty) {
                     let Type::Tuple(ty_items) = superty else {
                         return Err(ReifyError {
                             kind: dbg!(ReifyErrorKind::InvalidType),
@@ -868,7 +2298,8 @@ impl<'s> Reifier<'s> {
                     let mut reified_items = Vec::with_capacity(items.len());
                     let mut ty_its = Vec::with_capacity(items.len());
                     for item in &**items {
-                        let item = self.pattern(&item, &Type::Unknown)?;
+                        let ty = self.infer.fresh();
+                        let item = self.pattern(item, &ty)?;
                         ty_its.push(item.ty.clone());
                         reified_items.push(item);
                     }
@@ -879,6 +2310,49 @@ impl<'s> Reifier<'s> {
                     )
                 }
             }
+            parser::ExprKind::Record(fields) => {
+                let names = Self::field_names(fields)?;
+                if !self.infer.is_unbound(superty) {
+                    // fields that aren't named aren't matched
+                    let Type::Record(ty_fields) = superty else {
+                        return Err(ReifyError {
+                            kind: dbg!(ReifyErrorKind::InvalidType),
+                            span: Some(expr.span),
+                        });
+                    };
+
+                    let mut slots: Vec<_> = ty_fields.iter().map(|_| None).collect();
+                    for field in &**fields {
+                        let Some((slot, ty)) = superty.field(field.name) else {
+                            return Err(ReifyError {
+                                kind: ReifyErrorKind::UnknownField(field.name),
+                                span: Some(field.span),
+                            });
+                        };
+                        slots[slot] = Some(self.pattern(&field.value, ty)?);
+                    }
+
+                    (
+                        PatternKind::Record(slots.into_boxed_slice()),
+                        superty.clone(),
+                    )
+                } else {
+                    // the record has exactly the fields that are named
+                    let mut slots: Vec<_> = names.iter().map(|_| None).collect();
+                    for field in &**fields {
+                        let slot = names.iter().position(|&name| name == field.name).unwrap();
+                        let ty = self.infer.fresh();
+                        slots[slot] = Some(self.pattern(&field.value, &ty)?);
+                    }
+
+                    let tys = names.into_iter().zip(&slots).map(|(name, slot)| RecordFieldType {
+                        name,
+                        ty: slot.as_ref().unwrap().ty.clone(),
+                    });
+                    let ty = Type::Record(tys.collect());
+                    (PatternKind::Record(slots.into_boxed_slice()), ty)
+                }
+            }
             parser::ExprKind::Assert { expr, ty } => {
                 let ty = self.type_(ty)?;
                 let pat = self.pattern(expr, &ty)?;
@@ -886,13 +2360,6 @@ impl<'s> Reifier<'s> {
                 (pat.kind, pat.ty)
             }
             &parser::ExprKind::Solve(marker @ (SolveMarker::Val | SolveMarker::Var), name) => {
-                if superty.is_unknown() {
-                    return Err(ReifyError {
-                        kind: dbg!(ReifyErrorKind::InvalidType),
-                        span: Some(expr.span),
-                    });
-                };
-
                 let sym = self.scoper.new_symbol(name);
 
                 self.module.locals.insert(
@@ -901,11 +2368,13 @@ impl<'s> Reifier<'s> {
                         decl_span: expr.span,
                         name,
                         mutable: marker == SolveMarker::Var,
-                        ty: superty.clone(),
+                        ty: declared.clone(),
                     },
                 );
+                self.depths.insert(sym, self.returns.len());
+                self.loop_depths.insert(sym, self.loop_vars.len());
 
-                (PatternKind::Solve(marker, sym), superty.clone())
+                (PatternKind::Solve(marker, sym), declared.clone())
             }
             &parser::ExprKind::Solve(SolveMarker::Set, name) => {
                 let Some(sym) = self.scoper.lookup(name) else {
@@ -915,21 +2384,31 @@ impl<'s> Reifier<'s> {
                     });
                 };
 
-                let Some(local) = self.module.locals.get(&sym) else {
+                let ty = self.assigned_type(sym, name, expr.span)?;
+                if self.infer.constrain(superty, &ty).is_err() {
                     return Err(ReifyError {
-                        kind: ReifyErrorKind::InvalidPattern,
+                        kind: ReifyErrorKind::SetType {
+                            name,
+                            declared: self.infer.zonk(&ty),
+                            assigned: self.infer.zonk(superty),
+                        },
                         span: Some(expr.span),
                     });
-                };
+                }
 
-                if !local.mutable {
+                (PatternKind::Solve(SolveMarker::Set, sym), ty)
+            }
+            parser::ExprKind::SetIndex(array, index) => {
+                let (array, index, item_ty) = self.index(array, index)?;
+                let known = !self.infer.is_unbound(superty);
+                if known && self.infer.constrain(superty, &item_ty).is_err() {
                     return Err(ReifyError {
-                        kind: ReifyErrorKind::InvalidPattern,
+                        kind: ReifyErrorKind::InvalidType,
                         span: Some(expr.span),
                     });
                 }
 
-                (PatternKind::Solve(SolveMarker::Set, sym), local.ty.clone())
+                (PatternKind::SetIndex(array, index), item_ty)
             }
             &parser::ExprKind::Name(name) => {
                 let Some(sym) = self.scoper.lookup(name) else {
@@ -939,10 +2418,11 @@ impl<'s> Reifier<'s> {
                     });
                 };
 
-                (
-                    PatternKind::Symbol(sym),
-                    self.module.defs.get(&sym).unwrap().body.ty.clone(),
-                )
+                self.symbol_pattern(sym, expr.span, superty)?
+            }
+            parser::ExprKind::Field(..) if self.is_qualified(expr) => {
+                let sym = self.qualified(expr)?;
+                self.symbol_pattern(sym, expr.span, superty)?
             }
             _ => {
                 return Err(ReifyError {
@@ -952,6 +2432,14 @@ impl<'s> Reifier<'s> {
             }
         };
 
+        // a value that's only known from being matched against has the pattern's type
+        if self.infer.is_unbound(superty) && self.infer.constrain(&ty, superty).is_err() {
+            return Err(ReifyError {
+                kind: ReifyErrorKind::InvalidType,
+                span: Some(expr.span),
+            });
+        }
+
         Ok(Pattern {
             kind,
             span: expr.span,
@@ -959,6 +2447,116 @@ impl<'s> Reifier<'s> {
         })
     }
 
+    /// Reifies matching `value` against `pattern`, which solves for names.
+    fn solved(
+        &mut self,
+        pattern: &parser::Expr<'s>,
+        value: &parser::Expr<'s>,
+    ) -> Result<'s, ExprKind<'s>> {
+        let ty = self.declared_type(pattern)?;
+        let (value, pattern) = if let parser::ExprKind::Solve(SolveMarker::Set, _) = pattern.kind {
+            // the `set` checks the value itself, so it can say which `var` it didn't fit
+            let value = self.hinted(value, &ty)?;
+            let pattern = self.pattern(pattern, &value.ty)?;
+            (value, pattern)
+        } else {
+            // the pattern matches what it declares, which a `var` can widen
+            let value = self.expr(value, &ty)?;
+            (value, self.pattern(pattern, &ty)?)
+        };
+
+        Ok(ExprKind::StructuralEq(Box::new(pattern), Box::new(value)))
+    }
+
+    /// The type that `pattern` declares what it's matched against to be, so the value can be
+    /// checked against it, or a fresh variable if it doesn't declare one.
+    fn declared_type(&mut self, pattern: &parser::Expr<'s>) -> Result<'s, Type<'s>> {
+        match &pattern.kind {
+            parser::ExprKind::Assert { ty, .. } => self.type_(ty),
+            // a `set` declares what it assigns to
+            &parser::ExprKind::Solve(SolveMarker::Set, name) => match self.scoper.lookup(name) {
+                Some(sym) => self.assigned_type(sym, name, pattern.span),
+                None => Err(ReifyError {
+                    kind: ReifyErrorKind::UndefinedSymbol(name),
+                    span: Some(pattern.span),
+                }),
+            },
+            _ => Ok(self.infer.fresh()),
+        }
+    }
+
+    /// The type of `sym`, which `set name` assigns to, if it can be assigned to from here.
+    fn assigned_type(&mut self, sym: Symbol, name: Intern<'s>, span: Span) -> Result<'s, Type<'s>> {
+        let kind = match self.module.locals.get(&sym) {
+            None => ReifyErrorKind::SetItem(name),
+            Some(local) if !local.mutable => ReifyErrorKind::SetVal(name),
+            Some(_) if self.depths[&sym] < self.returns.len() => {
+                ReifyErrorKind::SetCaptured(name)
+            }
+            Some(_) if self.captures.contains(&sym) => ReifyErrorKind::SetAfterCapture(name),
+            Some(local) => {
+                for vars in &mut self.loop_vars[self.loop_depths[&sym]..] {
+                    vars.sets.entry(sym).or_insert(span);
+                }
+                return Ok(local.ty.clone());
+            }
+        };
+
+        Err(ReifyError {
+            kind,
+            span: Some(span),
+        })
+    }
+
+    /// A pattern that matches against the constructor `sym`.
+    fn symbol_pattern(
+        &mut self,
+        sym: Symbol,
+        span: Span,
+        superty: &Type<'s>,
+    ) -> Result<'s, (PatternKind<'s>, Type<'s>)> {
+        let ty = &self.module.defs.get(&sym).unwrap().body.ty;
+        let ty = match self.schemes.get(&sym) {
+            Some(vars) => self.infer.instantiate(ty, vars),
+            None => ty.clone(),
+        };
+        if !self.infer.is_unbound(superty) && self.infer.constrain(superty, &ty).is_err() {
+            return Err(ReifyError {
+                kind: ReifyErrorKind::InvalidType,
+                span: Some(span),
+            });
+        }
+
+        Ok((PatternKind::Symbol(sym), ty))
+    }
+
+    /// Whether `expr` is a variant that's only made up of other such variants, which means
+    /// it can be matched against as a pattern.
+    fn is_variant_pattern(&mut self, expr: &parser::Expr<'s>) -> bool {
+        match &expr.kind {
+            parser::ExprKind::Variant(items) => match &**items {
+                [item] => item.value.as_deref().is_none_or(|v| self.is_variant_pattern(v)),
+                _ => false,
+            },
+            parser::ExprKind::Apply(a, b) => {
+                let sym = match a.kind {
+                    parser::ExprKind::Name(name) => self.scoper.lookup(name),
+                    parser::ExprKind::Field(..) if self.is_qualified(a) => self.qualified(a).ok(),
+                    _ => return false,
+                };
+                let is_type = sym.is_some_and(|sym| self.type_arities.contains_key(&sym));
+                is_type && self.is_variant_pattern(b)
+            }
+            parser::ExprKind::Tuple { items } => {
+                items.iter().all(|item| self.is_variant_pattern(item))
+            }
+            parser::ExprKind::Record(fields) => {
+                fields.iter().all(|field| self.is_variant_pattern(&field.value))
+            }
+            _ => false,
+        }
+    }
+
     fn has_solve(expr: &parser::Expr) -> Result<'s, bool> {
         Ok(match &expr.kind {
             parser::ExprKind::Tuple { items } => {
@@ -981,10 +2579,195 @@ impl<'s> Reifier<'s> {
 
                 false
             }
-            parser::ExprKind::Assert { expr, .. } => Self::has_solve(&expr)?,
-            parser::ExprKind::Apply(a, b) => Self::has_solve(&a)? || Self::has_solve(&b)?,
-            parser::ExprKind::Solve(_, _) => true,
+            parser::ExprKind::Record(fields) => {
+                for field in fields.iter() {
+                    if Self::has_solve(&field.value)? {
+                        return Ok(true);
+                    }
+                }
+
+                false
+            }
+            parser::ExprKind::Assert { expr, .. } => Self::has_solve(expr)?,
+            parser::ExprKind::Apply(a, b) => Self::has_solve(a)? || Self::has_solve(b)?,
+            parser::ExprKind::Solve(_, _) | parser::ExprKind::SetIndex(..) => true,
             _ => false,
         })
     }
+
+    /// Splits `defs` into groups of mutually recursive defs, ordered so that every group
+    /// comes after the groups it refers to.
+    fn def_groups(defs: &[parser::Def<'s>]) -> Vec<Vec<usize>> {
+        let indices: FxHashMap<_, _> = defs.iter().enumerate().map(|(i, def)| (def.name, i)).collect();
+        let edges: Vec<Vec<usize>> = defs
+            .iter()
+            .map(|def| {
+                let mut names = FxHashSet::default();
+                Self::names(&def.value, &mut names);
+                names.iter().filter_map(|name| indices.get(name).copied()).collect()
+            })
+            .collect();
+
+        // Tarjan's algorithm, which finds each group after every group it refers to
+        struct Tarjan<'e> {
+            edges: &'e [Vec<usize>],
+            index: Vec<Option<usize>>,
+            lowlink: Vec<usize>,
+            stack: Vec<usize>,
+            on_stack: Vec<bool>,
+            groups: Vec<Vec<usize>>,
+        }
+
+        impl Tarjan<'_> {
+            fn visit(&mut self, v: usize) {
+                let index = self.stack.len() + self.groups.iter().map(Vec::len).sum::<usize>();
+                self.index[v] = Some(index);
+                self.lowlink[v] = index;
+                self.stack.push(v);
+                self.on_stack[v] = true;
+
+                for i in 0..self.edges[v].len() {
+                    let w = self.edges[v][i];
+                    match self.index[w] {
+                        None => {
+                            self.visit(w);
+                            self.lowlink[v] = self.lowlink[v].min(self.lowlink[w]);
+                        }
+                        Some(w_index) if self.on_stack[w] => {
+                            self.lowlink[v] = self.lowlink[v].min(w_index);
+                        }
+                        Some(_) => (),
+                    }
+                }
+
+                if Some(self.lowlink[v]) == self.index[v] {
+                    let mut group = Vec::new();
+                    loop {
+                        let w = self.stack.pop().unwrap();
+                        self.on_stack[w] = false;
+                        group.push(w);
+                        if w == v {
+                            break;
+                        }
+                    }
+                    // keep the source order within a group
+                    group.sort_unstable();
+                    self.groups.push(group);
+                }
+            }
+        }
+
+        let mut tarjan = Tarjan {
+            edges: &edges,
+            index: vec![None; defs.len()],
+            lowlink: vec![0; defs.len()],
+            stack: Vec::new(),
+            on_stack: vec![false; defs.len()],
+            groups: Vec::new(),
+        };
+        for v in 0..defs.len() {
+            if tarjan.index[v].is_none() {
+                tarjan.visit(v);
+            }
+        }
+
+        tarjan.groups
+    }
+
+    /// Collects every name `expr` refers to. Shadowing is ignored, so this may include names
+    /// that actually refer to something else.
+    fn names(expr: &parser::Expr<'s>, out: &mut FxHashSet<Intern<'s>>) {
+        match &expr.kind {
+            parser::ExprKind::Scope(scope) => {
+                for def in scope.defs.iter().chain(&*scope.typedefs) {
+                    Self::names(&def.value, out);
+                }
+                for expr in scope.exprs.iter() {
+                    Self::names(expr, out);
+                }
+            }
+            parser::ExprKind::Abstract { arg, body, ret, .. } => {
+                for expr in arg.iter().chain([body]).chain(ret) {
+                    Self::names(expr, out);
+                }
+            }
+            parser::ExprKind::For {
+                init,
+                cond,
+                afterthought,
+                body,
+            } => {
+                for expr in init.iter().chain([cond, body]).chain(afterthought) {
+                    Self::names(expr, out);
+                }
+            }
+            parser::ExprKind::ForIn {
+                pattern,
+                iterable,
+                body,
+            } => {
+                for expr in [pattern, iterable, body] {
+                    Self::names(expr, out);
+                }
+            }
+            parser::ExprKind::Range(a, b) => {
+                Self::names(a, out);
+                Self::names(b, out);
+            }
+            parser::ExprKind::Return(value) => {
+                if let Some(value) = value {
+                    Self::names(value, out);
+                }
+            }
+            parser::ExprKind::Case {
+                cond,
+                on_true,
+                on_false,
+            } => {
+                for expr in [cond, on_true].into_iter().chain(on_false) {
+                    Self::names(expr, out);
+                }
+            }
+            parser::ExprKind::Tuple { items } => {
+                for item in items.iter() {
+                    Self::names(item, out);
+                }
+            }
+            parser::ExprKind::Assert { expr, ty } => {
+                Self::names(expr, out);
+                Self::names(ty, out);
+            }
+            parser::ExprKind::Binary(_, a, b) | parser::ExprKind::Apply(a, b) => {
+                Self::names(a, out);
+                Self::names(b, out);
+            }
+            parser::ExprKind::Unary(_, a) | parser::ExprKind::Field(a, _) => Self::names(a, out),
+            parser::ExprKind::Index(a, b) | parser::ExprKind::SetIndex(a, b) => {
+                Self::names(a, out);
+                Self::names(b, out);
+            }
+            parser::ExprKind::Array(items) => {
+                for item in items.iter() {
+                    Self::names(item, out);
+                }
+            }
+            parser::ExprKind::Record(fields) => {
+                for field in fields.iter() {
+                    Self::names(&field.value, out);
+                }
+            }
+            parser::ExprKind::Variant(items) => {
+                for item in items.iter() {
+                    if let Some(value) = &item.value {
+                        Self::names(value, out);
+                    }
+                }
+            }
+            &parser::ExprKind::Solve(_, name) | &parser::ExprKind::Name(name) => {
+                out.insert(name);
+            }
+            parser::ExprKind::Literal(_) | parser::ExprKind::Break | parser::ExprKind::Continue => {
+            }
+        }
+    }
 }
@@ -1,9 +1,11 @@
 use std::{hash::Hash, num::NonZeroUsize};
 
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{strings::Intern, tokenizer::Span};
 
+use super::Builtin;
+
 pub use crate::parser::{BinOp, Literal, SolveMarker, UnOp};
 
 #[derive(Debug, Default)]
@@ -12,22 +14,55 @@ pub struct Module<'s> {
     pub defs: FxHashMap<Symbol, Def<'s>>,
     pub locals: FxHashMap<Symbol, Local<'s>>,
     pub builtins: FxHashMap<Symbol, (Builtin, Type<'s>)>,
+    pub externs: FxHashMap<Symbol, Extern<'s>>,
+    pub typedefs: FxHashMap<Symbol, TypeDef<'s>>,
+}
+
+impl<'s> Module<'s> {
+    /// The type that the `type` definition `ty` is an instance of was defined as, with
+    /// the instance's type arguments substituted in.
+    pub fn unfold(&self, ty: &Type<'s>) -> Option<Type<'s>> {
+        let Type::Instance(sym, args) = ty else {
+            return None;
+        };
+        let def = self.typedefs.get(sym)?;
+
+        let args = def.params.iter().copied().zip(args.iter().cloned()).collect();
+        Some(def.inner.substitute(&args))
+    }
+
+    /// The locals that `expr` uses without declaring them itself, in the order they're first
+    /// used.
+    pub fn free_locals(&self, expr: &Expr<'s>) -> Vec<Symbol> {
+        let mut used = Vec::new();
+        let mut declared = FxHashSet::default();
+        expr.symbols(&mut used, &mut declared);
+
+        let mut seen = FxHashSet::default();
+        used.into_iter()
+            .filter(|sym| {
+                self.locals.contains_key(sym) && !declared.contains(sym) && seen.insert(*sym)
+            })
+            .collect()
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum Builtin {
-    Alloc,
-    Spec,
-    Print,
-    Println,
-    Input,
-    Itoa,
+/// A function implemented outside of codef, called through the platform's C ABI.
+#[derive(Debug)]
+pub struct Extern<'s> {
+    pub decl_span: Span,
+    pub name: Intern<'s>,
+    /// The shared library to find the function in, or the program itself if `None`.
+    pub library: Option<Intern<'s>>,
+    pub ty: Type<'s>,
 }
 
 #[derive(Debug)]
 pub struct TypeDef<'s> {
     pub decl_span: Span,
     pub name: Intern<'s>,
+    /// The variables that the type's parameters stand for in `inner`.
+    pub params: Box<[TypeVar]>,
     pub inner: Type<'s>,
 }
 
@@ -40,6 +75,10 @@ impl<'s> Type<'s> {
         self.is_subtype(&(Type::Primitive(PrimitiveType::Float)))
     }
 
+    pub fn is_string(&self) -> bool {
+        self.is_subtype(&(Type::Primitive(PrimitiveType::String)))
+    }
+
     pub fn is_bool(&self) -> bool {
         self.is_subtype(&(Type::Primitive(PrimitiveType::Boolean)))
     }
@@ -48,56 +87,134 @@ impl<'s> Type<'s> {
         self.is_subtype(&Type::Tuple(Box::new([])))
     }
 
-    pub fn is_unknown(&self) -> bool {
+    pub fn is_never(&self) -> bool {
+        matches!(self, Type::Primitive(PrimitiveType::Never))
+    }
+
+    /// Whether the type is a tuple, record or variant, which are written the same way as the
+    /// `type`s defined as them.
+    pub fn is_structural(&self) -> bool {
+        matches!(self, Type::Tuple(_) | Type::Record(_) | Type::Variant(_))
+    }
+
+    /// The width and signedness of an integer type, which `Int` is the widest signed one of.
+    pub fn int_type(&self) -> Option<IntType> {
         match self {
-            Type::Unknown => true,
-            _ => false,
+            Type::Primitive(PrimitiveType::Integer) => Some(IntType::INT),
+            &Type::Primitive(PrimitiveType::Sized(int)) => Some(int),
+            _ => None,
         }
     }
 }
 
 impl<'s> Type<'s> {
-    /// Tries to widen with the other type.
+    /// Tries to widen with the other type, into the smallest type that both are subtypes of.
     pub fn widen(&self, other: &Type<'s>) -> Option<Type<'s>> {
-        if let (Type::Variant(a), Type::Variant(b)) = (self, other) {
-            // a, b must be nonoverlapping in names.
-            // if so, we can just concat their variants
-            let mut items = Vec::with_capacity(a.len() + b.len());
-            for a in a.iter() {
-                let mut added = false;
+        match (self, other) {
+            (Type::Primitive(PrimitiveType::Never), ty)
+            | (ty, Type::Primitive(PrimitiveType::Never)) => Some(ty.clone()),
+            (Type::Variant(a), Type::Variant(b)) => {
+                // the union of both, where variants in both must have payloads that widen
+                let mut items = a.to_vec();
                 for b in b.iter() {
-                    if a.name == b.name {
-                        // then their values must be able to widen too...
-                        if let (Some(ain), Some(bin)) = (&a.inner, &b.inner) {
-                            if let Some(win) = ain.widen(bin) {
-                                items.push(VariantItemType {
-                                    name: a.name,
-                                    inner: Some(win),
-                                });
-                                added = true;
-                            } else {
-                                return None;
-                            }
-                        } else {
-                            return None;
-                        }
-                    } else {
-                        items.push(b.clone())
+                    let Some(item) = items.iter_mut().find(|a| a.name == b.name) else {
+                        items.push(b.clone());
+                        continue;
+                    };
+
+                    match (&item.inner, &b.inner) {
+                        (Some(ain), Some(bin)) => item.inner = Some(ain.widen(bin)?),
+                        (None, None) => (),
+                        _ => return None,
                     }
                 }
 
-                if !added {
-                    items.push(a.clone())
+                Some(Type::Variant(items.into_boxed_slice()))
+            }
+            (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => {
+                let items = a.iter().zip(b.iter()).map(|(a, b)| a.widen(b));
+                Some(Type::Tuple(items.collect::<Option<_>>()?))
+            }
+            (Type::Record(a), Type::Record(b)) if Type::same_fields(a, b) => {
+                let fields = a.iter().zip(b.iter()).map(|(a, b)| {
+                    Some(RecordFieldType {
+                        name: a.name,
+                        ty: a.ty.widen(&b.ty)?,
+                    })
+                });
+                Some(Type::Record(fields.collect::<Option<_>>()?))
+            }
+            (Type::Function(aarg, aret), Type::Function(barg, bret)) => {
+                // the widened function can only be given what both accept
+                let arg = match (aarg, barg) {
+                    (Some(aarg), Some(barg)) => Some(Box::new(aarg.narrow(barg)?)),
+                    (None, None) => None,
+                    _ => return None,
+                };
+
+                Some(Type::Function(arg, Box::new(aret.widen(bret)?)))
+            }
+            _ if self.is_subtype(other) => Some(other.clone()),
+            _ if other.is_subtype(self) => Some(self.clone()),
+            _ => None,
+        }
+    }
+
+    /// Tries to narrow with the other type, into the largest type that is a subtype of both.
+    pub fn narrow(&self, other: &Type<'s>) -> Option<Type<'s>> {
+        match (self, other) {
+            (Type::Variant(a), Type::Variant(b)) => {
+                // the variants in both, whose payloads must narrow
+                let mut items = Vec::new();
+                for a in a.iter() {
+                    let Some(b) = b.iter().find(|b| b.name == a.name) else {
+                        continue;
+                    };
+
+                    let inner = match (&a.inner, &b.inner) {
+                        (Some(ain), Some(bin)) => Some(ain.narrow(bin)?),
+                        (None, None) => None,
+                        _ => return None,
+                    };
+                    items.push(VariantItemType {
+                        name: a.name,
+                        inner,
+                    });
+                }
+
+                // no value would have the type
+                if items.is_empty() {
+                    return None;
                 }
+
+                Some(Type::Variant(items.into_boxed_slice()))
             }
+            (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => {
+                let items = a.iter().zip(b.iter()).map(|(a, b)| a.narrow(b));
+                Some(Type::Tuple(items.collect::<Option<_>>()?))
+            }
+            (Type::Record(a), Type::Record(b)) if Type::same_fields(a, b) => {
+                let fields = a.iter().zip(b.iter()).map(|(a, b)| {
+                    Some(RecordFieldType {
+                        name: a.name,
+                        ty: a.ty.narrow(&b.ty)?,
+                    })
+                });
+                Some(Type::Record(fields.collect::<Option<_>>()?))
+            }
+            (Type::Function(aarg, aret), Type::Function(barg, bret)) => {
+                // the narrowed function must accept anything either does
+                let arg = match (aarg, barg) {
+                    (Some(aarg), Some(barg)) => Some(Box::new(aarg.widen(barg)?)),
+                    (None, None) => None,
+                    _ => return None,
+                };
 
-            None
-        } else if self.is_subtype(other) {
-            Some(other.clone())
-        } else if other.is_subtype(self) {
-            Some(self.clone())
-        } else {
-            None
+                Some(Type::Function(arg, Box::new(aret.narrow(bret)?)))
+            }
+            _ if self.is_subtype(other) => Some(self.clone()),
+            _ if other.is_subtype(self) => Some(other.clone()),
+            _ => None,
         }
     }
 
@@ -146,12 +263,153 @@ impl<'s> Type<'s> {
 
                 true
             }
-            (Type::Instance(a), Type::Instance(b)) => a == b,
+            // elements are invariant, since they can both be read and set
+            (Type::Array(a), Type::Array(b)) => a.is_subtype(b) && b.is_subtype(a),
+            (Type::Record(a), Type::Record(b)) => {
+                Type::same_fields(a, b)
+                    && a.iter().zip(b.iter()).all(|(a, b)| a.ty.is_subtype(&b.ty))
+            }
+            (Type::Instance(a, aargs), Type::Instance(b, bargs)) => {
+                // arguments are invariant, since the type may both produce and consume them
+                a == b
+                    && aargs.len() == bargs.len()
+                    && aargs
+                        .iter()
+                        .zip(bargs.iter())
+                        .all(|(a, b)| a.is_subtype(b) && b.is_subtype(a))
+            }
             (Type::Primitive(a), Type::Primitive(b)) => a == b,
-            (_, Type::Unknown) => true,
+            (Type::Var(a), Type::Var(b)) => a == b,
             _ => false,
         }
     }
+
+    /// Rebuilds the type, applying `f` to each of its direct components.
+    pub fn map(&self, f: &mut impl FnMut(&Type<'s>) -> Type<'s>) -> Type<'s> {
+        match self {
+            Type::Function(arg, ret) => Type::Function(
+                arg.as_ref().map(|arg| Box::new(f(arg))),
+                Box::new(f(ret)),
+            ),
+            Type::Variant(items) => Type::Variant(
+                items
+                    .iter()
+                    .map(|item| VariantItemType {
+                        name: item.name,
+                        inner: item.inner.as_ref().map(&mut *f),
+                    })
+                    .collect(),
+            ),
+            Type::Tuple(items) => Type::Tuple(items.iter().map(f).collect()),
+            Type::Array(item) => Type::Array(Box::new(f(item))),
+            Type::Record(fields) => Type::Record(
+                fields
+                    .iter()
+                    .map(|field| RecordFieldType {
+                        name: field.name,
+                        ty: f(&field.ty),
+                    })
+                    .collect(),
+            ),
+            Type::Instance(sym, args) => Type::Instance(*sym, args.iter().map(f).collect()),
+            Type::Var(_) | Type::Primitive(_) => self.clone(),
+        }
+    }
+
+    /// Adds every variable in the type to `out`, in the order they first appear.
+    pub fn vars(&self, out: &mut Vec<TypeVar>) {
+        match self {
+            Type::Var(var) => {
+                if !out.contains(var) {
+                    out.push(*var);
+                }
+            }
+            Type::Function(arg, ret) => {
+                if let Some(arg) = arg {
+                    arg.vars(out);
+                }
+                ret.vars(out);
+            }
+            Type::Array(item) => item.vars(out),
+            Type::Variant(items) => {
+                for item in items.iter() {
+                    if let Some(inner) = &item.inner {
+                        inner.vars(out);
+                    }
+                }
+            }
+            Type::Tuple(items) | Type::Instance(_, items) => {
+                for item in items.iter() {
+                    item.vars(out);
+                }
+            }
+            Type::Record(fields) => {
+                for field in fields.iter() {
+                    field.ty.vars(out);
+                }
+            }
+            Type::Primitive(_) => (),
+        }
+    }
+
+    /// Replaces the variables in the type that `subst` maps.
+    pub fn substitute(&self, subst: &FxHashMap<TypeVar, Type<'s>>) -> Type<'s> {
+        match self {
+            Type::Var(var) => subst.get(var).cloned().unwrap_or_else(|| self.clone()),
+            _ => self.map(&mut |ty| ty.substitute(subst)),
+        }
+    }
+
+    /// Finds what each variable in the type stands for in `instance`, which must be the
+    /// type with its variables substituted.
+    pub fn match_vars(&self, instance: &Type<'s>, subst: &mut FxHashMap<TypeVar, Type<'s>>) {
+        match (self, instance) {
+            (Type::Var(var), _) => {
+                subst.entry(*var).or_insert_with(|| instance.clone());
+            }
+            (Type::Function(aarg, aret), Type::Function(barg, bret)) => {
+                if let (Some(aarg), Some(barg)) = (aarg, barg) {
+                    aarg.match_vars(barg, subst);
+                }
+                aret.match_vars(bret, subst);
+            }
+            (Type::Array(a), Type::Array(b)) => a.match_vars(b, subst),
+            (Type::Variant(a), Type::Variant(b)) => {
+                for a in a.iter() {
+                    let b = b.iter().find(|b| b.name == a.name);
+                    if let (Some(ai), Some(bi)) = (&a.inner, b.and_then(|b| b.inner.as_ref())) {
+                        ai.match_vars(bi, subst);
+                    }
+                }
+            }
+            (Type::Tuple(a), Type::Tuple(b)) | (Type::Instance(_, a), Type::Instance(_, b)) => {
+                for (a, b) in a.iter().zip(b.iter()) {
+                    a.match_vars(b, subst);
+                }
+            }
+            (Type::Record(a), Type::Record(b)) if Type::same_fields(a, b) => {
+                for (a, b) in a.iter().zip(b.iter()) {
+                    a.ty.match_vars(&b.ty, subst);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Whether two records have the same fields, which puts them in the same slots.
+    pub fn same_fields(a: &[RecordFieldType<'s>], b: &[RecordFieldType<'s>]) -> bool {
+        a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.name == b.name)
+    }
+
+    /// The slot of the field `name`, if the type is a record that has it.
+    pub fn field(&self, name: Intern<'s>) -> Option<(usize, &Type<'s>)> {
+        let Type::Record(fields) = self else {
+            return None;
+        };
+
+        let slot = fields.iter().position(|field| field.name == name)?;
+        Some((slot, &fields[slot].ty))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -159,17 +417,62 @@ pub enum Type<'s> {
     Function(Option<Box<Type<'s>>>, Box<Type<'s>>),
     Variant(Box<[VariantItemType<'s>]>),
     Tuple(Box<[Type<'s>]>),
-    Instance(Symbol),
+    /// Named fields, sorted by name so that each one has a fixed slot, like a tuple item.
+    Record(Box<[RecordFieldType<'s>]>),
+    /// Any number of elements of one type, which can be set.
+    Array(Box<Type<'s>>),
+    /// A `type`, applied to its type arguments.
+    Instance(Symbol, Box<[Type<'s>]>),
     Primitive(PrimitiveType),
-    Unknown,
+    /// A type that's being inferred, or one that a generic `def` is quantified over.
+    Var(TypeVar),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypeVar(pub(super) u32);
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum PrimitiveType {
     Float,
     Integer,
+    /// An integer type other than `Int`, like `U8`.
+    Sized(IntType),
     String,
     Boolean,
+    /// The type of expressions that never produce a value, like `break`, which fit wherever
+    /// a value of any type is expected.
+    Never,
+}
+
+/// An integer type of a fixed width, whose values wrap around to stay within it. Each is held
+/// in a word, sign-extended if it's signed and zero-extended if not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IntType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+impl IntType {
+    /// `Int`, or `I64`.
+    pub const INT: IntType = IntType { signed: true, bits: 64 };
+
+    /// Whether an integer literal of `value` can be written for this type. A signed type
+    /// takes the magnitude of its most negative value too, so that it can be negated.
+    pub fn fits(self, value: u64) -> bool {
+        match self.signed {
+            true => value <= 1 << (self.bits - 1),
+            false => self.bits == 64 || value < 1 << self.bits,
+        }
+    }
+
+    /// `word` wrapped around to this type.
+    pub fn wrap(self, word: u64) -> u64 {
+        let shift = 64 - self.bits;
+        match self.signed {
+            true => ((word << shift) as i64 >> shift) as u64,
+            false => word << shift >> shift,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -178,6 +481,12 @@ pub struct VariantItemType<'s> {
     pub inner: Option<Type<'s>>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RecordFieldType<'s> {
+    pub name: Intern<'s>,
+    pub ty: Type<'s>,
+}
+
 #[derive(Debug)]
 pub struct Local<'s> {
     pub decl_span: Span,
@@ -190,6 +499,8 @@ pub struct Local<'s> {
 pub struct Def<'s> {
     pub decl_span: Span,
     pub name: Intern<'s>,
+    /// Whether the def opts out of checked arithmetic.
+    pub unchecked: bool,
     pub body: Expr<'s>,
 }
 
@@ -213,6 +524,114 @@ pub struct Expr<'s> {
     pub kind: ExprKind<'s>,
     pub span: Span,
     pub ty: Type<'s>,
+    pub bt: BindingTime,
+}
+
+impl<'s> Expr<'s> {
+    /// The direct subexpressions of this expression, in evaluation order.
+    pub fn children(&self) -> Vec<&Expr<'s>> {
+        match &self.kind {
+            ExprKind::Scope(scope) => scope.exprs.iter().collect(),
+            ExprKind::Abstract { body, .. } => vec![body],
+            ExprKind::For {
+                init,
+                cond,
+                afterthought,
+                body,
+            } => init
+                .iter()
+                .chain([cond])
+                .chain([body])
+                .chain(afterthought.iter())
+                .map(|e| &**e)
+                .collect(),
+            ExprKind::Case {
+                cond,
+                on_true,
+                on_false,
+            } => [cond, on_true]
+                .into_iter()
+                .chain(on_false.iter())
+                .map(|e| &**e)
+                .collect(),
+            ExprKind::Tuple(items) | ExprKind::Array(items) => items.iter().collect(),
+            ExprKind::Index(array, index) => vec![array, index],
+            ExprKind::Record(fields) => fields.iter().map(|(_, e)| e).collect(),
+            ExprKind::StructuralEq(_, e)
+            | ExprKind::Unary(_, e)
+            | ExprKind::Field(e, _)
+            | ExprKind::Return(e) => vec![e],
+            ExprKind::Binary(_, a, b) | ExprKind::Apply(a, b) => vec![a, b],
+            ExprKind::Variant(_, data) => data.iter().map(|e| &**e).collect(),
+            ExprKind::Constructor(_)
+            | ExprKind::Load(_)
+            | ExprKind::Literal(_)
+            | ExprKind::Break
+            | ExprKind::Continue => vec![],
+        }
+    }
+
+    /// Collects every symbol this expression refers to, and every local its patterns declare.
+    fn symbols(&self, used: &mut Vec<Symbol>, declared: &mut FxHashSet<Symbol>) {
+        match &self.kind {
+            ExprKind::Load(sym) | ExprKind::Constructor(sym) => used.push(*sym),
+            ExprKind::Abstract { arg: Some(arg), .. } => arg.symbols(used, declared),
+            ExprKind::StructuralEq(pat, _) => pat.symbols(used, declared),
+            _ => (),
+        }
+
+        for child in self.children() {
+            child.symbols(used, declared);
+        }
+    }
+}
+
+impl<'s> Pattern<'s> {
+    /// Like [`Expr::symbols`].
+    fn symbols(&self, used: &mut Vec<Symbol>, declared: &mut FxHashSet<Symbol>) {
+        match &self.kind {
+            PatternKind::Apply(a, b) => {
+                a.symbols(used, declared);
+                b.symbols(used, declared);
+            }
+            PatternKind::Variant(_, data) => {
+                if let Some(data) = data {
+                    data.symbols(used, declared);
+                }
+            }
+            PatternKind::Tuple(items) => {
+                for item in items.iter() {
+                    item.symbols(used, declared);
+                }
+            }
+            PatternKind::Record(fields) => {
+                for field in fields.iter().flatten() {
+                    field.symbols(used, declared);
+                }
+            }
+            PatternKind::SetIndex(array, index) => {
+                array.symbols(used, declared);
+                index.symbols(used, declared);
+            }
+            PatternKind::Solve(SolveMarker::Set, sym) | PatternKind::Symbol(sym) => used.push(*sym),
+            PatternKind::Solve(SolveMarker::Val | SolveMarker::Var, sym) => {
+                declared.insert(*sym);
+            }
+        }
+    }
+}
+
+/// When the value of an expression becomes known, relative to specialization.
+///
+/// Only expressions inside the body of a `$` abstraction are ever classified as
+/// [`BindingTime::Static`]; everything else is left [`BindingTime::Dynamic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingTime {
+    /// Depends only on the specialized parameters, so it can be computed once when
+    /// the specialization happens.
+    Static,
+    /// Must be computed by the generated code.
+    Dynamic,
 }
 
 #[derive(Debug)]
@@ -235,6 +654,14 @@ pub enum ExprKind<'s> {
         on_false: Option<Box<Expr<'s>>>,
     },
     Tuple(Box<[Expr<'s>]>),
+    /// Each field's value, along with its slot in the record's type, in the order they're
+    /// written.
+    Record(Box<[(usize, Expr<'s>)]>),
+    /// The field in the given slot of a record.
+    Field(Box<Expr<'s>>, usize),
+    Array(Box<[Expr<'s>]>),
+    /// An element of an array, which is checked to be in its bounds.
+    Index(Box<Expr<'s>>, Box<Expr<'s>>),
     StructuralEq(Box<Pattern<'s>>, Box<Expr<'s>>),
     Binary(BinOp, Box<Expr<'s>>, Box<Expr<'s>>),
     Unary(UnOp, Box<Expr<'s>>),
@@ -243,6 +670,12 @@ pub enum ExprKind<'s> {
     Constructor(Symbol),
     Load(Symbol),
     Literal(Literal<'s>),
+    /// Leaves the innermost loop.
+    Break,
+    /// Goes on to the next iteration of the innermost loop.
+    Continue,
+    /// Leaves the innermost function with the value.
+    Return(Box<Expr<'s>>),
 }
 
 #[derive(Debug)]
@@ -257,6 +690,10 @@ pub enum PatternKind<'s> {
     Apply(Box<Pattern<'s>>, Box<Pattern<'s>>),
     Variant(Intern<'s>, Option<Box<Pattern<'s>>>),
     Tuple(Box<[Pattern<'s>]>),
+    /// A pattern for each slot of the record, if it's matched at all.
+    Record(Box<[Option<Pattern<'s>>]>),
+    /// Stores the value in an element of an array, once the whole pattern has matched.
+    SetIndex(Box<Expr<'s>>, Box<Expr<'s>>),
     Solve(SolveMarker, Symbol),
     Symbol(Symbol),
 }
@@ -267,3 +704,96 @@ pub struct VariantItem<'s> {
     pub value: Option<Box<Expr<'s>>>,
     pub span: Span,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strings::Strings;
+
+    const INT: Type = Type::Primitive(PrimitiveType::Integer);
+    const STRING: Type = Type::Primitive(PrimitiveType::String);
+
+    /// A variant type of `items`, which are names along with their payload types, if any.
+    fn variant<'s>(strings: &'s Strings, items: &[(&str, Option<Type<'s>>)]) -> Type<'s> {
+        let items = items.iter().map(|(name, inner)| VariantItemType {
+            name: strings.intern((*name).into()),
+            inner: inner.clone(),
+        });
+        Type::Variant(items.collect())
+    }
+
+    fn tags<'s>(strings: &'s Strings, names: &[&str]) -> Type<'s> {
+        let items: Vec<_> = names.iter().map(|&name| (name, None)).collect();
+        variant(strings, &items)
+    }
+
+    fn function<'s>(param: Type<'s>, ret: Type<'s>) -> Type<'s> {
+        Type::Function(Some(Box::new(param)), Box::new(ret))
+    }
+
+    #[test]
+    fn variants_widen_to_their_union_without_duplicates() {
+        let strings = Strings::new();
+        let ab = tags(&strings, &["A", "B"]);
+        let bc = tags(&strings, &["B", "C"]);
+
+        assert_eq!(ab.widen(&bc), Some(tags(&strings, &["A", "B", "C"])));
+        assert_eq!(ab.widen(&ab), Some(ab.clone()));
+        assert_eq!(ab.narrow(&bc), Some(tags(&strings, &["B"])));
+        assert_eq!(ab.narrow(&tags(&strings, &["C"])), None);
+    }
+
+    #[test]
+    fn variant_payloads_widen() {
+        let strings = Strings::new();
+        let some = |inner| variant(&strings, &[("Some", Some(inner))]);
+
+        let widened = some(tags(&strings, &["A"])).widen(&some(tags(&strings, &["B"])));
+        assert_eq!(widened, Some(some(tags(&strings, &["A", "B"]))));
+
+        let narrowed = some(tags(&strings, &["A", "B"])).narrow(&some(tags(&strings, &["B"])));
+        assert_eq!(narrowed, Some(some(tags(&strings, &["B"]))));
+
+        // payloads that have nothing in common, or that only one side has, don't widen
+        assert_eq!(some(INT).widen(&some(STRING)), None);
+        assert_eq!(some(INT).widen(&tags(&strings, &["Some"])), None);
+    }
+
+    #[test]
+    fn tuples_of_different_arity_neither_widen_nor_narrow() {
+        let strings = Strings::new();
+        let pair = Type::Tuple(Box::new([INT, tags(&strings, &["A"])]));
+        let other_pair = Type::Tuple(Box::new([INT, tags(&strings, &["B"])]));
+        let triple = Type::Tuple(Box::new([INT, INT, INT]));
+
+        assert_eq!(pair.widen(&triple), None);
+        assert_eq!(pair.narrow(&triple), None);
+        assert_eq!(
+            pair.widen(&other_pair),
+            Some(Type::Tuple(Box::new([INT, tags(&strings, &["A", "B"])])))
+        );
+    }
+
+    #[test]
+    fn function_parameters_are_contravariant() {
+        let strings = Strings::new();
+        let a = function(tags(&strings, &["X", "Y"]), tags(&strings, &["P", "Q"]));
+        let b = function(tags(&strings, &["Y", "Z"]), tags(&strings, &["Q"]));
+
+        // a function either could be must only be given what both accept, and may return
+        // what either returns
+        assert_eq!(
+            a.widen(&b),
+            Some(function(tags(&strings, &["Y"]), tags(&strings, &["P", "Q"])))
+        );
+        // and a function that could stand in for both accepts what either does, and only
+        // returns what both may
+        assert_eq!(
+            a.narrow(&b),
+            Some(function(tags(&strings, &["X", "Y", "Z"]), tags(&strings, &["Q"])))
+        );
+        // a parameter type that has nothing in common with the other leaves no function
+        let c = function(tags(&strings, &["W"]), tags(&strings, &["Q"]));
+        assert_eq!(a.widen(&c), None);
+    }
+}
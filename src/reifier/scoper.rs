@@ -8,6 +8,9 @@ use super::Symbol;
 
 pub struct Scoper<'s> {
     stack: Vec<FxHashMap<Intern<'s>, Symbol>>,
+    /// The top-level items of each module, by the symbol that `use` binds the module to,
+    /// along with whether they're public.
+    namespaces: FxHashMap<Symbol, FxHashMap<Intern<'s>, (Symbol, bool)>>,
     sym_counter: NonZeroUsize,
 }
 
@@ -22,6 +25,28 @@ impl<'s> Scoper<'s> {
         None
     }
 
+    /// Looks up `name` among the top-level items of the module `namespace`, and whether it's
+    /// public.
+    pub fn lookup_in(&self, namespace: Symbol, name: Intern<'s>) -> Option<(Symbol, bool)> {
+        self.namespaces.get(&namespace)?.get(&name).copied()
+    }
+
+    pub fn is_namespace(&self, sym: Symbol) -> bool {
+        self.namespaces.contains_key(&sym)
+    }
+
+    /// Creates an empty namespace for a module's items to be exported into.
+    pub fn new_namespace(&mut self) -> Symbol {
+        let sym = self.new_anonymous_symbol();
+        self.namespaces.insert(sym, FxHashMap::default());
+        sym
+    }
+
+    /// Exports the item `sym`, which is in scope as `name`, from the module `namespace`.
+    pub fn export(&mut self, namespace: Symbol, name: Intern<'s>, sym: Symbol, public: bool) {
+        self.namespaces.get_mut(&namespace).unwrap().insert(name, (sym, public));
+    }
+
     pub fn new_anonymous_symbol(&mut self) -> Symbol {
         let index = self.sym_counter;
         if let Some(incremented) = self.sym_counter.checked_add(1) {
@@ -29,8 +54,7 @@ impl<'s> Scoper<'s> {
         } else {
             panic!("Symbol counter overflowed!")
         }
-        let sym = Symbol(index);
-        sym
+        Symbol(index)
     }
 
     pub fn new_symbol(&mut self, name: Intern<'s>) -> Symbol {
@@ -39,6 +63,11 @@ impl<'s> Scoper<'s> {
         sym
     }
 
+    /// Brings the existing symbol `sym` into the current scope as `name`.
+    pub fn bind(&mut self, name: Intern<'s>, sym: Symbol) {
+        self.stack.last_mut().unwrap().insert(name, sym);
+    }
+
     pub fn push(&mut self) {
         self.stack.push(FxHashMap::default())
     }
@@ -50,6 +79,10 @@ impl<'s> Scoper<'s> {
 
 impl<'s> Default for Scoper<'s> {
     fn default() -> Self {
-        Scoper { stack: vec![], sym_counter: NonZeroUsize::new(1).unwrap() }
+        Scoper {
+            stack: vec![],
+            namespaces: FxHashMap::default(),
+            sym_counter: NonZeroUsize::new(1).unwrap(),
+        }
     }
 }
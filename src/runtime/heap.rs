@@ -0,0 +1,408 @@
+//! A mark-sweep garbage collected heap.
+//!
+//! Every object is preceded by two header words:
+//!
+//! - the *size* word holds the number of data words, plus the `MARKED` and `FREE` bits;
+//! - the *pointer map* has bit `i` set if data word `i` may hold a pointer. Objects with
+//!   more than 63 words can set [`ALL_POINTERS`] instead.
+//!
+//! Addresses handed out point at the first data word, so the headers are invisible to
+//! lowered code. Pointers found in objects and roots are only followed if they are the
+//! address of a live object, which means roots can be scanned conservatively.
+
+use crate::reifier::{self, PrimitiveType, Type};
+
+/// The address of the first word of the heap.
+///
+/// Keeping it away from zero means small integers are never valid addresses.
+pub const HEAP_BASE: u64 = 1 << 32;
+
+/// Set in the pointer map of objects whose every word may hold a pointer.
+pub const ALL_POINTERS: u64 = 1 << 63;
+
+const MARKED: u64 = 1 << 63;
+const FREE: u64 = 1 << 62;
+const SIZE_MASK: u64 = FREE - 1;
+
+const HEADER_WORDS: usize = 2;
+
+/// How many words can be allocated before the first collection.
+const INITIAL_THRESHOLD: usize = 1 << 20;
+
+/// The memory that `Builtin::Alloc` hands out, addressed in bytes but stored as 8-byte words.
+#[derive(Debug)]
+pub struct Heap {
+    words: Vec<u64>,
+    /// Whether each word is the first data word of an allocated object.
+    starts: Vec<bool>,
+    /// Blocks freed by the last collection, as `(header index, total words)`.
+    free: Vec<(usize, usize)>,
+    /// No block in `free` is bigger than this.
+    largest_free: usize,
+    /// Words allocated since the last collection.
+    allocated: usize,
+    threshold: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Heap {
+        Heap {
+            words: Vec::new(),
+            starts: Vec::new(),
+            free: Vec::new(),
+            largest_free: 0,
+            allocated: 0,
+            threshold: INITIAL_THRESHOLD,
+        }
+    }
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap::default()
+    }
+
+    /// Allocates space for `bytes` bytes, rounded up to a whole number of words, whose
+    /// pointers are described by `pointer_map`.
+    pub fn alloc(&mut self, bytes: u64, pointer_map: u64) -> u64 {
+        // empty objects still take a word, so every object has its own address
+        let size = (bytes.div_ceil(8) as usize).max(1);
+        let total = HEADER_WORDS + size;
+
+        let header = match self.take_free(total) {
+            Some(header) => header,
+            None => {
+                let header = self.words.len();
+                self.words.resize(header + total, 0);
+                self.starts.resize(header + total, false);
+                header
+            }
+        };
+
+        self.words[header] = size as u64;
+        self.words[header + 1] = pointer_map;
+        self.words[header + HEADER_WORDS..header + total].fill(0);
+        self.starts[header + HEADER_WORDS] = true;
+        self.allocated += total;
+
+        Self::address(header + HEADER_WORDS)
+    }
+
+    /// Whether enough has been allocated since the last collection that it's worth
+    /// running another one.
+    pub fn should_collect(&self) -> bool {
+        self.allocated >= self.threshold
+    }
+
+    /// Frees every object that isn't reachable from `roots`.
+    pub fn collect(&mut self, roots: impl IntoIterator<Item = u64>) {
+        let mut stack: Vec<usize> = roots.into_iter().filter_map(|r| self.object(r)).collect();
+        while let Some(start) = stack.pop() {
+            let header = start - HEADER_WORDS;
+            if self.words[header] & MARKED != 0 {
+                continue;
+            }
+            self.words[header] |= MARKED;
+
+            let size = (self.words[header] & SIZE_MASK) as usize;
+            let map = self.words[header + 1];
+            for i in 0..size {
+                if map & ALL_POINTERS != 0 || (i < 63 && map & (1 << i) != 0) {
+                    stack.extend(self.object(self.words[start + i]));
+                }
+            }
+        }
+
+        self.sweep();
+    }
+
+    /// Allocates a string holding `bytes`: its length in bytes, followed by the bytes packed
+    /// eight to a word.
+    pub fn alloc_string(&mut self, bytes: &[u8]) -> u64 {
+        let addr = self.alloc(8 + bytes.len() as u64, 0);
+        let start = self.index(addr).unwrap();
+
+        self.words[start] = bytes.len() as u64;
+        for (i, chunk) in bytes.chunks(8).enumerate() {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.words[start + 1 + i] = u64::from_le_bytes(word);
+        }
+
+        addr
+    }
+
+    /// The bytes of the string at `addr`, if there's an object there big enough to be one.
+    pub fn string(&self, addr: u64) -> Option<Vec<u8>> {
+        let start = self.object(addr)?;
+        let size = (self.words[start - HEADER_WORDS] & SIZE_MASK) as usize;
+        let len = self.words[start] as usize;
+        if len.div_ceil(8) >= size {
+            return None;
+        }
+
+        let mut bytes = Vec::with_capacity(len);
+        for word in &self.words[start + 1..start + 1 + len.div_ceil(8)] {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes.truncate(len);
+
+        Some(bytes)
+    }
+
+    /// The data words of the object at `addr`, if there's one there that hasn't been freed.
+    pub fn object_words(&self, addr: u64) -> Option<&[u64]> {
+        let start = self.object(addr)?;
+        let size = (self.words[start - HEADER_WORDS] & SIZE_MASK) as usize;
+        Some(&self.words[start..start + size])
+    }
+
+    pub fn load(&self, addr: u64) -> Option<u64> {
+        self.index(addr).map(|i| self.words[i])
+    }
+
+    pub fn store(&mut self, addr: u64, value: u64) -> Option<()> {
+        let i = self.index(addr)?;
+        self.words[i] = value;
+        Some(())
+    }
+
+    /// Rebuilds the free list from every unmarked object, merging neighbouring blocks.
+    fn sweep(&mut self) {
+        self.free.clear();
+        self.largest_free = 0;
+
+        let mut live = 0;
+        let mut header = 0;
+        while header < self.words.len() {
+            let total = HEADER_WORDS + (self.words[header] & SIZE_MASK) as usize;
+
+            if self.words[header] & MARKED != 0 {
+                self.words[header] &= !MARKED;
+                live += total;
+            } else {
+                self.starts[header + HEADER_WORDS] = false;
+                match self.free.last_mut() {
+                    Some((last, last_total)) if *last + *last_total == header => {
+                        *last_total += total;
+                        self.words[*last] = FREE | (*last_total - HEADER_WORDS) as u64;
+                    }
+                    _ => {
+                        self.words[header] |= FREE;
+                        self.free.push((header, total));
+                    }
+                }
+            }
+
+            header += total;
+        }
+
+        self.largest_free = self.free.iter().map(|&(_, total)| total).max().unwrap_or(0);
+        self.allocated = 0;
+        self.threshold = INITIAL_THRESHOLD.max(2 * live);
+    }
+
+    /// Carves `total` words out of a free block, returning the index of their header.
+    fn take_free(&mut self, total: usize) -> Option<usize> {
+        if total > self.largest_free {
+            return None;
+        }
+
+        // what's left of a block must be big enough for its own header and a data word, like
+        // any other block
+        let i = self.free.iter().rposition(|&(_, free)| {
+            free == total || free > total + HEADER_WORDS
+        })?;
+        let (header, free) = self.free[i];
+
+        if free == total {
+            self.free.swap_remove(i);
+        } else {
+            let rest = header + total;
+            self.words[rest] = FREE | (free - total - HEADER_WORDS) as u64;
+            self.free[i] = (rest, free - total);
+        }
+
+        Some(header)
+    }
+
+    /// The index of the first data word of the object at `addr`, if there is one.
+    fn object(&self, addr: u64) -> Option<usize> {
+        self.index(addr).filter(|&i| self.starts[i])
+    }
+
+    fn address(index: usize) -> u64 {
+        HEAP_BASE + index as u64 * 8
+    }
+
+    fn index(&self, addr: u64) -> Option<usize> {
+        if addr < HEAP_BASE || !addr.is_multiple_of(8) {
+            return None;
+        }
+
+        let i = ((addr - HEAP_BASE) / 8) as usize;
+        if i < self.words.len() {
+            Some(i)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether values of `ty` may be represented by a pointer into the heap.
+pub fn is_pointer(module: &reifier::Module, ty: &Type) -> bool {
+    match ty {
+        Type::Primitive(
+            PrimitiveType::Integer
+            | PrimitiveType::Sized(_)
+            | PrimitiveType::Boolean
+            | PrimitiveType::Float
+            | PrimitiveType::Never,
+        ) => false,
+        // function values are either handles, which keep their own data alive, or closures
+        Type::Function(..) => true,
+        Type::Instance(..) => match module.unfold(ty) {
+            Some(inner) => is_pointer(module, &inner),
+            None => true,
+        },
+        Type::Primitive(PrimitiveType::String)
+        | Type::Tuple(_)
+        | Type::Record(_)
+        | Type::Array(_)
+        | Type::Variant(_) => true,
+        Type::Var(_) => true,
+    }
+}
+
+/// The pointer map of an object whose words hold values of `tys`, in order.
+pub fn pointer_map<'t, 's: 't>(
+    module: &reifier::Module<'s>,
+    tys: impl IntoIterator<Item = &'t Type<'s>>,
+) -> u64 {
+    let mut map = 0;
+    for (i, ty) in tys.into_iter().enumerate() {
+        if is_pointer(module, ty) {
+            map |= if i < 63 { 1 << i } else { ALL_POINTERS };
+        }
+    }
+
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Allocates an object of `words` words, whose words are all pointers if `pointers`.
+    fn object(heap: &mut Heap, words: u64, pointers: bool) -> u64 {
+        heap.alloc(words * 8, if pointers { ALL_POINTERS } else { 0 })
+    }
+
+    #[test]
+    fn collect_frees_what_roots_cant_reach() {
+        let mut heap = Heap::new();
+        let root = object(&mut heap, 2, true);
+        let child = object(&mut heap, 1, false);
+        let garbage = object(&mut heap, 1, true);
+        heap.store(root + 8, child).unwrap();
+        // a pointer from unreachable garbage doesn't keep anything alive
+        heap.store(garbage, root).unwrap();
+
+        heap.collect([root]);
+        assert!(heap.object_words(root).is_some());
+        assert!(heap.object_words(child).is_some());
+        assert!(heap.object_words(garbage).is_none());
+
+        heap.collect([]);
+        assert!(heap.object_words(root).is_none());
+        assert!(heap.object_words(child).is_none());
+    }
+
+    #[test]
+    fn collect_frees_unreachable_cycles() {
+        let mut heap = Heap::new();
+        let a = object(&mut heap, 1, true);
+        let b = object(&mut heap, 1, true);
+        heap.store(a, b).unwrap();
+        heap.store(b, a).unwrap();
+
+        heap.collect([a]);
+        assert!(heap.object_words(b).is_some());
+
+        heap.collect([]);
+        assert!(heap.object_words(a).is_none());
+        assert!(heap.object_words(b).is_none());
+    }
+
+    #[test]
+    fn roots_are_only_followed_to_the_start_of_objects() {
+        let mut heap = Heap::new();
+        let a = object(&mut heap, 2, false);
+        let b = object(&mut heap, 2, false);
+
+        // a word that isn't a pointer, and one into the middle of `b`
+        heap.collect([a, 5, b + 8]);
+        assert!(heap.object_words(a).is_some());
+        assert!(heap.object_words(b).is_none());
+    }
+
+    #[test]
+    fn only_words_in_the_pointer_map_are_followed() {
+        let mut heap = Heap::new();
+        let parent = heap.alloc(16, 0b10);
+        let skipped = object(&mut heap, 1, false);
+        let followed = object(&mut heap, 1, false);
+        heap.store(parent, skipped).unwrap();
+        heap.store(parent + 8, followed).unwrap();
+
+        heap.collect([parent]);
+        assert!(heap.object_words(skipped).is_none());
+        assert!(heap.object_words(followed).is_some());
+    }
+
+    #[test]
+    fn sweep_merges_neighbouring_free_blocks() {
+        let mut heap = Heap::new();
+        let a = object(&mut heap, 1, false);
+        object(&mut heap, 2, false);
+        object(&mut heap, 1, false);
+        let kept = object(&mut heap, 1, false);
+        let last = object(&mut heap, 3, false);
+
+        heap.collect([kept]);
+        // the three blocks before `kept` become one, and the one after it stays on its own
+        assert_eq!(heap.free, [(0, 10), (13, 5)]);
+        assert_eq!(heap.largest_free, 10);
+        assert!(heap.object_words(last).is_none());
+
+        // so the merged block fits an object bigger than any of the ones it was made of
+        assert_eq!(object(&mut heap, 8, false), a);
+    }
+
+    #[test]
+    fn alloc_reuses_free_blocks() {
+        let mut heap = Heap::new();
+        let freed = object(&mut heap, 6, false);
+        let kept = object(&mut heap, 1, false);
+        heap.store(freed, 42).unwrap();
+        heap.collect([kept]);
+        let end = heap.words.len();
+
+        // a smaller object is carved out of the start of the block, leaving the rest free
+        let small = object(&mut heap, 2, false);
+        assert_eq!(small, freed);
+        assert_eq!(heap.object_words(small), Some(&[0, 0][..]));
+        assert_eq!(heap.free, [(4, 4)]);
+
+        // a block of one word would be left after an object of one word, which is too small
+        // to be a block of its own, so what's left is only reused by an object that fits it
+        // exactly
+        let appended = object(&mut heap, 1, false);
+        assert_eq!(heap.words.len(), end + 3);
+        assert_eq!(heap.index(appended), Some(end + HEADER_WORDS));
+        let exact = object(&mut heap, 2, false);
+        assert_eq!(heap.index(exact), Some(4 + HEADER_WORDS));
+        assert!(heap.free.is_empty());
+        assert_eq!(heap.words.len(), end + 3);
+    }
+}
@@ -0,0 +1,219 @@
+//! Conversions between host [`Value`]s and the memory layout used by lowered code.
+//!
+//! Every value takes up one 8-byte word. Integers, booleans and floats are stored in the
+//! word directly; everything else is a pointer into the heap:
+//!
+//! - a tuple points to one word per item, in order;
+//! - a record points to one word per field, in the order of the record type's fields;
+//! - an array points to its length, followed by one word per element;
+//! - a variant points to its id (see [`lowerer::Module::variants`]), followed by a word for
+//!   its payload if it has one;
+//! - a string points to its length in bytes, followed by its UTF-8 bytes packed eight to a
+//!   word.
+//!
+//! Functions can't be passed either way, since a host value can't hold code; converting one
+//! is a [`MarshalError::Unsupported`].
+
+use std::borrow::Cow;
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    lowerer::{self, Value},
+    reifier::{self, PrimitiveType, Type},
+    strings::Intern,
+};
+
+use super::{pointer_map, Heap};
+
+#[derive(Debug)]
+pub enum MarshalError<'s> {
+    /// The value doesn't have the expected type.
+    Mismatch(Type<'s>),
+    /// Values of this type can't be passed between codef and the host.
+    Unsupported(Type<'s>),
+    InvalidAddress(u64),
+    UnknownVariant(u64),
+}
+
+type Result<'s, T> = std::result::Result<T, MarshalError<'s>>;
+
+/// Everything needed to interpret the layout of a program's values.
+pub struct Layout<'m, 's> {
+    pub reified: &'m reifier::Module<'s>,
+    pub variants: &'m FxHashMap<Intern<'s>, u64>,
+}
+
+impl<'m, 's> Layout<'m, 's> {
+    pub fn new(reified: &'m reifier::Module<'s>, lowered: &'m lowerer::Module<'s>) -> Self {
+        Layout {
+            reified,
+            variants: &lowered.variants,
+        }
+    }
+
+    /// Allocates `value` in `heap` as a `ty`, returning the word that represents it.
+    pub fn write(&self, heap: &mut Heap, value: &Value<'s>, ty: &Type<'s>) -> Result<'s, u64> {
+        match (&*self.resolve(ty)?, value) {
+            (Type::Primitive(PrimitiveType::Integer), &Value::Integer(i)) => Ok(i as u64),
+            (Type::Primitive(PrimitiveType::Sized(int)), &Value::Integer(i))
+                if int.wrap(i as u64) == i as u64 =>
+            {
+                Ok(i as u64)
+            }
+            (Type::Primitive(PrimitiveType::Boolean), &Value::Boolean(b)) => Ok(b as u64),
+            (Type::Primitive(PrimitiveType::Float), &Value::Float(f)) => Ok(f.to_bits()),
+            (Type::Primitive(PrimitiveType::String), Value::String(string)) => {
+                Ok(heap.alloc_string(string.as_bytes()))
+            }
+            (Type::Tuple(tys), Value::Tuple(items)) if tys.len() == items.len() => {
+                let addr = heap.alloc(8 * items.len() as u64, pointer_map(self.reified, &**tys));
+                for (i, (item, ty)) in items.iter().zip(tys.iter()).enumerate() {
+                    let word = self.write(heap, item, ty)?;
+                    Self::store(heap, addr + 8 * i as u64, word)?;
+                }
+
+                Ok(addr)
+            }
+            (Type::Record(fields), Value::Record(values)) if fields.len() == values.len() => {
+                let tys = fields.iter().map(|field| &field.ty);
+                let addr = heap.alloc(8 * fields.len() as u64, pointer_map(self.reified, tys));
+                for (i, field) in fields.iter().enumerate() {
+                    let Some((_, value)) = values.iter().find(|(name, _)| *name == field.name)
+                    else {
+                        return Err(MarshalError::Mismatch(ty.clone()));
+                    };
+                    let word = self.write(heap, value, &field.ty)?;
+                    Self::store(heap, addr + 8 * i as u64, word)?;
+                }
+
+                Ok(addr)
+            }
+            (Type::Array(item_ty), Value::Array(items)) => {
+                let len_ty = Type::Primitive(PrimitiveType::Integer);
+                let tys = [&len_ty].into_iter().chain(items.iter().map(|_| &**item_ty));
+                let addr = heap.alloc(8 * (items.len() as u64 + 1), pointer_map(self.reified, tys));
+                Self::store(heap, addr, items.len() as u64)?;
+                for (i, item) in items.iter().enumerate() {
+                    let word = self.write(heap, item, item_ty)?;
+                    Self::store(heap, addr + 8 * (i as u64 + 1), word)?;
+                }
+
+                Ok(addr)
+            }
+            (Type::Variant(tys), Value::Variant(name, data)) => {
+                let Some(item_ty) = tys.iter().find(|it| it.name == *name) else {
+                    return Err(MarshalError::Mismatch(ty.clone()));
+                };
+                // a variant no code ever constructs or matches on has no id
+                let Some(&id) = self.variants.get(name) else {
+                    return Err(MarshalError::Mismatch(ty.clone()));
+                };
+
+                match (&item_ty.inner, data) {
+                    (Some(inner_ty), Some(data)) => {
+                        let word = self.write(heap, data, inner_ty)?;
+                        let map = pointer_map(self.reified, [&Type::Primitive(PrimitiveType::Integer), inner_ty]);
+                        let addr = heap.alloc(16, map);
+                        Self::store(heap, addr, id)?;
+                        Self::store(heap, addr + 8, word)?;
+                        Ok(addr)
+                    }
+                    (None, None) => {
+                        let addr = heap.alloc(8, 0);
+                        Self::store(heap, addr, id)?;
+                        Ok(addr)
+                    }
+                    _ => Err(MarshalError::Mismatch(ty.clone())),
+                }
+            }
+            (Type::Function(..), _) => Err(MarshalError::Unsupported(ty.clone())),
+            _ => Err(MarshalError::Mismatch(ty.clone())),
+        }
+    }
+
+    /// Reads the `ty` represented by `word` back out of `heap`.
+    pub fn read(&self, heap: &Heap, word: u64, ty: &Type<'s>) -> Result<'s, Value<'s>> {
+        match &*self.resolve(ty)? {
+            Type::Primitive(PrimitiveType::Integer | PrimitiveType::Sized(_)) => {
+                Ok(Value::Integer(word as i64))
+            }
+            Type::Primitive(PrimitiveType::Boolean) => Ok(Value::Boolean(word != 0)),
+            Type::Primitive(PrimitiveType::Float) => Ok(Value::Float(f64::from_bits(word))),
+            Type::Primitive(PrimitiveType::String) => {
+                let bytes = heap.string(word).ok_or(MarshalError::InvalidAddress(word))?;
+                match String::from_utf8(bytes) {
+                    Ok(string) => Ok(Value::String(string.into_boxed_str())),
+                    Err(_) => Err(MarshalError::Mismatch(ty.clone())),
+                }
+            }
+            Type::Tuple(tys) => {
+                let mut items = Vec::with_capacity(tys.len());
+                for (i, ty) in tys.iter().enumerate() {
+                    let item = Self::load(heap, word + 8 * i as u64)?;
+                    items.push(self.read(heap, item, ty)?);
+                }
+
+                Ok(Value::Tuple(items.into_boxed_slice()))
+            }
+            Type::Record(fields) => {
+                let mut values = Vec::with_capacity(fields.len());
+                for (i, field) in fields.iter().enumerate() {
+                    let value = Self::load(heap, word + 8 * i as u64)?;
+                    values.push((field.name, self.read(heap, value, &field.ty)?));
+                }
+
+                Ok(Value::Record(values.into_boxed_slice()))
+            }
+            Type::Array(item_ty) => {
+                let len = Self::load(heap, word)?;
+                let mut items = Vec::new();
+                for i in 0..len {
+                    let item = Self::load(heap, word + 8 * (i + 1))?;
+                    items.push(self.read(heap, item, item_ty)?);
+                }
+
+                Ok(Value::Array(items.into_boxed_slice()))
+            }
+            Type::Variant(tys) => {
+                let id = Self::load(heap, word)?;
+                let Some((&name, _)) = self.variants.iter().find(|(_, &v)| v == id) else {
+                    return Err(MarshalError::UnknownVariant(id));
+                };
+                let Some(item_ty) = tys.iter().find(|it| it.name == name) else {
+                    return Err(MarshalError::Mismatch(ty.clone()));
+                };
+
+                let data = if let Some(inner_ty) = &item_ty.inner {
+                    let data = Self::load(heap, word + 8)?;
+                    Some(Box::new(self.read(heap, data, inner_ty)?))
+                } else {
+                    None
+                };
+
+                Ok(Value::Variant(name, data))
+            }
+            _ => Err(MarshalError::Unsupported(ty.clone())),
+        }
+    }
+
+    /// Unfolds instances of `type` definitions into the type they were defined as.
+    fn resolve<'t>(&self, ty: &'t Type<'s>) -> Result<'s, Cow<'t, Type<'s>>> {
+        match ty {
+            Type::Instance(..) => match self.reified.unfold(ty) {
+                Some(inner) => Ok(Cow::Owned(self.resolve(&inner)?.into_owned())),
+                None => Err(MarshalError::Unsupported(ty.clone())),
+            },
+            _ => Ok(Cow::Borrowed(ty)),
+        }
+    }
+
+    fn load(heap: &Heap, addr: u64) -> Result<'s, u64> {
+        heap.load(addr).ok_or(MarshalError::InvalidAddress(addr))
+    }
+
+    fn store(heap: &mut Heap, addr: u64, word: u64) -> Result<'s, ()> {
+        heap.store(addr, word)
+            .ok_or(MarshalError::InvalidAddress(addr))
+    }
+}
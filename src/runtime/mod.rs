@@ -0,0 +1,7 @@
+//! Runtime support shared by everything that executes lowered code.
+
+mod heap;
+mod marshal;
+
+pub use heap::*;
+pub use marshal::*;
@@ -26,6 +26,12 @@ pub struct Strings {
     strings: UnsafeCell<FxHashSet<&'static str>>,
 }
 
+impl Default for Strings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Strings {
     pub fn new() -> Strings {
         Strings {
@@ -33,6 +33,8 @@ pub struct Tokens<'s, R> {
     chars: R,
     pub(crate) strings: &'s Strings,
     peek: Option<Token<'s>>,
+    /// A token that was read along with the one before it.
+    pending: Option<Token<'s>>,
 }
 
 #[derive(Debug, Clone)]
@@ -52,6 +54,8 @@ pub enum TokenKind<'s> {
     /* Keywords */
     Def,
     Use,
+    Pub,
+    Unchecked,
     Val,
     Var,
     Set,
@@ -60,9 +64,13 @@ pub enum TokenKind<'s> {
     Else,
     For,
     In,
+    Break,
+    Continue,
+    Return,
 
     /* Punctuation */
     Dot,
+    DotDot,
     Comma,
     Colon,
     ColonColon,
@@ -72,6 +80,7 @@ pub enum TokenKind<'s> {
     Amp,
     Dollar,
     ThinArrow,
+    LeftArrow,
     FatArrow,
     ColonEqual,
     Equal,
@@ -80,6 +89,8 @@ pub enum TokenKind<'s> {
     Lt,
     GtEq,
     LtEq,
+    GtGt,
+    LtLt,
     Plus,
     Minus,
     Star,
@@ -97,26 +108,32 @@ pub enum TokenKind<'s> {
     OpenBrace,
     CloseBrace,
 
+    Boolean(bool),
     Float(f64),
     Integer(u64),
     Name(Intern<'s>),
     String(Intern<'s>),
 }
 
-impl<'i, 's, R: CharReader> Tokens<'s, R> {
+impl<'s, R: CharReader> Tokens<'s, R> {
     pub fn of(chars: R, strings: &'s Strings) -> Tokens<'s, R> {
         Tokens {
             chars,
             strings,
             peek: None,
+            pending: None,
         }
     }
 
     /// Reads the next token from the input stream.
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<Option<Token<'s>>> {
         if let Some(peek) = self.peek.take() {
             return Ok(Some(peek));
         }
+        if let Some(pending) = self.pending.take() {
+            return Ok(Some(pending));
+        }
 
         while let Some((start, ch)) = self.chars.peek()? {
             return match ch {
@@ -126,6 +143,7 @@ impl<'i, 's, R: CharReader> Tokens<'s, R> {
                 }
                 '.' => self.advance_double(TokenKind::Dot, |ch| match ch {
                     '{' => Some(TokenKind::DotOpenBrace),
+                    '.' => Some(TokenKind::DotDot),
                     _ => None,
                 }),
                 '$' => self.advance_single(TokenKind::Dollar),
@@ -158,12 +176,18 @@ impl<'i, 's, R: CharReader> Tokens<'s, R> {
                 }),
                 '>' => self.advance_double(TokenKind::Gt, |ch| match ch {
                     '=' => Some(TokenKind::GtEq),
+                    '>' => Some(TokenKind::GtGt),
                     _ => None,
                 }),
-                '<' => self.advance_double(TokenKind::Lt, |ch| match ch {
-                    '=' => Some(TokenKind::LtEq),
-                    _ => None,
-                }),
+                '<' => {
+                    let token = self.advance_double(TokenKind::Lt, |ch| match ch {
+                        '=' => Some(TokenKind::LtEq),
+                        '<' => Some(TokenKind::LtLt),
+                        '-' => Some(TokenKind::LeftArrow),
+                        _ => None,
+                    })?;
+                    self.split_left_arrow(token)
+                }
                 '+' => self.advance_single(TokenKind::Plus),
                 '*' => self.advance_single(TokenKind::Star),
                 '%' => self.advance_single(TokenKind::Percent),
@@ -358,6 +382,8 @@ impl<'i, 's, R: CharReader> Tokens<'s, R> {
             kind: match &*name {
                 "def" => TokenKind::Def,
                 "use" => TokenKind::Use,
+                "pub" => TokenKind::Pub,
+                "unchecked" => TokenKind::Unchecked,
                 "val" => TokenKind::Val,
                 "var" => TokenKind::Var,
                 "set" => TokenKind::Set,
@@ -366,6 +392,11 @@ impl<'i, 's, R: CharReader> Tokens<'s, R> {
                 "else" => TokenKind::Else,
                 "for" => TokenKind::For,
                 "in" => TokenKind::In,
+                "break" => TokenKind::Break,
+                "continue" => TokenKind::Continue,
+                "return" => TokenKind::Return,
+                "true" => TokenKind::Boolean(true),
+                "false" => TokenKind::Boolean(false),
                 _ => TokenKind::Name(self.strings.intern(name.into_boxed_str())),
             },
             span: Span { start, end },
@@ -379,13 +410,41 @@ impl<'i, 's, R: CharReader> Tokens<'s, R> {
         }
 
         let mut seen_point = false;
+        let mut seen_exponent = false;
+        let mut range = None;
 
         let mut saver = CharReaderSaver::with_capacity(&mut self.chars, 16);
 
         while let Some((_, ch)) = saver.peek()? {
             if !seen_point && ch == '.' {
+                let Some((point, _)) = saver.next()? else { unreachable!() };
+                // `0..n` is an integer followed by `..`, not a float followed by `.`
+                if let Some((_, '.')) = saver.peek()? {
+                    saver.next()?;
+                    range = Some(Span {
+                        start: point,
+                        end: point + 2,
+                    });
+                    break;
+                }
                 seen_point = true;
-                saver.next()?;
+            } else if matches!(ch, 'e' | 'E') {
+                // an exponent, like `1.5e-3`, which has to have digits
+                let Some((e, _)) = saver.next()? else { unreachable!() };
+                if let Some((_, '+' | '-')) = saver.peek()? {
+                    saver.next()?;
+                }
+                let Some((_, '0'..='9')) = saver.peek()? else {
+                    return Err(TokenizationError {
+                        kind: TokenizationErrorKind::Unexpected,
+                        span: Some(Span { start: e, end: e + 1 }),
+                    });
+                };
+                while let Some((_, '0'..='9')) = saver.peek()? {
+                    saver.next()?;
+                }
+                seen_exponent = true;
+                break;
             } else if !ch.is_ascii_digit() {
                 break;
             } else {
@@ -393,10 +452,17 @@ impl<'i, 's, R: CharReader> Tokens<'s, R> {
             }
         }
 
-        let saved = saver.finish();
+        let mut saved = saver.finish();
+        if let Some(span) = range {
+            saved.truncate(saved.len() - 2);
+            self.pending = Some(Token {
+                kind: TokenKind::DotDot,
+                span,
+            });
+        }
         let end = start + saved.len();
 
-        if seen_point {
+        if seen_point || seen_exponent {
             let Ok(value) = saved.parse::<f64>() else {
                 unreachable!("Compiler bug: Unexpected error from parse::<f64>()")
             };
@@ -428,6 +494,36 @@ impl<'i, 's, R: CharReader> Tokens<'s, R> {
         }))
     }
 
+    /// Splits a `<-` that's followed by a digit into `<` and `-`, since what's received from
+    /// is never a number, so `x<-1` compares `x` to `-1`.
+    fn split_left_arrow(&mut self, token: Option<Token<'s>>) -> Result<Option<Token<'s>>> {
+        let Some(Token {
+            kind: TokenKind::LeftArrow,
+            span,
+        }) = token
+        else {
+            return Ok(token);
+        };
+        let Some((_, '0'..='9')) = self.chars.peek()? else {
+            return Ok(token);
+        };
+
+        self.pending = Some(Token {
+            kind: TokenKind::Minus,
+            span: Span {
+                start: span.start + 1,
+                end: span.end,
+            },
+        });
+        Ok(Some(Token {
+            kind: TokenKind::Lt,
+            span: Span {
+                start: span.start,
+                end: span.start + 1,
+            },
+        }))
+    }
+
     fn advance_double(
         &mut self,
         primary: TokenKind<'s>,
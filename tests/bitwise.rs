@@ -0,0 +1,45 @@
+//! Checks that the bitwise operators parse with the right precedence and compute the right
+//! values for signed and unsigned integers.
+
+mod common;
+
+use codef::Strings;
+use common::{boolean, int, run, tuple};
+
+#[test]
+fn operators() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def main() -> (Int, Int, Int, Int, Int, Int) {
+            (6 & 3, 6 | 3, 6 ^ 3, 1 << 10, 1024 >> 3, 0 - 16 >> 2)
+        }",
+    );
+    assert_eq!(result, tuple([int(2), int(7), int(5), int(1024), int(128), int(-4)]));
+}
+
+#[test]
+fn precedence() {
+    // `|` binds loosest, then `^`, `&` and the shifts, which bind looser than `+`
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def main() -> (Int, Int, Bool) {
+            (1 + 2 << 3 | 1, 12 ^ 10 & 6 | 1, 5 & 1 = 1)
+        }",
+    );
+    assert_eq!(result, tuple([int(25), int(15), boolean(true)]));
+}
+
+#[test]
+fn unsigned() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def main() -> (U8, U8) {
+            val x :: U8 = 200;
+            (x >> 1, x << 1)
+        }",
+    );
+    assert_eq!(result, tuple([int(100), int(144)]));
+}
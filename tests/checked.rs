@@ -0,0 +1,96 @@
+//! Checks that checked arithmetic aborts the program when a signed operation overflows, and
+//! that unchecked arithmetic wraps instead.
+
+mod common;
+
+use codef::{backends::interp::RuntimeError, compile_with, Error, Options, Strings, Value};
+use common::{int, tuple};
+
+/// Compiles `source` with checked arithmetic, and calls its `main`.
+fn run_checked<'s>(strings: &'s Strings, source: &str) -> Result<Value<'s>, Error<'s>> {
+    let mut program = compile_with(strings, source, Options { checked: true }).unwrap();
+    let main = program.def("main").unwrap();
+    program.call(&main, &[])
+}
+
+/// Calls `main` after defining `min` as the most negative `Int`, and checks that `expr`
+/// overflows.
+fn assert_overflows(expr: &str) {
+    let strings = Strings::new();
+    let source = format!(
+        "def min() -> Int => -9223372036854775807 - 1;
+
+        def main() -> Int {{
+            val m = min();
+            {expr}
+        }}"
+    );
+    let result = run_checked(&strings, &source);
+    assert!(
+        matches!(result, Err(Error::Runtime(RuntimeError::Overflow(_)))),
+        "expected `{expr}` to overflow, got {result:?}",
+    );
+}
+
+#[test]
+fn subtraction() {
+    assert_overflows("m - 1");
+}
+
+#[test]
+fn negation() {
+    assert_overflows("-m");
+}
+
+#[test]
+fn division() {
+    assert_overflows("m / -1");
+}
+
+#[test]
+fn remainder() {
+    assert_overflows("m % -1");
+}
+
+#[test]
+fn narrow_negation() {
+    let strings = Strings::new();
+    let result = run_checked(
+        &strings,
+        "def main() -> I8 {
+            val x :: I8 = -127 - 1;
+            -x
+        }",
+    );
+    assert!(matches!(result, Err(Error::Runtime(RuntimeError::Overflow(_)))));
+}
+
+#[test]
+fn in_range() {
+    let strings = Strings::new();
+    let result = run_checked(
+        &strings,
+        "def min() -> Int => -9223372036854775807 - 1;
+
+        def main() -> (Int, Int, Int, Int) {
+            val m = min();
+            (-(m + 1), m / 1, m % 2, 7 % -1)
+        }",
+    );
+    assert_eq!(result.unwrap(), tuple([int(i64::MAX), int(i64::MIN), int(0), int(0)]));
+}
+
+#[test]
+fn unchecked_wraps() {
+    let strings = Strings::new();
+    let result = run_checked(
+        &strings,
+        "def min() -> Int => -9223372036854775807 - 1;
+
+        unchecked def main() -> (Int, Int, Int) {
+            val m = min();
+            (-m, m / -1, m % -1)
+        }",
+    );
+    assert_eq!(result.unwrap(), tuple([int(i64::MIN), int(i64::MIN), int(0)]));
+}
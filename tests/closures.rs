@@ -0,0 +1,145 @@
+//! Checks what closures capture from the functions around them, and that they never hold a
+//! stale copy of a `var`, since they capture the values of locals.
+
+mod common;
+
+use codef::{reifier::ReifyErrorKind, Strings};
+use common::{int, reify_error, run, tuple};
+
+/// Compiles `source`, which must fail because it `set`s the captured `var` called `name`.
+fn assert_set_after_capture(source: &str, name: &str) {
+    let strings = Strings::new();
+    match reify_error(&strings, source) {
+        ReifyErrorKind::SetAfterCapture(var) => assert_eq!(var.0, name),
+        kind => panic!("expected a set after a capture, got {kind:?}"),
+    }
+}
+
+#[test]
+fn set_after_capture() {
+    assert_set_after_capture(
+        "def main() -> Int {
+            var x = 1;
+            val f = ((val y :: Int) => x + y);
+            set x = 10;
+            f(1)
+        }",
+        "x",
+    );
+}
+
+#[test]
+fn set_in_loop_around_capture() {
+    assert_set_after_capture(
+        "def main() -> Int {
+            var f = ((val y :: Int) => y);
+            for var i = 0; i < 3; set i = i + 1 {
+                set f = ((val y :: Int) => i + y);
+            }
+            f(1)
+        }",
+        "i",
+    );
+}
+
+#[test]
+fn set_before_capture() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def main() -> Int {
+            var x = 1;
+            set x = 10;
+            val f = ((val y :: Int) => x + y);
+            f(1)
+        }",
+    );
+    assert_eq!(result, int(11));
+}
+
+#[test]
+fn set_in_iteration_that_declares() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def main() -> Int {
+            var total = 0;
+            for var i = 0; i < 3; set i = i + 1 {
+                var x = i;
+                set x = x * 2;
+                set total = total + ((val y :: Int) => x + y)(1);
+            }
+            total
+        }",
+    );
+    assert_eq!(result, int(9));
+}
+
+#[test]
+fn captures_params_and_locals() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def adder(val n :: Int) -> (Int => Int) {
+            val m = n * 10;
+            ((val x :: Int) => x + n + m)
+        }
+
+        def main() -> Int {
+            val add = adder 2;
+            add(1) + adder(3)(0)
+        }",
+    );
+    assert_eq!(result, int(56));
+}
+
+#[test]
+fn nested_captures() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def main() -> Int {
+            val a = 100;
+            val outer = ((val b :: Int) => ((val c :: Int) => a + b + c));
+            outer(20)(3)
+        }",
+    );
+    assert_eq!(result, int(123));
+}
+
+#[test]
+fn mutated_through_captured_array() {
+    // a value that a closure and its function both change is boxed in an array
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def main() -> (Int, Int) {
+            val count = [0];
+            val tick = (() {
+                set count[0] = count[0] + 1;
+                count[0]
+            });
+            tick();
+            set count[0] = count[0] + 10;
+            (tick(), count[0])
+        }",
+    );
+    assert_eq!(result, tuple([int(12), int(12)]));
+}
+
+#[test]
+fn set_captured() {
+    let strings = Strings::new();
+    let kind = reify_error(
+        &strings,
+        "def main() -> Int {
+            var x = 1;
+            val f = (() {
+                set x = 2;
+                x
+            });
+            f()
+        }",
+    );
+    assert!(matches!(kind, ReifyErrorKind::SetCaptured(name) if name.0 == "x"));
+}
@@ -0,0 +1,58 @@
+//! Helpers shared by the integration tests, which each include this module.
+
+// every test file uses a different subset of the helpers
+#![allow(dead_code)]
+
+use codef::{
+    compile,
+    reifier::{ReifyError, ReifyErrorKind},
+    Error, Strings, Value,
+};
+
+/// Compiles `source` and returns what calling its `main` returns.
+pub fn run<'s>(strings: &'s Strings, source: &str) -> Value<'s> {
+    let mut program = compile(strings, source).unwrap();
+    let main = program.def("main").unwrap();
+    program.call(&main, &[]).unwrap()
+}
+
+/// Compiles `source`, which must fail, and returns why it did.
+pub fn compile_error<'s>(strings: &'s Strings, source: &str) -> Error<'s> {
+    match compile(strings, source) {
+        Ok(_) => panic!("expected compiling to fail, but it succeeded"),
+        Err(err) => err,
+    }
+}
+
+/// Compiles `source`, which must fail to reify, and returns the kind of error.
+pub fn reify_error<'s>(strings: &'s Strings, source: &str) -> ReifyErrorKind<'s> {
+    match compile_error(strings, source) {
+        Error::Reify(ReifyError { kind, .. }) => kind,
+        err => panic!("expected a reify error, got {err:?}"),
+    }
+}
+
+pub fn int(i: i64) -> Value<'static> {
+    Value::Integer(i)
+}
+
+pub fn boolean(b: bool) -> Value<'static> {
+    Value::Boolean(b)
+}
+
+pub fn float(f: f64) -> Value<'static> {
+    Value::Float(f)
+}
+
+pub fn string(s: &str) -> Value<'static> {
+    Value::String(s.into())
+}
+
+pub fn tuple<'s>(items: impl IntoIterator<Item = Value<'s>>) -> Value<'s> {
+    Value::Tuple(items.into_iter().collect())
+}
+
+/// The variant `name` of `strings`, with `data` if it has any.
+pub fn variant<'s>(strings: &'s Strings, name: &str, data: Option<Value<'s>>) -> Value<'s> {
+    Value::Variant(strings.intern(name.into()), data.map(Box::new))
+}
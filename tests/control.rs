@@ -0,0 +1,133 @@
+//! Checks that `break`, `continue` and `return` leave the innermost loop or function, carrying
+//! the variables set before them along.
+
+mod common;
+
+use codef::{reifier::ReifyErrorKind, Strings};
+use common::{int, reify_error, run, tuple};
+
+#[test]
+fn break_for_in() {
+    let strings = Strings::new();
+    let source = "def main() -> (Int, Int) {
+        var sum = 0;
+        var last = 0;
+        for val i in 0..10 {
+            case i = 4 => break;
+            set sum = sum + i;
+            set last = i;
+        };
+        (sum, last)
+    }";
+    assert_eq!(run(&strings, source), tuple([int(6), int(3)]));
+}
+
+#[test]
+fn continue_for_in() {
+    let strings = Strings::new();
+    let source = "def main() -> Int {
+        var sum = 0;
+        for val x in [1, 2, 3, 4, 5] {
+            case x % 2 = 0 => continue;
+            set sum = sum + x;
+        };
+        sum
+    }";
+    assert_eq!(run(&strings, source), int(9));
+}
+
+#[test]
+fn continue_runs_afterthought() {
+    let strings = Strings::new();
+    let source = "def main() -> (Int, Int) {
+        var sum = 0;
+        var n = 0;
+        for var i = 0; i < 6; set i = i + 1 {
+            set n = n + 1;
+            case i = 2 => continue;
+            set sum = sum + i;
+        };
+        (sum, n)
+    }";
+    assert_eq!(run(&strings, source), tuple([int(13), int(6)]));
+}
+
+#[test]
+fn break_leaves_only_innermost_loop() {
+    let strings = Strings::new();
+    let source = "def main() -> Int {
+        var pairs = 0;
+        for val i in 0..3 {
+            for val j in 0..3 {
+                case j > i => break;
+                set pairs = pairs + 1;
+            };
+        };
+        pairs
+    }";
+    assert_eq!(run(&strings, source), int(6));
+}
+
+#[test]
+fn return_from_loop() {
+    let strings = Strings::new();
+    let source = "def find(val xs :: Array Int, val target :: Int) -> Int {
+        for val i in 0..len(xs) {
+            case xs[i] = target => return i;
+        };
+        -1
+    }
+    def main() -> (Int, Int) => (find([5, 7, 9], 9), find([5, 7, 9], 8));";
+    assert_eq!(run(&strings, source), tuple([int(2), int(-1)]));
+}
+
+#[test]
+fn return_leaves_only_innermost_function() {
+    let strings = Strings::new();
+    let source = "def main() -> Int {
+        val clamp = ((val x :: Int) {
+            case x > 10 => return 10;
+            x
+        });
+        clamp(4) + clamp(40)
+    }";
+    assert_eq!(run(&strings, source), int(14));
+}
+
+#[test]
+fn return_without_value() {
+    let strings = Strings::new();
+    let source = "def fill(val xs :: Array Int) {
+        for val i in 0..len(xs) {
+            case i = 2 => return;
+            set xs[i] = 1;
+        };
+    }
+    def main() -> Int {
+        val xs = [0, 0, 0, 0];
+        fill(xs);
+        xs[0] + xs[1] + xs[2] + xs[3]
+    }";
+    assert_eq!(run(&strings, source), int(2));
+}
+
+#[test]
+fn break_outside_loop() {
+    let strings = Strings::new();
+    let kind = reify_error(&strings, "def main() -> Int { break; 1 }");
+    assert!(matches!(kind, ReifyErrorKind::OutsideLoop));
+}
+
+#[test]
+fn continue_in_closure_outside_loop() {
+    let strings = Strings::new();
+    let source = "def main() -> Int {
+        for val i in 0..3 {
+            val f = (() { continue; });
+            f();
+        };
+        1
+    }";
+    let kind = reify_error(&strings, source);
+    assert!(matches!(kind, ReifyErrorKind::OutsideLoop));
+}
@@ -0,0 +1,119 @@
+//! Checks the diagnostics for `case` chains that match on a value: an error for a chain that
+//! misses some of its values, and a warning for an arm that can never be reached.
+
+use std::{path::PathBuf, process::Command};
+
+mod common;
+
+use codef::{reifier::ReifyErrorKind, Strings};
+use common::{int, reify_error, run, tuple};
+
+const COLOR: &str = "type Color \\Red \\Green \\Blue;";
+
+/// The names of the variants that the `case` chain in `source` is missing.
+fn missing(source: &str) -> Vec<String> {
+    let strings = Strings::new();
+    match reify_error(&strings, &format!("{COLOR}\n{source}")) {
+        ReifyErrorKind::NonExhaustiveCase(names) => {
+            names.iter().map(|name| name.0.to_string()).collect()
+        }
+        kind => panic!("expected a non-exhaustive case, got {kind:?}"),
+    }
+}
+
+/// Runs `source` with the `codef` binary, returning the warnings it printed.
+fn warnings(name: &str, source: &str) -> Vec<String> {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.co"));
+    std::fs::write(&path, format!("{COLOR}\n{source}")).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_codef"))
+        .arg("--run")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "running {name} failed: {output:?}");
+
+    String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .filter(|line| line.starts_with("WARNING"))
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn missing_arm() {
+    let names = missing(
+        "def f(val c :: Color) -> Int {
+            case c = Color \\Red => 1;
+            else c = Color \\Green => 2;
+        }
+
+        def main() -> Int => f(Color \\Red);",
+    );
+    assert_eq!(names, ["Blue"]);
+}
+
+#[test]
+fn missing_arms() {
+    let names = missing(
+        "def f(val c :: Color) -> Int {
+            case c = Color \\Green => 1;
+        }
+
+        def main() -> Int => f(Color \\Green);",
+    );
+    assert_eq!(names, ["Red", "Blue"]);
+}
+
+#[test]
+fn complete_chain_needs_no_else() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        &format!(
+            "{COLOR}
+            def f(val c :: Color) -> Int {{
+                case c = Color \\Red => 1;
+                else c = Color \\Green => 2;
+                else c = Color \\Blue => 3;
+            }}
+
+            def main() -> (Int, Int) => (f(Color \\Blue), f(Color \\Green));"
+        ),
+    );
+    assert_eq!(result, tuple([int(3), int(2)]));
+}
+
+#[test]
+fn unreachable_arm() {
+    let warnings = warnings(
+        "unreachable_arm",
+        "def f(val c :: Color) -> Int {
+            case c = Color \\Red => 1;
+            else c = Color \\Green => 2;
+            else c = Color \\Red => 3;
+            else c = Color \\Blue => 4;
+        }
+
+        def main() -> Int => f(Color \\Red);",
+    );
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert!(warnings[0].contains("UnreachableArm"), "{warnings:?}");
+}
+
+#[test]
+fn unreachable_else() {
+    let warnings = warnings(
+        "unreachable_else",
+        "def f(val c :: Color) -> Int {
+            case c = Color \\Red => 1;
+            else c = Color \\Green => 2;
+            else c = Color \\Blue => 3;
+            else => 4;
+        }
+
+        def main() -> Int => f(Color \\Red);",
+    );
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert!(warnings[0].contains("UnreachableArm"), "{warnings:?}");
+}
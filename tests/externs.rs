@@ -0,0 +1,77 @@
+//! Checks calling externs, both ones bound to host functions and native ones, and that a
+//! native one that can't be found is reported by name before anything runs.
+
+use std::{cell::Cell, rc::Rc};
+
+mod common;
+
+use codef::{backends::interp::RuntimeError, compile, Error, Strings, Value};
+use common::{int, run};
+
+#[test]
+fn native() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "use def labs(Int) -> Int;
+
+        def main() -> Int => labs(-5);",
+    );
+    assert_eq!(result, int(5));
+}
+
+#[test]
+fn host() {
+    let strings = Strings::new();
+    let mut program = compile(
+        &strings,
+        "use def triple(Int) -> Int;
+
+        def main() -> Int => triple 4;",
+    )
+    .unwrap();
+    program
+        .define_extern("triple", |args| match args {
+            [Value::Integer(i)] => Value::Integer(i * 3),
+            _ => panic!("expected one integer, got {args:?}"),
+        })
+        .unwrap();
+    let main = program.def("main").unwrap();
+    assert_eq!(program.call(&main, &[]).unwrap(), int(12));
+}
+
+#[test]
+fn unresolved() {
+    let strings = Strings::new();
+    let mut program = compile(
+        &strings,
+        "use def log(Int);
+        use def codef_no_such_function(Int) -> Int;
+
+        def main() -> Int {
+            log 1;
+            codef_no_such_function 2
+        }",
+    )
+    .unwrap();
+    let logged = Rc::new(Cell::new(false));
+    let log = logged.clone();
+    program
+        .define_extern("log", move |_| {
+            log.set(true);
+            Value::Tuple(Box::new([]))
+        })
+        .unwrap();
+    let main = program.def("main").unwrap();
+
+    let result = program.call(&main, &[]);
+    assert!(
+        matches!(
+            &result,
+            Err(Error::Runtime(RuntimeError::UnresolvedExtern(name)))
+                if name == "codef_no_such_function"
+        ),
+        "expected the extern to be unresolved, got {result:?}",
+    );
+    assert!(!logged.get(), "`main` ran before the extern was resolved");
+}
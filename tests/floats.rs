@@ -0,0 +1,80 @@
+//! Checks float literals, arithmetic, conversions and the math builtins, and that comparisons
+//! treat NaN as unordered.
+
+mod common;
+
+use codef::Strings;
+use common::{boolean, float, int, run, string, tuple};
+
+#[test]
+fn literals() {
+    let strings = Strings::new();
+    let source = "def main() -> (Float, Float, Float) => (1.5, 2.5e-3, 1e3);";
+    assert_eq!(run(&strings, source), tuple([float(1.5), float(2.5e-3), float(1e3)]));
+}
+
+#[test]
+fn arithmetic() {
+    let strings = Strings::new();
+    let source = "def main() -> Float => (1.5 + 2.0) * 3.0 / 2.0 - 0.25;";
+    assert_eq!(run(&strings, source), float(5.0));
+}
+
+#[test]
+fn conversions() {
+    let strings = Strings::new();
+    let source = "def main() -> (Float, Int, Int) => (itof 3, ftoi 2.9, ftoi (0.0 - 2.9));";
+    assert_eq!(run(&strings, source), tuple([float(3.0), int(2), int(-2)]));
+}
+
+#[test]
+fn formatting() {
+    let strings = Strings::new();
+    let source = "def main() -> (String, String, Float) =>
+        (ftoa 2.5, ftoa_fixed(3.14159, 2), atof \"0.125\");";
+    assert_eq!(
+        run(&strings, source),
+        tuple([string("2.5"), string("3.14"), float(0.125)])
+    );
+}
+
+#[test]
+fn rounding() {
+    let strings = Strings::new();
+    let source = "def main() -> (Float, Float, Float) => (floor 2.7, ceil 2.1, round 2.5);";
+    assert_eq!(run(&strings, source), tuple([float(2.0), float(3.0), float(3.0)]));
+}
+
+#[test]
+fn math() {
+    let strings = Strings::new();
+    let source = "def main() -> (Float, Float, Float, Float, Float) =>
+        (sqrt 16.0, pow(2.0, 10.0), exp 0.0, log 1.0, atan2(0.0, 1.0));";
+    assert_eq!(
+        run(&strings, source),
+        tuple([float(4.0), float(1024.0), float(1.0), float(0.0), float(0.0)])
+    );
+}
+
+#[test]
+fn trig() {
+    let strings = Strings::new();
+    let source = "def main() -> Int {
+        val pi = 4.0 * atan 1.0;
+        ftoi (round ((sin (pi / 2.0) + cos pi + tan (pi / 4.0)) * 1000.0))
+    }";
+    assert_eq!(run(&strings, source), int(1000));
+}
+
+#[test]
+fn nan() {
+    let strings = Strings::new();
+    let source = "def main() -> (Bool, Bool, Bool, Bool, Bool) {
+        val n = 0.0 / 0.0;
+        (is_nan n, n = n, n < 1.0, n >= 1.0, !(n < 1.0))
+    }";
+    assert_eq!(
+        run(&strings, source),
+        tuple([boolean(true), boolean(false), boolean(false), boolean(false), boolean(true)])
+    );
+}
@@ -0,0 +1,122 @@
+//! Runs `for pattern in iterable` over ranges, arrays and sources, checking the elements are
+//! visited in order and that those which don't match the pattern are skipped.
+
+mod common;
+
+use codef::Strings;
+use common::{int, run, tuple};
+
+#[test]
+fn range() {
+    let strings = Strings::new();
+    let source = "def main() -> Int {
+        var digits = 0;
+        for val i in 1..4 {
+            set digits = digits * 10 + i;
+        };
+        digits
+    }";
+    assert_eq!(run(&strings, source), int(123));
+}
+
+#[test]
+fn empty_range() {
+    let strings = Strings::new();
+    let source = "def main() -> Int {
+        var n = 0;
+        for val i in 3..3 {
+            set n = n + 1;
+        };
+        for val i in 5..2 {
+            set n = n + 1;
+        };
+        n
+    }";
+    assert_eq!(run(&strings, source), int(0));
+}
+
+#[test]
+fn range_of_expressions() {
+    let strings = Strings::new();
+    let source = "def main() -> Int {
+        val xs = [4, 5, 6];
+        var sum = 0;
+        for val i in len(xs) - 2..len(xs) {
+            set sum = sum + xs[i];
+        };
+        sum
+    }";
+    assert_eq!(run(&strings, source), int(11));
+}
+
+#[test]
+fn array() {
+    let strings = Strings::new();
+    let source = "def main() -> Int {
+        var digits = 0;
+        for val x in [3, 1, 2] {
+            set digits = digits * 10 + x;
+        };
+        digits
+    }";
+    assert_eq!(run(&strings, source), int(312));
+}
+
+#[test]
+fn destructures_elements() {
+    let strings = Strings::new();
+    let source = "def main() -> (Int, Int) {
+        var keys = 0;
+        var values = 0;
+        for (val k, val v) in [(1, 10), (2, 20), (3, 30)] {
+            set keys = keys + k;
+            set values = values + v;
+        };
+        (keys, values)
+    }";
+    assert_eq!(run(&strings, source), tuple([int(6), int(60)]));
+}
+
+#[test]
+fn skips_unmatched_elements() {
+    let strings = Strings::new();
+    let source = "type Opt \\Some Int \\None;
+    def main() -> (Int, Int) {
+        var sum = 0;
+        var n = 0;
+        for Opt \\Some (val x) in [Opt \\Some 1, Opt \\None, Opt \\Some 2, Opt \\None] {
+            set sum = sum + x;
+            set n = n + 1;
+        };
+        (sum, n)
+    }";
+    assert_eq!(run(&strings, source), tuple([int(3), int(2)]));
+}
+
+#[test]
+fn source() {
+    let strings = Strings::new();
+    let source = "def main() -> Int {
+        var digits = 0;
+        for val x in list.iter(list.range(1, 4)) {
+            set digits = digits * 10 + x;
+        };
+        digits
+    }";
+    assert_eq!(run(&strings, source), int(123));
+}
+
+#[test]
+fn nested() {
+    let strings = Strings::new();
+    let source = "def main() -> Int {
+        var pairs = 0;
+        for val i in 0..4 {
+            for val j in i..4 {
+                set pairs = pairs + 1;
+            };
+        };
+        pairs
+    }";
+    assert_eq!(run(&strings, source), int(10));
+}
@@ -0,0 +1,127 @@
+//! Loads programs whose modules are written to a fresh directory and checks how they see each
+//! other's items and how cycles between them are reported.
+
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use codef::{
+    compile_file,
+    loader::LoadError,
+    reifier::{ReifyError, ReifyErrorKind},
+    Error, Strings, Value,
+};
+use common::int;
+
+/// Writes each `(name, source)` module to its own file in a directory named after `test`, and
+/// returns the directory.
+fn write_modules(test: &str, modules: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("codef-{test}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for (name, source) in modules {
+        let path = dir.join(format!("{name}.co"));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+    dir
+}
+
+/// Compiles `root` in `dir` and returns what calling its `main` returns.
+fn run<'s>(strings: &'s Strings, dir: &Path, root: &str) -> Result<Value<'s>, Error<'s>> {
+    let result = compile_file(strings, &dir.join(format!("{root}.co")));
+    fs::remove_dir_all(dir).unwrap();
+    let mut program = result?;
+    let main = program.def("main").unwrap();
+    program.call(&main, &[])
+}
+
+/// Compiles `root` in `dir`, which must fail with a cycle, and returns the cycle.
+fn cycle(dir: &Path, root: &str) -> Vec<String> {
+    let strings = Strings::new();
+    let result = compile_file(&strings, &dir.join(format!("{root}.co")));
+    fs::remove_dir_all(dir).unwrap();
+    match result {
+        Err(Error::Load(LoadError::Cycle(cycle))) => cycle.into_vec(),
+        Err(err) => panic!("expected a cycle, got {err:?}"),
+        Ok(_) => panic!("expected a cycle, but it compiled"),
+    }
+}
+
+#[test]
+fn cycle_through_root() {
+    let dir = write_modules(
+        "cycle_through_root",
+        &[
+            ("a", "use b;\ndef main() -> Int => b.f();\npub def g() -> Int => 1;\n"),
+            ("b", "use a;\npub def f() -> Int => a.g();\n"),
+        ],
+    );
+    assert_eq!(cycle(&dir, "a"), ["a", "b", "a"]);
+}
+
+#[test]
+fn cycle_below_root() {
+    let dir = write_modules(
+        "cycle_below_root",
+        &[
+            ("a", "use b;\ndef main() -> Int => b.f();\n"),
+            ("b", "use c;\npub def f() -> Int => c.g();\npub def h() -> Int => 1;\n"),
+            ("c", "use b;\npub def g() -> Int => b.h();\n"),
+        ],
+    );
+    assert_eq!(cycle(&dir, "a"), ["b", "c", "b"]);
+}
+
+#[test]
+fn pub_items_of_nested_module() {
+    let dir = write_modules(
+        "pub_items_of_nested_module",
+        &[
+            (
+                "main",
+                "use geo::point;\n\
+                 def main() -> Int {\n\
+                     val p :: point.Point = .{ x = 3, y = 4 };\n\
+                     point.dist2(p, .{ x = 0, y = 0 })\n\
+                 }\n",
+            ),
+            (
+                "geo/point",
+                "pub type Point .{ x = Int, y = Int };\n\
+                 pub def dist2(val a :: Point, val b :: Point) -> Int =>\n\
+                     square (a.x - b.x) + square (a.y - b.y);\n\
+                 def square(val n :: Int) -> Int => n * n;\n",
+            ),
+        ],
+    );
+    let strings = Strings::new();
+    assert_eq!(run(&strings, &dir, "main").unwrap(), int(25));
+}
+
+#[test]
+fn private_def() {
+    let dir = write_modules(
+        "private_def",
+        &[
+            ("a", "use b;\ndef main() -> Int => b.hidden();\n"),
+            ("b", "def hidden() -> Int => 1;\n"),
+        ],
+    );
+    let strings = Strings::new();
+    let Err(Error::Reify(ReifyError { kind, .. })) = run(&strings, &dir, "a") else {
+        panic!("expected a reify error");
+    };
+    assert!(matches!(kind, ReifyErrorKind::PrivateItem(name) if name.0 == "hidden"));
+}
+
+#[test]
+fn cycle_of_one() {
+    let dir = write_modules(
+        "cycle_of_one",
+        &[("a", "use a;\ndef main() -> Int => 1;\n")],
+    );
+    assert_eq!(cycle(&dir, "a"), ["a", "a"]);
+}
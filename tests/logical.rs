@@ -0,0 +1,70 @@
+//! Checks `&&` and `||`, both as the condition of a `case` and as values, along with the names
+//! that their operands bind.
+
+mod common;
+
+use codef::{reifier::ReifyErrorKind, Strings};
+use common::{boolean, int, reify_error, run, tuple};
+
+#[test]
+fn values() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def main() -> (Bool, Bool, Bool, Bool) {
+            val a = (1 < 2 && 3 < 2);
+            val b = (1 < 2 || 3 < 2);
+            (a, b, a || b, a && b)
+        }",
+    );
+    assert_eq!(result, tuple([boolean(false), boolean(true), boolean(true), boolean(false)]));
+}
+
+#[test]
+fn short_circuit() {
+    // the division by zero would fail if it ran
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def main() -> (Bool, Bool) {
+            (3 < 2 && 1 / 0 = 1, 2 > 1 || 1 / 0 = 1)
+        }",
+    );
+    assert_eq!(result, tuple([boolean(false), boolean(true)]));
+}
+
+#[test]
+fn bindings_of_and() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def check(val o :: option.Option Int) -> Int {
+            case o = option.Option \\Some (val v) && v > 3 => v;
+            else => 0;
+        }
+
+        def main() -> (Int, Int, Int) {
+            (
+                check(option.Option \\Some 5),
+                check(option.Option \\Some 2),
+                check(option.Option \\None),
+            )
+        }",
+    );
+    assert_eq!(result, tuple([int(5), int(0), int(0)]));
+}
+
+#[test]
+fn bindings_of_or() {
+    // `v` isn't bound if only the right operand holds
+    let strings = Strings::new();
+    let kind = reify_error(
+        &strings,
+        "def main() -> Int {
+            val o = option.Option \\Some 1;
+            case o = option.Option \\Some (val v) || 1 < 2 => v;
+            else => 0;
+        }",
+    );
+    assert!(matches!(kind, ReifyErrorKind::UndefinedSymbol(name) if name.0 == "v"));
+}
@@ -0,0 +1,97 @@
+//! Passes host values into codef functions and reads their results back, checking that each
+//! kind of value keeps its shape and that values of the wrong type are rejected.
+
+use codef::{compile, runtime::MarshalError, Error, Strings, Value};
+
+const SOURCE: &str = r#"
+    type Point .{ x = Int, y = Int };
+
+    def flip(val p :: Point) -> Point => .{ x = p.y, y = p.x };
+
+    def sum(val xs :: Array Int) -> Int {
+        var total = 0;
+        for val x in xs {
+            set total = total + x;
+        };
+        total
+    }
+
+    def parities(val n :: Int) -> Array Bool => [n % 2 = 0, (n + 1) % 2 = 0];
+
+    def not(val b :: Bool) -> Bool {
+        case b => false;
+        else => true;
+    }
+
+    def twice(val f :: (Int => Int)) -> Int => f(f 1);
+"#;
+
+#[test]
+fn booleans() {
+    let strings = Strings::new();
+    let mut program = compile(&strings, SOURCE).unwrap();
+    let not = program.def("not").unwrap();
+
+    assert_eq!(program.call(&not, &[Value::Boolean(true)]).unwrap(), Value::Boolean(false));
+    assert_eq!(program.call(&not, &[Value::Boolean(false)]).unwrap(), Value::Boolean(true));
+    // an integer isn't a boolean, even if it's 0 or 1
+    assert!(matches!(
+        program.call(&not, &[Value::Integer(1)]),
+        Err(Error::ArgumentType {
+            index: 0,
+            error: MarshalError::Mismatch(_)
+        })
+    ));
+}
+
+#[test]
+fn records() {
+    let strings = Strings::new();
+    let mut program = compile(&strings, SOURCE).unwrap();
+    let flip = program.def("flip").unwrap();
+    let (x, y) = (strings.intern("x".into()), strings.intern("y".into()));
+
+    // the fields can be given in any order, but come back in the order of the type's
+    let point = Value::Record(Box::new([(y, Value::Integer(2)), (x, Value::Integer(1))]));
+    let flipped = Value::Record(Box::new([(x, Value::Integer(2)), (y, Value::Integer(1))]));
+    assert_eq!(program.call(&flip, &[point]).unwrap(), flipped);
+
+    let missing = Value::Record(Box::new([(x, Value::Integer(1)), (x, Value::Integer(2))]));
+    assert!(matches!(
+        program.call(&flip, &[missing]),
+        Err(Error::ArgumentType {
+            error: MarshalError::Mismatch(_),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn arrays() {
+    let strings = Strings::new();
+    let mut program = compile(&strings, SOURCE).unwrap();
+    let sum = program.def("sum").unwrap();
+    let parities = program.def("parities").unwrap();
+
+    let xs = Value::Array((1..=4).map(Value::Integer).collect());
+    assert_eq!(program.call(&sum, &[xs]).unwrap(), Value::Integer(10));
+    assert_eq!(program.call(&sum, &[Value::Array(Box::new([]))]).unwrap(), Value::Integer(0));
+
+    let result = program.call(&parities, &[Value::Integer(3)]).unwrap();
+    assert_eq!(result, Value::Array(Box::new([Value::Boolean(false), Value::Boolean(true)])));
+}
+
+#[test]
+fn functions_are_unsupported() {
+    let strings = Strings::new();
+    let mut program = compile(&strings, SOURCE).unwrap();
+    let twice = program.def("twice").unwrap();
+
+    assert!(matches!(
+        program.call(&twice, &[Value::Integer(0)]),
+        Err(Error::ArgumentType {
+            index: 0,
+            error: MarshalError::Unsupported(_)
+        })
+    ));
+}
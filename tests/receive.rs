@@ -0,0 +1,42 @@
+//! Checks receiving from a source with `<-`, and that `<` followed by a negative number is
+//! still a comparison.
+
+mod common;
+
+use codef::Strings;
+use common::{boolean, int, run, tuple};
+
+#[test]
+fn drain() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def main() -> Int {
+            val next = list.iter(list.range(1, 4));
+            var total = 0;
+            for val x <- next {
+                set total = total * 10 + x;
+            }
+            total
+        }",
+    );
+    assert_eq!(result, int(123));
+}
+
+#[test]
+fn less_than_negative() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def sign(val x :: Int) -> Int {
+            case x<-1 => -1;
+            else => 1;
+        }
+
+        def main() -> (Int, Int, Bool) {
+            val y = -3;
+            (sign(-5), sign(0), y<-2)
+        }",
+    );
+    assert_eq!(result, tuple([int(-1), int(1), boolean(true)]));
+}
@@ -0,0 +1,58 @@
+//! Checks that specializing a `$` abstraction only precomputes what can't change before the
+//! specialized function runs.
+
+mod common;
+
+use codef::Strings;
+use common::{int, run};
+
+#[test]
+fn array_read() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def first(val xs :: Array Int) $=> (val y :: Int) => xs[0] + y;
+
+        def main() -> Int {
+            val xs = [1, 2];
+            val f = first xs;
+            set xs[0] = 100;
+            f(1)
+        }",
+    );
+    assert_eq!(result, int(101));
+}
+
+#[test]
+fn array_read_in_def() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def head(val xs :: Array Int) -> Int => xs[0];
+
+        def first(val xs :: Array Int) $=> (val y :: Int) => head xs + y;
+
+        def main() -> Int {
+            val xs = [1, 2];
+            val f = first xs;
+            set xs[0] = 100;
+            f(1)
+        }",
+    );
+    assert_eq!(result, int(101));
+}
+
+#[test]
+fn static_arithmetic() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def scale(val n :: Int) $=> (val y :: Int) => n * n + y;
+
+        def main() -> Int {
+            val f = scale 3;
+            f(1) + f(2)
+        }",
+    );
+    assert_eq!(result, int(21));
+}
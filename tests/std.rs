@@ -0,0 +1,198 @@
+//! Compiles small programs against the bundled standard library, calls their `main` and checks
+//! what it returns.
+
+mod common;
+
+use codef::{Strings, Value};
+use common::{boolean, int, run, tuple};
+
+/// Compiles `source`, calls its `main`, which returns a tuple, and compares it to `expected`.
+fn check(source: &str, expected: impl IntoIterator<Item = Value<'static>>) {
+    let strings = Strings::new();
+    assert_eq!(run(&strings, source), tuple(expected));
+}
+
+#[test]
+fn option() {
+    check(
+        r#"
+        def half(val x :: Int) -> option.Option Int {
+            case x % 2 = 0 => option.Option \Some (x / 2);
+            else => option.Option \None;
+        }
+
+        def main() -> (Int, Int, Int, Bool, Bool) {
+            val some = option.Option \Some 4;
+            val none = option.map(((val x :: Int) => x + 1), option.Option \None);
+            (
+                option.unwrap_or(option.map(((val x :: Int) => x * 10), some), 0),
+                option.unwrap_or(none, 7),
+                option.unwrap_or(option.and_then(half, some), 0),
+                option.is_some(option.and_then(half, option.Option \Some 3)),
+                option.is_some(some),
+            )
+        }
+        "#,
+        [int(40), int(7), int(2), boolean(false), boolean(true)],
+    );
+}
+
+#[test]
+fn result() {
+    check(
+        r#"
+        def parse_digit(val s :: String) -> result.Result (Int, String) {
+            case strlen s = 1 => result.Result \Ok (byte_at(s, 0) - 48);
+            else => result.Result \Err "not a digit";
+        }
+
+        def double(val r :: result.Result (Int, String)) -> result.Result (Int, String) =>
+            result.and_then(((val n :: Int) => result.Result \Ok (n * 2)), r);
+
+        def err_or[T, E](val r :: result.Result (T, E), val default :: E) -> E {
+            case r = result.Result \Err (val e) => e;
+            else => default;
+        }
+
+        def main() -> (Int, Int, Int, Bool, Bool, Int, Int) {
+            val ok = parse_digit "7";
+            val err = parse_digit "42";
+            (
+                result.unwrap_or(result.map(((val n :: Int) => n + 1), ok), 0),
+                result.unwrap_or(err, -1),
+                result.unwrap_or(double ok, 0),
+                result.is_ok ok,
+                result.is_ok err,
+                err_or(result.map_err(strlen, err), 0),
+                option.unwrap_or(result.ok err, 5),
+            )
+        }
+        "#,
+        [int(8), int(-1), int(14), boolean(true), boolean(false), int(11), int(5)],
+    );
+}
+
+#[test]
+fn list() {
+    check(
+        r#"
+        def main() -> (Int, Int, Int, Int, Int, Int, Int, Int) {
+            val l = list.range(1, 6);
+            val evens = list.filter(((val x :: Int) => x % 2 = 0), l);
+            val digits = ((val acc :: Int, val x :: Int) => acc * 10 + x);
+            val next = list.iter(list.reverse l);
+            next();
+            (
+                list.length l,
+                list.sum(list.map(((val x :: Int) => x * x), l)),
+                list.sum evens,
+                list.fold(digits, 0, list.append(evens, l)),
+                option.unwrap_or(list.head l, 0),
+                option.unwrap_or(list.nth(l, 3), 0),
+                option.unwrap_or(list.nth(l, 9), -1),
+                option.unwrap_or(next(), 0),
+            )
+        }
+        "#,
+        [int(5), int(55), int(6), int(2412345), int(1), int(4), int(-1), int(4)],
+    );
+}
+
+#[test]
+fn map() {
+    check(
+        r#"
+        def main() -> (Int, Int, Int, Bool, Bool, Int, String) {
+            var m = map.empty();
+            set m = map.insert(m, "b", 2);
+            set m = map.insert(m, "a", 1);
+            set m = map.insert(m, "c", 3);
+            set m = map.insert(m, "a", 10);
+            val removed = map.remove(m, "b");
+            (
+                map.size m,
+                option.unwrap_or(map.get(m, "a"), 0),
+                option.unwrap_or(map.get(m, "z"), -1),
+                map.contains(m, "b"),
+                map.contains(removed, "b"),
+                map.size removed,
+                list.fold(((val acc :: String, val k :: String) => concat(acc, k)), "", map.keys m),
+            )
+        }
+        "#,
+        [int(3), int(10), int(-1), boolean(true), boolean(false), int(2), common::string("abc")],
+    );
+}
+
+#[test]
+fn string() {
+    check(
+        r#"
+        def main() -> (Bool, Bool, Bool, Bool, Int, Int, String, String, String, String, Int) {
+            val parts = string.split("a,bc,,d", ",");
+            (
+                string.starts_with("codef", "co"),
+                string.starts_with("co", "codef"),
+                string.ends_with("codef", "def"),
+                string.contains("codef", "ode"),
+                option.unwrap_or(string.find("abcabc", "c", 3), -1),
+                option.unwrap_or(string.find("abc", "x", 0), -1),
+                string.repeat("ab", 3),
+                string.trim("  \t hi there \n"),
+                string.to_upper "Hello, World",
+                string.to_lower "Hello, World",
+                list.length parts,
+            )
+        }
+        "#,
+        [
+            boolean(true),
+            boolean(false),
+            boolean(true),
+            boolean(true),
+            int(5),
+            int(-1),
+            common::string("ababab"),
+            common::string("hi there"),
+            common::string("HELLO, WORLD"),
+            common::string("hello, world"),
+            int(4),
+        ],
+    );
+}
+
+#[test]
+fn math() {
+    check(
+        r#"
+        def main() -> (Int, Int, Int, Int, Int, Int, Int, Int, Int, Int, Int) {
+            (
+                math.abs(-5),
+                math.sign(-5),
+                math.sign 0,
+                math.min(3, 8),
+                math.max(3, 8),
+                math.clamp(12, 0, 10),
+                math.pow(3, 4),
+                math.gcd(12, 18),
+                math.lcm(4, 6),
+                math.isqrt 99,
+                math.isqrt 100,
+            )
+        }
+        "#,
+        [
+            int(5),
+            int(-1),
+            int(0),
+            int(3),
+            int(8),
+            int(10),
+            int(81),
+            int(6),
+            int(12),
+            int(9),
+            int(10),
+        ],
+    );
+}
@@ -0,0 +1,184 @@
+//! Checks what the type checker accepts and rejects, especially where inference has to relate
+//! a `type` to what it was defined as.
+
+mod common;
+
+use codef::{reifier::ReifyErrorKind, Strings};
+use common::{int, reify_error, run, string, tuple};
+
+#[test]
+fn scope_tail_of_type() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "type Opt \\Some Int \\None;
+
+        def mk() -> Opt {
+            val a = 1;
+            \\Some a
+        }
+
+        def main() -> Int {
+            case mk() = Opt \\Some (val v) => v;
+            else => 0;
+        }",
+    );
+    assert_eq!(result, int(1));
+}
+
+#[test]
+fn scope_tail_of_generic_type() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "type Pair[A, B] (A, B);
+
+        def swap(val p :: Pair(Int, String)) -> Pair(String, Int) {
+            (val a, val b) = p;
+            (b, a)
+        }
+
+        def main() -> (String, Int) {
+            (val s, val n) = swap((1, \"x\"));
+            (s, n)
+        }",
+    );
+    assert_eq!(result, tuple([string("x"), int(1)]));
+}
+
+#[test]
+fn structural_value_as_type() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "type Opt \\Some Int \\None;
+
+        def f(val o :: Opt) -> Int {
+            case o = Opt \\Some (val v) => v;
+            else => 0;
+        }
+
+        def main() -> (Int, Int) {
+            val v = \\Some 1;
+            val n = \\None;
+            (f(v), f(n))
+        }",
+    );
+    assert_eq!(result, tuple([int(1), int(0)]));
+}
+
+#[test]
+fn type_as_structural_value() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "type Opt \\Some Int \\None;
+
+        def f(val o :: \\None \\Some Int) -> Int {
+            case o = \\Some (val v) => v;
+            else => 0;
+        }
+
+        def main() -> (Int, Int) {
+            val o :: Opt = \\Some 4;
+            (f(o), f(Opt \\None))
+        }",
+    );
+    assert_eq!(result, tuple([int(4), int(0)]));
+}
+
+#[test]
+fn var_widens() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def count(val o :: \\None \\Some Int) -> Int {
+            case o = \\Some (val v) => v;
+            else => 0;
+        }
+
+        def main() -> Int {
+            var total = 0;
+            var o = \\None;
+            for var i = 1; i < 4; set i = i + 1 {
+                set total = total + count(o);
+                set o = \\Some i;
+            }
+            total
+        }",
+    );
+    assert_eq!(result, int(3));
+}
+
+#[test]
+fn var_widens_only_where_used() {
+    // `o` was passed where only `\None` fits, so it can't widen to hold `\Some 1`
+    let strings = Strings::new();
+    let kind = reify_error(
+        &strings,
+        "def only_none(val o :: \\None) -> Int => 0;
+
+        def main() -> Int {
+            var o = \\None;
+            only_none(o);
+            set o = \\Some 1;
+            0
+        }",
+    );
+    assert!(matches!(kind, ReifyErrorKind::SetType { name, .. } if name.0 == "o"));
+}
+
+#[test]
+fn set_type() {
+    let strings = Strings::new();
+    let kind = reify_error(
+        &strings,
+        "def main() -> Int {
+            var n = 1;
+            set n = \"x\";
+            n
+        }",
+    );
+    let ReifyErrorKind::SetType {
+        name,
+        declared,
+        assigned,
+    } = kind
+    else {
+        panic!("expected a set of the wrong type, got {kind:?}");
+    };
+    assert_eq!(name.0, "n");
+    assert!(declared.is_int());
+    assert!(assigned.is_string());
+}
+
+#[test]
+fn generic_def_at_two_types() {
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def pair[T](val x :: T) -> (T, T) => (x, x);
+
+        def first(val p) => p[0];
+
+        def main() -> ((Int, Int), (String, String), Int, String) {
+            (pair 1, pair \"a\", first [3, 4], first [\"c\"])
+        }",
+    );
+    let pairs = [tuple([int(1), int(1)]), tuple([string("a"), string("a")])];
+    assert_eq!(result, tuple(pairs.into_iter().chain([int(3), string("c")])));
+}
+
+#[test]
+fn inferred_def_is_generalized() {
+    // `id` declares no type parameters, but nothing constrains its parameter, so it's
+    // generic anyway
+    let strings = Strings::new();
+    let result = run(
+        &strings,
+        "def id(val x) => x;
+
+        def main() -> (Int, String) => (id 2, id \"b\");",
+    );
+    assert_eq!(result, tuple([int(2), string("b")]));
+}